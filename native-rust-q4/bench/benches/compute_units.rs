@@ -0,0 +1,63 @@
+//! # Compute units
+//!
+//! Prints how many compute units every instruction of every program in this repo consumes,
+//! one markdown table per program. Build the programs first, see `native_bench`.
+//!
+//! -- Usage --
+//! > cargo bench                          markdown tables
+//! > cargo bench -- --json                the same numbers as JSON
+//! > cargo bench -- --save baseline.json  write the JSON to a baseline file
+//! > cargo bench -- --check baseline.json fail on anything that got more expensive than the
+//! >                                      baseline, or that stopped succeeding
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let mut json = false;
+    let mut save = None;
+    let mut check = None;
+
+    // Cargo passes `--bench` to every bench target, anything unknown is ignored
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--save" => save = Some(args.next().expect("--save needs a path")),
+            "--check" => check = Some(args.next().expect("--check needs a path")),
+            _ => {}
+        }
+    }
+
+    let report = native_bench::run();
+
+    if json {
+        println!("{:#}", report.json());
+    } else {
+        print!("{}", report.markdown());
+    }
+
+    if let Some(path) = save {
+        report.save(&path).expect("failed to write the baseline");
+        println!("Saved to {path}");
+    }
+
+    if let Some(path) = check {
+        let regressions = match report.check(&path) {
+            Ok(regressions) => regressions,
+            Err(error) => {
+                eprintln!("{error}");
+                return ExitCode::FAILURE;
+            }
+        };
+
+        if !regressions.is_empty() {
+            eprintln!("Regressions against {path}:");
+            for regression in regressions {
+                eprintln!("  {regression}");
+            }
+            return ExitCode::FAILURE;
+        }
+    }
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,11 @@
+#[path = "../program_id.rs"]
+mod program_id;
+
+// The same IDs the programs under measurement get built with
+fn main() {
+    println!("cargo:rerun-if-changed=../program_id.rs");
+
+    for (program, _) in program_id::DEFAULTS {
+        program_id::generate(program, &format!("{program}_program_id.rs"));
+    }
+}
@@ -0,0 +1,185 @@
+//! The pinocchio escrow in `../escrow`.
+
+use mollusk_svm::{program, Mollusk};
+use solana_sdk::{
+    account::AccountSharedData,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+use crate::{token, Measurement};
+
+const PROGRAM_ID: Pubkey =
+    Pubkey::new_from_array(include!(concat!(env!("OUT_DIR"), "/escrow_program_id.rs")));
+
+const ESCROW_LEN: usize = 176;
+
+pub fn measure() -> Vec<Measurement> {
+    let mut mollusk = Mollusk::new(&PROGRAM_ID, "../escrow/target/deploy/native_escrow");
+    mollusk_token::token::add_program(&mut mollusk);
+    let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+    let (system_program, system_program_account) = program::keyed_account_for_system_program();
+
+    let maker = Pubkey::new_unique();
+    let taker = Pubkey::new_unique();
+    let cranker = Pubkey::new_unique();
+    let escrow = Pubkey::new_unique();
+    let vault = Pubkey::new_unique();
+    let (authority, bump) = Pubkey::find_program_address(&[escrow.as_ref()], &PROGRAM_ID);
+    let mint_a = Pubkey::new_unique();
+    let mint_b = Pubkey::new_unique();
+    let maker_ta_a = Pubkey::new_unique();
+    let maker_ta_b = Pubkey::new_unique();
+    let taker_ta_a = Pubkey::new_unique();
+    let taker_ta_b = Pubkey::new_unique();
+
+    let wallet = AccountSharedData::new(1_000_000_000, 0, &Pubkey::default());
+    let terms = |expiry: i64| {
+        [
+            maker_ta_b.as_ref(),
+            mint_a.as_ref(),
+            mint_b.as_ref(),
+            &1_000_000u64.to_le_bytes(),
+            &expiry.to_le_bytes(),
+            Pubkey::default().as_ref(),
+        ]
+        .concat()
+    };
+
+    let mut escrow_account = AccountSharedData::new(
+        mollusk.sysvars.rent.minimum_balance(ESCROW_LEN),
+        ESCROW_LEN,
+        &PROGRAM_ID,
+    );
+    escrow_account.set_data_from_slice(&[maker.as_ref(), &terms(0)].concat());
+    let mut expired_escrow_account = escrow_account.clone();
+    expired_escrow_account.set_data_from_slice(&[maker.as_ref(), &terms(1)].concat());
+    let vault_account = token::account(&mollusk, &token_program, mint_a, authority, 1_000_000);
+
+    let make = mollusk.process_instruction(
+        &Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[&[0], &terms(0)[..]].concat(),
+            vec![
+                AccountMeta::new(maker, true),
+                AccountMeta::new(escrow, true),
+                AccountMeta::new_readonly(mint_a, false),
+                AccountMeta::new_readonly(mint_b, false),
+                AccountMeta::new_readonly(system_program, false),
+            ],
+        ),
+        &[
+            (maker, wallet.clone()),
+            (
+                escrow,
+                AccountSharedData::new(
+                    mollusk.sysvars.rent.minimum_balance(ESCROW_LEN),
+                    ESCROW_LEN,
+                    &PROGRAM_ID,
+                ),
+            ),
+            (mint_a, token::mint(&mollusk, &token_program)),
+            (mint_b, token::mint(&mollusk, &token_program)),
+            (system_program, system_program_account),
+        ],
+    );
+
+    // Fills `amount_b` of the 1_000_000 on offer, a partial fill leaves the escrow open
+    let take = |amount_b: u64| {
+        mollusk.process_instruction(
+            &Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[1, bump][..], &amount_b.to_le_bytes(), &0u64.to_le_bytes()].concat(),
+                vec![
+                    AccountMeta::new(taker, true),
+                    AccountMeta::new(taker_ta_a, false),
+                    AccountMeta::new(taker_ta_b, false),
+                    AccountMeta::new(maker_ta_b, false),
+                    AccountMeta::new(escrow, false),
+                    AccountMeta::new(vault, false),
+                    AccountMeta::new(authority, false),
+                    AccountMeta::new(mint_a, false),
+                    AccountMeta::new_readonly(mint_b, false),
+                    AccountMeta::new_readonly(token_program, false),
+                    AccountMeta::new_readonly(token_program, false),
+                ],
+            ),
+            &[
+                (taker, wallet.clone()),
+                (taker_ta_a, token::account(&mollusk, &token_program, mint_a, taker, 0)),
+                (taker_ta_b, token::account(&mollusk, &token_program, mint_b, taker, 1_000_000)),
+                (maker_ta_b, token::account(&mollusk, &token_program, mint_b, maker, 0)),
+                (escrow, escrow_account.clone()),
+                (vault, vault_account.clone()),
+                (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (mint_a, token::mint(&mollusk, &token_program)),
+                (mint_b, token::mint(&mollusk, &token_program)),
+                (token_program, token_program_account.clone()),
+            ],
+        )
+    };
+    let take_all = take(1_000_000);
+    let take_partial = take(400_000);
+
+    let refund = mollusk.process_instruction(
+        &Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[2, bump],
+            vec![
+                AccountMeta::new(maker, true),
+                AccountMeta::new(maker_ta_a, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new(authority, false),
+                AccountMeta::new(mint_a, false),
+                AccountMeta::new_readonly(token_program, false),
+            ],
+        ),
+        &[
+            (maker, wallet.clone()),
+            (maker_ta_a, token::account(&mollusk, &token_program, mint_a, maker, 0)),
+            (escrow, escrow_account),
+            (vault, vault_account.clone()),
+            (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (mint_a, token::mint(&mollusk, &token_program)),
+            (token_program, token_program_account.clone()),
+        ],
+    );
+
+    // The same escrow, past its expiry, cranked by someone else
+    mollusk.sysvars.clock.unix_timestamp = 1;
+    let refund_expired = mollusk.process_instruction(
+        &Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[3, bump],
+            vec![
+                AccountMeta::new(cranker, true),
+                AccountMeta::new(maker, false),
+                AccountMeta::new(maker_ta_a, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new(authority, false),
+                AccountMeta::new(mint_a, false),
+                AccountMeta::new_readonly(token_program, false),
+            ],
+        ),
+        &[
+            (cranker, wallet.clone()),
+            (maker, wallet),
+            (maker_ta_a, token::account(&mollusk, &token_program, mint_a, maker, 0)),
+            (escrow, expired_escrow_account),
+            (vault, vault_account),
+            (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (mint_a, token::mint(&mollusk, &token_program)),
+            (token_program, token_program_account),
+        ],
+    );
+
+    vec![
+        Measurement::new("escrow", "make", "escrow", &make),
+        Measurement::new("escrow", "take", "escrow", &take_all),
+        Measurement::new("escrow", "take (partial)", "escrow", &take_partial),
+        Measurement::new("escrow", "refund", "escrow", &refund),
+        Measurement::new("escrow", "refund (expired)", "escrow", &refund_expired),
+    ]
+}
@@ -0,0 +1,155 @@
+//! The solana-program escrow in `../escrow_native`.
+
+use mollusk_svm::{program, Mollusk};
+use solana_sdk::{
+    account::AccountSharedData,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+use crate::{token, Measurement};
+
+const PROGRAM_ID: Pubkey =
+    Pubkey::new_from_array(include!(concat!(env!("OUT_DIR"), "/escrow_native_program_id.rs")));
+
+const ESCROW_LEN: usize = 112;
+
+pub fn measure() -> Vec<Measurement> {
+    let mut mollusk = Mollusk::new(&PROGRAM_ID, "../escrow_native/target/deploy/escrow");
+    mollusk_token::token::add_program(&mut mollusk);
+    let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+    let (system_program, system_program_account) = program::keyed_account_for_system_program();
+
+    let maker = Pubkey::new_unique();
+    let taker = Pubkey::new_unique();
+    let (escrow, bump) =
+        Pubkey::find_program_address(&[b"escrow", maker.as_ref()], &PROGRAM_ID);
+    let vault = Pubkey::new_unique();
+    let mint_a = Pubkey::new_unique();
+    let mint_b = Pubkey::new_unique();
+    let maker_ta_a = Pubkey::new_unique();
+    let maker_ta_b = Pubkey::new_unique();
+    let taker_ta_a = Pubkey::new_unique();
+    let taker_ta_b = Pubkey::new_unique();
+
+    let wallet = AccountSharedData::new(1_000_000_000, 0, &Pubkey::default());
+    let mint_a_account = token::mint(&mollusk, &token_program);
+    let mint_b_account = token::mint(&mollusk, &token_program);
+
+    let mut escrow_account = AccountSharedData::new(
+        mollusk.sysvars.rent.minimum_balance(ESCROW_LEN),
+        ESCROW_LEN,
+        &PROGRAM_ID,
+    );
+    escrow_account.set_data_from_slice(
+        &[
+            maker.as_ref(),
+            mint_a.as_ref(),
+            mint_b.as_ref(),
+            &1_000_000u64.to_le_bytes(),
+            &(bump as u64).to_le_bytes(),
+        ]
+        .concat(),
+    );
+
+    // Borsh `Make(EscrowArgs { maker, amount, receive, escrow_bump })`
+    let make = mollusk.process_instruction(
+        &Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[
+                &[0],
+                maker.as_ref(),
+                &1_000_000u64.to_le_bytes(),
+                &1_000_000u64.to_le_bytes(),
+                &[bump],
+            ]
+            .concat(),
+            vec![
+                AccountMeta::new(maker, true),
+                AccountMeta::new_readonly(mint_a, false),
+                AccountMeta::new_readonly(mint_b, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(maker_ta_a, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(token_program, false),
+                AccountMeta::new_readonly(system_program, false),
+            ],
+        ),
+        &[
+            (maker, wallet.clone()),
+            (mint_a, mint_a_account.clone()),
+            (mint_b, mint_b_account.clone()),
+            (escrow, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (maker_ta_a, token::account(&mollusk, &token_program, mint_a, maker, 1_000_000)),
+            (vault, token::account(&mollusk, &token_program, mint_a, escrow, 0)),
+            (token_program, token_program_account.clone()),
+            (system_program, system_program_account.clone()),
+        ],
+    );
+
+    let vault_account = token::account(&mollusk, &token_program, mint_a, escrow, 1_000_000);
+
+    let take = mollusk.process_instruction(
+        &Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[1],
+            vec![
+                AccountMeta::new(taker, true),
+                AccountMeta::new(maker, false),
+                AccountMeta::new_readonly(mint_a, false),
+                AccountMeta::new_readonly(mint_b, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(maker_ta_b, false),
+                AccountMeta::new(taker_ta_a, false),
+                AccountMeta::new(taker_ta_b, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(token_program, false),
+                AccountMeta::new_readonly(system_program, false),
+            ],
+        ),
+        &[
+            (taker, wallet.clone()),
+            (maker, wallet.clone()),
+            (mint_a, mint_a_account.clone()),
+            (mint_b, mint_b_account),
+            (escrow, escrow_account.clone()),
+            (maker_ta_b, token::account(&mollusk, &token_program, mint_b, maker, 0)),
+            (taker_ta_a, token::account(&mollusk, &token_program, mint_a, taker, 0)),
+            (taker_ta_b, token::account(&mollusk, &token_program, mint_b, taker, 1_000_000)),
+            (vault, vault_account.clone()),
+            (token_program, token_program_account.clone()),
+            (system_program, system_program_account.clone()),
+        ],
+    );
+
+    let refund = mollusk.process_instruction(
+        &Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[2],
+            vec![
+                AccountMeta::new(maker, true),
+                AccountMeta::new_readonly(mint_a, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(maker_ta_a, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(token_program, false),
+                AccountMeta::new_readonly(system_program, false),
+            ],
+        ),
+        &[
+            (maker, wallet),
+            (mint_a, mint_a_account),
+            (escrow, escrow_account),
+            (maker_ta_a, token::account(&mollusk, &token_program, mint_a, maker, 0)),
+            (vault, vault_account),
+            (token_program, token_program_account),
+            (system_program, system_program_account),
+        ],
+    );
+
+    vec![
+        Measurement::new("escrow", "make", "escrow_native", &make),
+        Measurement::new("escrow", "take", "escrow_native", &take),
+        Measurement::new("escrow", "refund", "escrow_native", &refund),
+    ]
+}
@@ -0,0 +1,69 @@
+//! # Compute Units
+//!
+//! Runs every instruction of every program in this repo through Mollusk and records how many
+//! compute units it consumes, so the implementations can be compared side by side:
+//! > vault: `native`, `optimized`, `lazy` and `based` backends
+//! > escrow: `escrow_native` (solana-program) and `escrow` (pinocchio)
+//!
+//! Programs are loaded from each crate's `target/deploy`, so build them first. The vault
+//! backends are mutually exclusive features, so each build gets renamed, same as the vault's
+//! differential tests expect:
+//!
+//! ```sh
+//! (cd ../escrow && cargo build-sbf)
+//! (cd ../escrow_native && cargo build-sbf)
+//! cd ../vault
+//! for backend in native optimized lazy based; do
+//!     cargo build-sbf --no-default-features --features $backend
+//!     mv target/deploy/native_vault.so target/deploy/native_vault_$backend.so
+//! done
+//! ```
+//!
+//! Program IDs come from the same `*_PROGRAM_ID` / `*_PROGRAM_KEYPAIR` variables the programs
+//! build with (see `../program_id.rs`), so set them the same way for both.
+
+use mollusk_svm::result::InstructionResult;
+
+mod escrow;
+mod escrow_native;
+mod report;
+#[cfg(test)]
+mod tests;
+mod token;
+mod vault;
+
+pub use report::Report;
+
+/// Compute units consumed by one instruction of one implementation.
+#[derive(Clone, Debug)]
+pub struct Measurement {
+    pub program: &'static str,
+    pub instruction: &'static str,
+    pub implementation: &'static str,
+    pub compute_units: u64,
+    pub success: bool,
+}
+
+impl Measurement {
+    fn new(
+        program: &'static str,
+        instruction: &'static str,
+        implementation: &'static str,
+        result: &InstructionResult,
+    ) -> Self {
+        Self {
+            program,
+            instruction,
+            implementation,
+            compute_units: result.compute_units_consumed,
+            success: !result.program_result.is_err(),
+        }
+    }
+}
+
+/// Measures everything.
+pub fn run() -> Report {
+    Report::new(
+        [vault::measure(), escrow_native::measure(), escrow::measure()].concat(),
+    )
+}
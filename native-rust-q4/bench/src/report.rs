@@ -0,0 +1,142 @@
+use std::{fs, io, path::Path};
+
+use serde_json::{json, Map, Value};
+
+use crate::Measurement;
+
+/// Every measurement of a run, renderable as markdown or JSON and comparable against a
+/// baseline saved from an earlier run.
+pub struct Report {
+    measurements: Vec<Measurement>,
+}
+
+impl Report {
+    pub fn new(measurements: Vec<Measurement>) -> Self {
+        Self { measurements }
+    }
+
+    /// One table per program, one row per instruction and one column per implementation.
+    pub fn markdown(&self) -> String {
+        let mut out = String::new();
+
+        for program in unique(self.measurements.iter().map(|m| m.program)) {
+            let measurements: Vec<&Measurement> =
+                self.measurements.iter().filter(|m| m.program == program).collect();
+            let implementations = unique(measurements.iter().map(|m| m.implementation));
+
+            out += &format!("### {program}\n\n| Instruction |");
+            for implementation in &implementations {
+                out += &format!(" {implementation} |");
+            }
+            out += "\n|---|";
+            out += &"---:|".repeat(implementations.len());
+            out += "\n";
+
+            for instruction in unique(measurements.iter().map(|m| m.instruction)) {
+                out += &format!("| {instruction} |");
+                for implementation in &implementations {
+                    let cell = match measurements
+                        .iter()
+                        .find(|m| m.instruction == instruction && m.implementation == *implementation)
+                    {
+                        Some(m) if m.success => m.compute_units.to_string(),
+                        Some(m) => format!("{} (failed)", m.compute_units),
+                        None => "-".to_string(),
+                    };
+                    out += &format!(" {cell} |");
+                }
+                out += "\n";
+            }
+            out += "\n";
+        }
+
+        out
+    }
+
+    /// `{ program: { instruction: { implementation: compute_units } } }`, with `null` for
+    /// instructions that failed.
+    pub fn json(&self) -> Value {
+        let mut programs = Map::new();
+
+        for m in &self.measurements {
+            let instructions = programs
+                .entry(m.program)
+                .or_insert_with(|| json!({}))
+                .as_object_mut()
+                .unwrap();
+            let implementations = instructions
+                .entry(m.instruction)
+                .or_insert_with(|| json!({}))
+                .as_object_mut()
+                .unwrap();
+            implementations.insert(
+                m.implementation.to_string(),
+                if m.success { json!(m.compute_units) } else { Value::Null },
+            );
+        }
+
+        Value::Object(programs)
+    }
+
+    /// Writes the JSON to `path`, the baseline a later run can `check` against.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, format!("{:#}\n", self.json()))
+    }
+
+    /// The `regressions` against the baseline saved at `path`.
+    pub fn check(&self, path: impl AsRef<Path>) -> Result<Vec<String>, String> {
+        let baseline =
+            fs::read_to_string(path).map_err(|error| format!("failed to read the baseline: {error}"))?;
+        let baseline =
+            serde_json::from_str(&baseline).map_err(|error| format!("the baseline is not valid JSON: {error}"))?;
+
+        Ok(self.regressions(&baseline))
+    }
+
+    /// Every instruction in `baseline` that now fails, is missing, or consumes more compute
+    /// units than it used to. Instructions that aren't in the baseline yet are ignored.
+    pub fn regressions(&self, baseline: &Value) -> Vec<String> {
+        let mut regressions = vec![];
+        let current = self.json();
+
+        let Some(programs) = baseline.as_object() else {
+            return vec!["baseline is not a JSON object".to_string()];
+        };
+
+        for (program, instructions) in programs {
+            for (instruction, implementations) in instructions.as_object().into_iter().flatten() {
+                for (implementation, expected) in implementations.as_object().into_iter().flatten() {
+                    let Some(expected) = expected.as_u64() else {
+                        continue;
+                    };
+                    let name = format!("{program}/{instruction}/{implementation}");
+
+                    match current[program][instruction].get(implementation) {
+                        None => regressions.push(format!("{name}: missing")),
+                        Some(Value::Null) => regressions.push(format!("{name}: failed")),
+                        Some(actual) => {
+                            let actual = actual.as_u64().unwrap();
+                            if actual > expected {
+                                regressions
+                                    .push(format!("{name}: {expected} -> {actual} compute units"));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        regressions
+    }
+}
+
+/// Keeps the first occurrence of every item, in order.
+fn unique<'a>(items: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut seen = vec![];
+    for item in items {
+        if !seen.contains(&item) {
+            seen.push(item);
+        }
+    }
+    seen
+}
@@ -0,0 +1,70 @@
+use serde_json::json;
+
+use crate::{Measurement, Report};
+
+fn measurement(instruction: &'static str, compute_units: u64, success: bool) -> Measurement {
+    Measurement { program: "vault", instruction, implementation: "native", compute_units, success }
+}
+
+fn report(measurements: &[(&'static str, u64, bool)]) -> Report {
+    Report::new(
+        measurements
+            .iter()
+            .map(|&(instruction, compute_units, success)| measurement(instruction, compute_units, success))
+            .collect(),
+    )
+}
+
+#[test]
+fn json() {
+    let report = report(&[("withdraw", 100, true), ("close", 50, false)]);
+
+    assert_eq!(report.json(), json!({ "vault": { "withdraw": { "native": 100 }, "close": { "native": null } } }));
+}
+
+#[test]
+fn regressions_over_the_baseline() {
+    let baseline = json!({ "vault": { "withdraw": { "native": 100 } } });
+
+    // Anything up to the baseline passes, a single compute unit over it doesn't
+    assert!(report(&[("withdraw", 99, true)]).regressions(&baseline).is_empty());
+    assert!(report(&[("withdraw", 100, true)]).regressions(&baseline).is_empty());
+    assert_eq!(
+        report(&[("withdraw", 101, true)]).regressions(&baseline),
+        ["vault/withdraw/native: 100 -> 101 compute units"]
+    );
+
+    assert_eq!(report(&[("withdraw", 90, false)]).regressions(&baseline), ["vault/withdraw/native: failed"]);
+}
+
+#[test]
+fn regressions_for_new_and_missing_cases() {
+    let baseline = json!({ "vault": { "withdraw": { "native": 100 }, "close": { "native": null } } });
+
+    // New cases have nothing to compare against, and neither do ones that failed before
+    assert!(report(&[("withdraw", 100, true), ("set lock", 500, true), ("close", 900, true)])
+        .regressions(&baseline)
+        .is_empty());
+
+    assert_eq!(report(&[("close", 50, true)]).regressions(&baseline), ["vault/withdraw/native: missing"]);
+    assert_eq!(report(&[]).regressions(&json!([])), ["baseline is not a JSON object"]);
+}
+
+#[test]
+fn save_then_check() {
+    let path = std::env::temp_dir().join(format!("native-bench-{}.json", std::process::id()));
+
+    report(&[("withdraw", 100, true), ("close", 50, false)]).save(&path).unwrap();
+
+    assert_eq!(report(&[("withdraw", 100, true), ("close", 40, true)]).check(&path), Ok(vec![]));
+    assert_eq!(
+        report(&[("withdraw", 101, true)]).check(&path),
+        Ok(vec!["vault/withdraw/native: 100 -> 101 compute units".to_string()])
+    );
+
+    std::fs::write(&path, "not json").unwrap();
+    assert!(report(&[]).check(&path).unwrap_err().starts_with("the baseline is not valid JSON"));
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(report(&[]).check(&path).unwrap_err().starts_with("failed to read the baseline"));
+}
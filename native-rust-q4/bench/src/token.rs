@@ -0,0 +1,57 @@
+use mollusk_svm::Mollusk;
+use solana_sdk::{
+    account::{AccountSharedData, WritableAccount},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::state::AccountState;
+
+pub fn mint(mollusk: &Mollusk, token_program: &Pubkey) -> AccountSharedData {
+    let mut account = AccountSharedData::new(
+        mollusk.sysvars.rent.minimum_balance(spl_token::state::Mint::LEN),
+        spl_token::state::Mint::LEN,
+        token_program,
+    );
+    Pack::pack(
+        spl_token::state::Mint {
+            mint_authority: COption::None,
+            supply: 100_000_000_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        account.data_as_mut_slice(),
+    )
+    .unwrap();
+    account
+}
+
+pub fn account(
+    mollusk: &Mollusk,
+    token_program: &Pubkey,
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> AccountSharedData {
+    let mut account = AccountSharedData::new(
+        mollusk.sysvars.rent.minimum_balance(spl_token::state::Account::LEN),
+        spl_token::state::Account::LEN,
+        token_program,
+    );
+    Pack::pack(
+        spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        },
+        account.data_as_mut_slice(),
+    )
+    .unwrap();
+    account
+}
@@ -0,0 +1,236 @@
+use mollusk_svm::{program, Mollusk};
+use solana_sdk::{
+    account::AccountSharedData,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+use crate::Measurement;
+
+const PROGRAM_ID: Pubkey =
+    Pubkey::new_from_array(include!(concat!(env!("OUT_DIR"), "/vault_program_id.rs")));
+
+const BACKENDS: [&str; 4] = ["native", "optimized", "lazy", "based"];
+
+/// `VaultState::LEN`, `Delegate::LEN` and `MultisigConfig::LEN`, see `../vault/src/state.rs`.
+const VAULT_STATE_LEN: usize = 56;
+const DELEGATE_LEN: usize = 48;
+const MULTISIG_CONFIG_LEN: usize = 2 + 32 * 10;
+
+pub fn measure() -> Vec<Measurement> {
+    let signer = Pubkey::new_unique();
+    let (vault, bump) = Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+    let (system_program, system_program_account) = program::keyed_account_for_system_program();
+
+    let signer_account = AccountSharedData::new(1_000_000_000, 0, &Pubkey::default());
+    let vault_account = AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID);
+
+    // More than any instruction takes, which only costs what it takes to notice
+    let extra: Vec<_> = (0..16).map(|_| (Pubkey::new_unique(), AccountSharedData::default())).collect();
+
+    // A vault with one delegate allowed 300_000_000 lamports, that never expires
+    let delegate = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+    let mut delegated = vec![0; VAULT_STATE_LEN + DELEGATE_LEN];
+    delegated[VAULT_STATE_LEN..VAULT_STATE_LEN + 32].copy_from_slice(delegate.as_ref());
+    delegated[VAULT_STATE_LEN + 32..VAULT_STATE_LEN + 40].copy_from_slice(&300_000_000u64.to_le_bytes());
+    let mut delegated_account = AccountSharedData::new(1_000_000_000, delegated.len(), &PROGRAM_ID);
+    delegated_account.set_data_from_slice(&delegated);
+
+    // A 2 of 3 multisig, its config account before and after creation and its vault
+    let members = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let config = Pubkey::new_unique();
+    let (multisig_vault, multisig_bump) =
+        Pubkey::try_find_program_address(&[b"multisig", config.as_ref()], &PROGRAM_ID).unwrap();
+    let member_set = [&[2, members.len() as u8][..], &members.map(|member| member.to_bytes()).concat()].concat();
+    let uncreated_config_account = AccountSharedData::new(1_000_000_000, MULTISIG_CONFIG_LEN, &PROGRAM_ID);
+    let mut config_account = uncreated_config_account.clone();
+    config_account.set_data_from_slice(&[&member_set[..], &vec![0; MULTISIG_CONFIG_LEN - member_set.len()]].concat());
+    let member_accounts: Vec<_> = members.iter().map(|member| (*member, AccountSharedData::default())).collect();
+
+    let deposit = Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &[&[1], &400_000_000u64.to_le_bytes()[..], &[bump], signer.as_ref()].concat(),
+        vec![
+            AccountMeta::new(signer, true),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(system_program, false),
+        ],
+    );
+
+    let cases = [
+        (
+            "withdraw",
+            Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[0], &400_000_000u64.to_le_bytes()[..], &[bump]].concat(),
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            vec![(signer, signer_account.clone()), (vault, vault_account.clone())],
+        ),
+        (
+            "withdraw (too many accounts)",
+            Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[0], &400_000_000u64.to_le_bytes()[..], &[bump]].concat(),
+                [AccountMeta::new(signer, true), AccountMeta::new(vault, false)]
+                    .into_iter()
+                    .chain(extra.iter().map(|(key, _)| AccountMeta::new_readonly(*key, false)))
+                    .collect(),
+            ),
+            [vec![(signer, signer_account.clone()), (vault, vault_account.clone())], extra].concat(),
+        ),
+        (
+            "deposit",
+            deposit.clone(),
+            vec![
+                (signer, signer_account.clone()),
+                (vault, vault_account.clone()),
+                (system_program, system_program_account.clone()),
+            ],
+        ),
+        (
+            // Also assigns the vault to the program
+            "deposit (new vault)",
+            deposit,
+            vec![
+                (signer, signer_account.clone()),
+                (vault, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (system_program, system_program_account.clone()),
+            ],
+        ),
+        (
+            "close",
+            Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[2, bump],
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            vec![(signer, signer_account.clone()), (vault, vault_account.clone())],
+        ),
+        (
+            // Grows an empty vault to hold the lock
+            "set lock",
+            Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[3], &1i64.to_le_bytes()[..], &1u64.to_le_bytes(), &[bump]].concat(),
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            vec![(signer, signer_account.clone()), (vault, vault_account.clone())],
+        ),
+        (
+            "set rate limit",
+            Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[9], &100_000_000u64.to_le_bytes()[..], &[bump]].concat(),
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            vec![(signer, signer_account.clone()), (vault, vault_account.clone())],
+        ),
+        (
+            "set delegate",
+            Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[10], delegate.as_ref(), &300_000_000u64.to_le_bytes(), &0i64.to_le_bytes(), &[bump]].concat(),
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            vec![(signer, signer_account.clone()), (vault, vault_account.clone())],
+        ),
+        (
+            "revoke delegate",
+            Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[11], delegate.as_ref(), &[bump]].concat(),
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            vec![(signer, signer_account.clone()), (vault, delegated_account.clone())],
+        ),
+        (
+            // Tries the owner's PDA first, then finds the delegate
+            "withdraw (delegated)",
+            Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[0], &100_000_000u64.to_le_bytes()[..], &[0]].concat(),
+                vec![
+                    AccountMeta::new(delegate, true),
+                    AccountMeta::new(vault, false),
+                    AccountMeta::new(destination, false),
+                ],
+            ),
+            vec![
+                (delegate, signer_account.clone()),
+                (vault, delegated_account),
+                (destination, AccountSharedData::default()),
+            ],
+        ),
+        (
+            "create multisig",
+            Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[4], &member_set[..]].concat(),
+                vec![AccountMeta::new(config, true)],
+            ),
+            vec![(config, uncreated_config_account)],
+        ),
+        (
+            "rotate members",
+            Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[5], &member_set[..]].concat(),
+                vec![
+                    AccountMeta::new(config, false),
+                    AccountMeta::new_readonly(members[0], true),
+                    AccountMeta::new_readonly(members[1], true),
+                ],
+            ),
+            [vec![(config, config_account.clone())], member_accounts[..2].to_vec()].concat(),
+        ),
+        (
+            "withdraw multisig",
+            Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[6], &400_000_000u64.to_le_bytes()[..], &[multisig_bump]].concat(),
+                vec![
+                    AccountMeta::new_readonly(config, false),
+                    AccountMeta::new(multisig_vault, false),
+                    AccountMeta::new(destination, false),
+                    AccountMeta::new_readonly(system_program, false),
+                    AccountMeta::new_readonly(members[0], true),
+                    AccountMeta::new_readonly(members[1], true),
+                ],
+            ),
+            [
+                vec![
+                    (config, config_account),
+                    (multisig_vault, signer_account.clone()),
+                    (destination, AccountSharedData::default()),
+                    (system_program, system_program_account.clone()),
+                ],
+                member_accounts[..2].to_vec(),
+            ]
+            .concat(),
+        ),
+    ];
+
+    BACKENDS
+        .iter()
+        .flat_map(|backend| {
+            let mollusk = Mollusk::new(
+                &PROGRAM_ID,
+                &format!("../vault/target/deploy/native_vault_{backend}"),
+            );
+
+            cases
+                .iter()
+                .map(|(name, instruction, accounts)| {
+                    Measurement::new(
+                        "vault",
+                        name,
+                        backend,
+                        &mollusk.process_instruction(instruction, accounts),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
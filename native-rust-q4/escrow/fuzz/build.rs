@@ -0,0 +1,8 @@
+#[path = "../../program_id.rs"]
+mod program_id;
+
+// The same ID the escrow under fuzzing gets built with
+fn main() {
+    println!("cargo:rerun-if-changed=../../program_id.rs");
+    program_id::generate("escrow", "program_id.rs");
+}
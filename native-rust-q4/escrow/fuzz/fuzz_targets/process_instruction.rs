@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use native_escrow_fuzz::{run, Case};
+
+fuzz_target!(|case: Case| {
+    run(&case);
+});
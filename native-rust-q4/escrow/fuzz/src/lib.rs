@@ -0,0 +1,360 @@
+//! # Escrow fuzzing
+//!
+//! Drives the escrow's `process_instruction` through Mollusk with arbitrary instruction data
+//! and account sets, and checks what has to hold whatever comes in:
+//! > the program never aborts. A read past the instruction data or an account's data, like a
+//! > failed assert, ends it with `ProgramFailedToComplete` instead of a `ProgramError`
+//! > lamports are conserved
+//! > token balances are conserved, per mint, across every token account passed in and what
+//! > Token-2022 accounts and mints have withheld in transfer fees
+//! > a successful `make` wrote the maker and exactly the bytes it was given, nothing past them
+//! > a successful `take` of a private offer was signed by its taker
+//!
+//! Build the program first, the harness loads it from `../target/deploy`, and Token-2022
+//! from `../tests/fixtures` like the escrow's own tests:
+//!
+//! ```sh
+//! (cd .. && cargo build-sbf)
+//! solana program dump -um TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb ../tests/fixtures/spl_token_2022.so
+//! cargo fuzz run process_instruction
+//! ```
+//!
+//! -- Regressions --
+//! Once `cargo fuzz fmt process_instruction <crash>` shows what a crash in
+//! `artifacts/process_instruction` is, write it as a named test in `tests.rs` that checks what
+//! the program now answers. The crash bytes themselves only decode to that case with the
+//! `Case` they were found with, so they aren't kept.
+
+use std::collections::BTreeMap;
+
+use arbitrary::Arbitrary;
+use mollusk_svm::{
+    program,
+    result::{InstructionResult, ProgramResult},
+    Mollusk,
+};
+use solana_sdk::{
+    account::{AccountSharedData, ReadableAccount},
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests;
+
+pub const PROGRAM_ID: Pubkey = Pubkey::new_from_array(include!(concat!(env!("OUT_DIR"), "/program_id.rs")));
+
+/// Plain keys cases pick from, few enough that accounts keep running into each other.
+const KEYS: u8 = 8;
+
+const TOKEN_2022_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Where an escrow keeps the only key that can take it.
+const TAKER_OFFSET: usize = 144;
+
+thread_local! {
+    static MOLLUSK: Mollusk = {
+        let mut mollusk = Mollusk::new(&PROGRAM_ID, concat!(env!("CARGO_MANIFEST_DIR"), "/../target/deploy/native_escrow"));
+        mollusk_token::token::add_program(&mut mollusk);
+        mollusk.add_program(
+            &TOKEN_2022_PROGRAM_ID,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/../tests/fixtures/spl_token_2022"),
+            &program::loader_keys::LOADER_V3,
+        );
+        mollusk
+    };
+}
+
+/// One instruction and the accounts it runs with, in the order the instruction lists them.
+#[derive(Arbitrary, Clone, Debug)]
+pub struct Case {
+    pub data: Data,
+    pub accounts: Vec<Account>,
+}
+
+#[derive(Arbitrary, Clone, Debug)]
+pub enum Data {
+    /// Anything at all
+    Raw(Vec<u8>),
+    /// A well-formed `make`, private to `taker` if there is one
+    Make { maker_ta_b: u8, mint_a: u8, mint_b: u8, amount_b: u64, expiry: i64, taker: Option<u8> },
+    /// `discriminator` and the canonical bump of the authority of `escrow`, which gets `refund`
+    /// and `refund_expired` as far as their CPIs
+    Bump { discriminator: u8, escrow: u8 },
+    /// A well-formed `take` of `amount_b` from `escrow`, signed with its canonical bump
+    Take { escrow: u8, amount_b: u64, min_received: u64 },
+}
+
+#[derive(Arbitrary, Clone, Debug)]
+pub struct Account {
+    /// See `key`
+    pub key: u8,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub lamports: u32,
+    pub state: State,
+}
+
+#[derive(Arbitrary, Clone, Debug)]
+pub enum State {
+    /// A system account without data
+    Empty,
+    /// An escrow of ours
+    Escrow { maker: u8, maker_ta_b: u8, mint_a: u8, mint_b: u8, amount_b: u64, expiry: i64, taker: Option<u8> },
+    /// An initialized token account
+    Token { mint: u8, owner: u8, amount: u64 },
+    /// An initialized mint of the legacy token program
+    Mint { decimals: u8 },
+    /// Any data, owned by anyone
+    Raw { owner: Owner, data: Vec<u8> },
+    /// The system program, whatever `key` says
+    SystemProgram,
+    /// The token program, whatever `key` says
+    TokenProgram,
+    /// Token-2022, whatever `key` says
+    Token2022Program,
+}
+
+#[derive(Arbitrary, Clone, Copy, Debug)]
+pub enum Owner {
+    System,
+    Escrow,
+    Token,
+    /// Token-2022, its accounts and their extensions only ever come as arbitrary data
+    Token2022,
+}
+
+/// Key `index` of the pool: `KEYS` plain keys, then the authority of each of them, seeded by
+/// it the way the authority of an escrow is.
+pub fn key(index: u8) -> Pubkey {
+    match index % (2 * KEYS) {
+        index if index < KEYS => Pubkey::new_from_array([index + 1; 32]),
+        index => authority(index - KEYS).0,
+    }
+}
+
+/// The Taker an escrow stores for `taker`, all zeroes for a public offer.
+fn taker_key(taker: Option<u8>) -> Pubkey {
+    taker.map_or(Pubkey::default(), key)
+}
+
+fn authority(escrow: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[key(escrow % KEYS).as_ref()], &PROGRAM_ID)
+}
+
+impl Data {
+    pub fn bytes(&self) -> Vec<u8> {
+        match *self {
+            Data::Raw(ref data) => data.clone(),
+            Data::Make { maker_ta_b, mint_a, mint_b, amount_b, expiry, taker } => [
+                &[0][..],
+                key(maker_ta_b).as_ref(),
+                key(mint_a).as_ref(),
+                key(mint_b).as_ref(),
+                &amount_b.to_le_bytes(),
+                &expiry.to_le_bytes(),
+                taker_key(taker).as_ref(),
+            ]
+            .concat(),
+            Data::Bump { discriminator, escrow } => vec![discriminator, authority(escrow).1],
+            Data::Take { escrow, amount_b, min_received } => {
+                [&[1, authority(escrow).1][..], &amount_b.to_le_bytes(), &min_received.to_le_bytes()].concat()
+            }
+        }
+    }
+}
+
+impl Account {
+    pub fn keyed(&self) -> (Pubkey, AccountSharedData) {
+        let lamports = self.lamports as u64;
+
+        let (owner, data) = match self.state {
+            State::Empty => (Pubkey::default(), vec![]),
+            State::Escrow { maker, maker_ta_b, mint_a, mint_b, amount_b, expiry, taker } => (
+                PROGRAM_ID,
+                [
+                    key(maker).as_ref(),
+                    key(maker_ta_b).as_ref(),
+                    key(mint_a).as_ref(),
+                    key(mint_b).as_ref(),
+                    &amount_b.to_le_bytes(),
+                    &expiry.to_le_bytes(),
+                    taker_key(taker).as_ref(),
+                ]
+                .concat(),
+            ),
+            State::Token { mint, owner, amount } => {
+                let mut data = vec![0; TokenAccount::LEN];
+                let token_account = TokenAccount {
+                    mint: key(mint),
+                    owner: key(owner),
+                    amount,
+                    state: AccountState::Initialized,
+                    ..Default::default()
+                };
+                TokenAccount::pack(token_account, &mut data).unwrap();
+
+                (spl_token::ID, data)
+            }
+            State::Mint { decimals } => {
+                let mut data = vec![0; Mint::LEN];
+                Mint::pack(Mint { decimals, is_initialized: true, ..Default::default() }, &mut data).unwrap();
+
+                (spl_token::ID, data)
+            }
+            State::Raw { owner, ref data } => {
+                let owner = match owner {
+                    Owner::System => Pubkey::default(),
+                    Owner::Escrow => PROGRAM_ID,
+                    Owner::Token => spl_token::ID,
+                    Owner::Token2022 => TOKEN_2022_PROGRAM_ID,
+                };
+
+                (owner, data.clone())
+            }
+            State::SystemProgram => return program::keyed_account_for_system_program(),
+            State::TokenProgram => return mollusk_token::token::keyed_account(),
+            State::Token2022Program => {
+                return (TOKEN_2022_PROGRAM_ID, program::create_program_account_loader_v3(&TOKEN_2022_PROGRAM_ID))
+            }
+        };
+
+        let mut account = AccountSharedData::new(lamports, data.len(), &owner);
+        account.set_data_from_slice(&data);
+
+        (key(self.key), account)
+    }
+}
+
+/// Runs `case` and panics on anything that must never happen, whether or not the program
+/// accepted the instruction. Returns what the program made of it, for the named cases in
+/// `tests.rs` to check.
+pub fn run(case: &Case) -> InstructionResult {
+    let mut accounts: Vec<(Pubkey, AccountSharedData)> = vec![];
+    let mut metas = vec![];
+
+    for account in &case.accounts {
+        let (key, shared) = account.keyed();
+
+        // The first account with a key is the one that counts, later ones only repeat its key
+        if !accounts.iter().any(|(existing, _)| *existing == key) {
+            accounts.push((key, shared));
+        }
+        metas.push(AccountMeta { pubkey: key, is_signer: account.is_signer, is_writable: account.is_writable });
+    }
+
+    let instruction = Instruction::new_with_bytes(PROGRAM_ID, &case.data.bytes(), metas);
+    let result = MOLLUSK.with(|mollusk| mollusk.process_instruction(&instruction, &accounts));
+
+    check(&instruction, &accounts, &result);
+
+    result
+}
+
+fn check(instruction: &Instruction, accounts: &[(Pubkey, AccountSharedData)], result: &InstructionResult) {
+    assert_ne!(
+        result.program_result,
+        ProgramResult::UnknownError(InstructionError::ProgramFailedToComplete),
+        "the program aborted",
+    );
+
+    // A failed instruction doesn't change anything
+    if result.program_result.is_err() {
+        return;
+    }
+
+    let after = &result.resulting_accounts;
+    assert_eq!(lamports(accounts), lamports(after), "lamports weren't conserved");
+    assert_eq!(token_balances(accounts), token_balances(after), "token balances weren't conserved");
+
+    if instruction.data.first() == Some(&0) {
+        let maker = instruction.accounts[0].pubkey;
+        let escrow = result.get_account(&instruction.accounts[1].pubkey).unwrap();
+
+        assert_eq!(escrow.data(), [maker.as_ref(), &instruction.data[1..]].concat(), "make wrote something else");
+    }
+
+    if instruction.data.first() == Some(&1) {
+        let taker = &instruction.accounts[0];
+        let escrow = accounts.iter().find(|(key, _)| *key == instruction.accounts[4].pubkey).unwrap();
+        // Escrows from before Taker are shorter, and public
+        if let Some(allowed) = escrow.1.data().get(TAKER_OFFSET..TAKER_OFFSET + 32) {
+            if allowed != Pubkey::default().as_ref() {
+                assert!(allowed == taker.pubkey.as_ref() && taker.is_signer, "someone else took a private offer");
+            }
+        }
+    }
+}
+
+fn lamports(accounts: &[(Pubkey, AccountSharedData)]) -> u128 {
+    accounts.iter().map(|(_, account)| account.lamports() as u128).sum()
+}
+
+/// Tokens held per mint, by every initialized token account in `accounts`. A transfer fee
+/// moves tokens out of what a Token-2022 account holds into what it withheld, and a harvest
+/// moves those on to the mint, so both count too.
+fn token_balances(accounts: &[(Pubkey, AccountSharedData)]) -> BTreeMap<Pubkey, u128> {
+    let mut balances = BTreeMap::new();
+
+    for (key, account) in accounts {
+        let data = account.data();
+
+        if account.owner() == &spl_token::ID {
+            if let Ok(token_account) = TokenAccount::unpack(data) {
+                *balances.entry(token_account.mint).or_default() += token_account.amount as u128;
+            }
+            continue;
+        }
+        if account.owner() != &TOKEN_2022_PROGRAM_ID {
+            continue;
+        }
+
+        // Token-2022 keeps the legacy layouts, an extended one gets padded to an account's
+        // length and then an AccountType, 1 for a mint and 2 for a token account
+        match (data.len(), data.get(TokenAccount::LEN)) {
+            (TokenAccount::LEN, _) | (_, Some(2)) => {
+                if let Ok(token_account) = TokenAccount::unpack(&data[..TokenAccount::LEN]) {
+                    // TransferFeeAmount: [withheld 8]
+                    let withheld = extension(data, 2).and_then(|value| value.get(0..8));
+                    *balances.entry(token_account.mint).or_default() +=
+                        token_account.amount as u128 + withheld.map_or(0, le_u64) as u128;
+                }
+            }
+            (_, Some(1)) if Mint::unpack(&data[..Mint::LEN]).is_ok() => {
+                // TransferFeeConfig: [authorities 64][withheld 8][older 18][newer 18]
+                if let Some(withheld) = extension(data, 1).and_then(|value| value.get(64..72)) {
+                    *balances.entry(*key).or_default() += le_u64(withheld) as u128;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    balances
+}
+
+/// The value of the extension of type `extension_type` of an extended Token-2022 account, if
+/// it has one and its TLV holds together that far.
+fn extension(data: &[u8], extension_type: u16) -> Option<&[u8]> {
+    let mut tlv = data.get(TokenAccount::LEN + 1..)?;
+
+    while let [t0, t1, l0, l1, rest @ ..] = tlv {
+        let length = u16::from_le_bytes([*l0, *l1]) as usize;
+        let value = rest.get(..length)?;
+
+        match u16::from_le_bytes([*t0, *t1]) {
+            0 => return None,
+            found if found == extension_type => return Some(value),
+            _ => tlv = &rest[length..],
+        }
+    }
+
+    None
+}
+
+fn le_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes.try_into().unwrap())
+}
@@ -0,0 +1,247 @@
+#[cfg(test)]
+mod tests {
+    use mollusk_svm::result::{InstructionResult, ProgramResult};
+    use native_escrow::error::EscrowError;
+    use solana_sdk::{account::ReadableAccount, program_error::ProgramError, program_pack::Pack};
+    use spl_token::state::Account as TokenAccount;
+
+    use crate::{key, run, Account, Case, Data, Owner, State, KEYS};
+
+    fn account(key: u8, is_signer: bool, state: State) -> Account {
+        Account { key, is_signer, is_writable: true, lamports: 1_000_000_000, state }
+    }
+
+    fn escrow(maker: u8) -> State {
+        State::Escrow { maker, maker_ta_b: 4, mint_a: 5, mint_b: 6, amount_b: 1_000_000, expiry: 0, taker: None }
+    }
+
+    fn failure(error: EscrowError) -> ProgramResult {
+        ProgramResult::Failure(ProgramError::Custom(error.code()))
+    }
+
+    fn token_amount(result: &InstructionResult, index: u8) -> u64 {
+        TokenAccount::unpack(result.get_account(&key(index)).unwrap().data()).unwrap().amount
+    }
+
+    #[test]
+    fn make_with_short_data() {
+        // Used to copy 104 bytes whatever the length of the data
+        let result = run(&Case {
+            data: Data::Raw(vec![0, 1, 2, 3]),
+            accounts: vec![
+                account(0, true, State::Empty),
+                account(1, true, State::Raw { owner: Owner::Escrow, data: vec![0; 176] }),
+                account(5, false, State::Mint { decimals: 6 }),
+                account(6, false, State::Mint { decimals: 6 }),
+                account(2, false, State::SystemProgram),
+            ],
+        });
+
+        assert_eq!(result.program_result, ProgramResult::Failure(ProgramError::InvalidInstructionData));
+    }
+
+    #[test]
+    fn make_into_a_short_escrow() {
+        // Used to write 136 bytes into however much the escrow had
+        let result = run(&Case {
+            data: Data::Make { maker_ta_b: 4, mint_a: 5, mint_b: 6, amount_b: 1_000_000, expiry: 0, taker: None },
+            accounts: vec![
+                account(0, true, State::Empty),
+                account(1, true, State::Raw { owner: Owner::Escrow, data: vec![0; 8] }),
+                account(5, false, State::Mint { decimals: 6 }),
+                account(6, false, State::Mint { decimals: 6 }),
+                account(2, false, State::SystemProgram),
+            ],
+        });
+
+        assert_eq!(result.program_result, failure(EscrowError::InvalidEscrowData));
+    }
+
+    #[test]
+    fn make_with_an_unsigned_escrow() {
+        let result = run(&Case {
+            data: Data::Make { maker_ta_b: 4, mint_a: 5, mint_b: 6, amount_b: 1_000_000, expiry: 0, taker: None },
+            accounts: vec![
+                account(0, true, State::Empty),
+                account(1, false, State::Raw { owner: Owner::Escrow, data: vec![0; 176] }),
+                account(5, false, State::Mint { decimals: 6 }),
+                account(6, false, State::Mint { decimals: 6 }),
+                account(2, false, State::SystemProgram),
+            ],
+        });
+
+        assert_eq!(result.program_result, failure(EscrowError::MissingSignature));
+    }
+
+    #[test]
+    fn take_without_a_bump() {
+        // Used to index past the end of the data
+        let result = run(&Case {
+            data: Data::Raw(vec![1]),
+            accounts: vec![
+                account(0, true, State::Empty),
+                account(1, false, State::Token { mint: 5, owner: 0, amount: 0 }),
+                account(2, false, State::Token { mint: 6, owner: 0, amount: 1_000_000 }),
+                account(4, false, State::Token { mint: 6, owner: 3, amount: 0 }),
+                account(7, false, escrow(3)),
+                account(3, false, State::Token { mint: 5, owner: KEYS + 7, amount: 1_000_000 }),
+                account(KEYS + 7, false, State::Empty),
+                account(5, false, State::Mint { decimals: 6 }),
+                account(6, false, State::Mint { decimals: 6 }),
+                account(0, false, State::TokenProgram),
+                account(0, false, State::TokenProgram),
+            ],
+        });
+
+        assert_eq!(result.program_result, ProgramResult::Failure(ProgramError::InvalidInstructionData));
+    }
+
+    #[test]
+    fn take_from_a_short_vault() {
+        // The vault's mint used to be read from wherever its data ended
+        let result = run(&Case {
+            data: Data::Take { escrow: 7, amount_b: 1_000_000, min_received: 0 },
+            accounts: vec![
+                account(0, true, State::Empty),
+                account(1, false, State::Token { mint: 5, owner: 0, amount: 0 }),
+                account(2, false, State::Token { mint: 6, owner: 0, amount: 1_000_000 }),
+                account(4, false, State::Token { mint: 6, owner: 3, amount: 0 }),
+                account(7, false, escrow(3)),
+                account(3, false, State::Raw { owner: Owner::Token, data: vec![5; 16] }),
+                account(KEYS + 7, false, State::Empty),
+                account(5, false, State::Mint { decimals: 6 }),
+                account(6, false, State::Mint { decimals: 6 }),
+                account(0, false, State::TokenProgram),
+                account(0, false, State::TokenProgram),
+            ],
+        });
+
+        assert_eq!(result.program_result, failure(EscrowError::InvalidTokenAccount));
+    }
+
+    #[test]
+    fn take_part_of_an_offer() {
+        // Rounds down, and leaves the escrow open with what's left of it
+        let result = run(&Case {
+            data: Data::Take { escrow: 7, amount_b: 333_333, min_received: 0 },
+            accounts: vec![
+                account(0, true, State::Empty),
+                account(1, false, State::Token { mint: 5, owner: 0, amount: 0 }),
+                account(2, false, State::Token { mint: 6, owner: 0, amount: 1_000_000 }),
+                account(4, false, State::Token { mint: 6, owner: 3, amount: 0 }),
+                account(7, false, escrow(3)),
+                account(3, false, State::Token { mint: 5, owner: KEYS + 7, amount: 999_999 }),
+                account(KEYS + 7, false, State::Empty),
+                account(5, false, State::Mint { decimals: 6 }),
+                account(6, false, State::Mint { decimals: 6 }),
+                account(0, false, State::TokenProgram),
+                account(0, false, State::TokenProgram),
+            ],
+        });
+
+        assert_eq!(result.program_result, ProgramResult::Success);
+        assert_eq!(token_amount(&result, 1), 333_332);
+        assert_eq!(token_amount(&result, 3), 666_667);
+        assert_eq!(result.get_account(&key(7)).unwrap().data()[128..136], 666_667u64.to_le_bytes());
+    }
+
+    #[test]
+    fn refund_from_a_foreign_escrow() {
+        let result = run(&Case {
+            data: Data::Bump { discriminator: 2, escrow: 2 },
+            accounts: vec![
+                account(0, true, State::Empty),
+                account(1, false, State::Token { mint: 5, owner: 0, amount: 0 }),
+                account(2, false, State::Raw { owner: Owner::System, data: vec![0; 176] }),
+                account(3, false, State::Token { mint: 5, owner: KEYS + 2, amount: 1_000_000 }),
+                account(KEYS + 2, false, State::Empty),
+                account(5, false, State::Mint { decimals: 6 }),
+                account(0, false, State::TokenProgram),
+            ],
+        });
+
+        assert_eq!(result.program_result, failure(EscrowError::InvalidAccountOwner));
+    }
+
+    #[test]
+    fn refund_by_someone_else() {
+        let result = run(&Case {
+            data: Data::Bump { discriminator: 2, escrow: 2 },
+            accounts: vec![
+                account(1, true, State::Empty),
+                account(3, false, State::Token { mint: 5, owner: 1, amount: 0 }),
+                account(2, false, escrow(0)),
+                account(4, false, State::Token { mint: 5, owner: KEYS + 2, amount: 1_000_000 }),
+                account(KEYS + 2, false, State::Empty),
+                account(5, false, State::Mint { decimals: 6 }),
+                account(0, false, State::TokenProgram),
+            ],
+        });
+
+        assert_eq!(result.program_result, failure(EscrowError::MakerMismatch));
+    }
+
+    #[test]
+    fn refund_expired_to_someone_else() {
+        // The crank's maker_ta_a isn't the maker's
+        let result = run(&Case {
+            data: Data::Bump { discriminator: 3, escrow: 2 },
+            accounts: vec![
+                account(1, false, State::Empty),
+                account(0, false, State::Empty),
+                account(3, false, State::Token { mint: 5, owner: 1, amount: 0 }),
+                // Expired before whatever the clock says
+                account(2, false, State::Escrow { maker: 0, maker_ta_b: 4, mint_a: 5, mint_b: 6, amount_b: 1_000_000, expiry: i64::MIN, taker: None }),
+                account(4, false, State::Token { mint: 5, owner: KEYS + 2, amount: 1_000_000 }),
+                account(KEYS + 2, false, State::Empty),
+                account(5, false, State::Mint { decimals: 6 }),
+                account(0, false, State::TokenProgram),
+            ],
+        });
+
+        assert_eq!(result.program_result, failure(EscrowError::MakerTokenAccountMismatch));
+    }
+
+    #[test]
+    fn take_someone_elses_private_offer() {
+        let result = run(&Case {
+            data: Data::Take { escrow: 7, amount_b: 1_000_000, min_received: 0 },
+            accounts: vec![
+                account(0, true, State::Empty),
+                account(1, false, State::Token { mint: 5, owner: 0, amount: 0 }),
+                account(2, false, State::Token { mint: 6, owner: 0, amount: 1_000_000 }),
+                account(4, false, State::Token { mint: 6, owner: 3, amount: 0 }),
+                account(7, false, State::Escrow { maker: 3, maker_ta_b: 4, mint_a: 5, mint_b: 6, amount_b: 1_000_000, expiry: 0, taker: Some(1) }),
+                account(3, false, State::Token { mint: 5, owner: KEYS + 7, amount: 1_000_000 }),
+                account(KEYS + 7, false, State::Empty),
+                account(5, false, State::Mint { decimals: 6 }),
+                account(6, false, State::Mint { decimals: 6 }),
+                account(0, false, State::TokenProgram),
+                account(0, false, State::TokenProgram),
+            ],
+        });
+
+        assert_eq!(result.program_result, failure(EscrowError::NotTheTaker));
+    }
+
+    #[test]
+    fn make_with_token_2022_extensions() {
+        // A Token-2022 mint whose TLV runs past the end of its data
+        let mut mint_b = vec![0; 166];
+        mint_b[165] = 1;
+        mint_b.extend([9, 0, 0xff, 0xff]);
+
+        let result = run(&Case {
+            data: Data::Make { maker_ta_b: 4, mint_a: 5, mint_b: 6, amount_b: 1_000_000, expiry: 0, taker: None },
+            accounts: vec![
+                account(0, true, State::Empty),
+                account(1, true, State::Raw { owner: Owner::Escrow, data: vec![0; 176] }),
+                account(5, false, State::Mint { decimals: 6 }),
+                account(6, false, State::Raw { owner: Owner::Token2022, data: mint_b }),
+                account(2, false, State::SystemProgram),
+            ],
+        });
+
+        assert_eq!(result.program_result, failure(EscrowError::InvalidMint));
+    }
+}
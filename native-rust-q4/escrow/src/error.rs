@@ -0,0 +1,122 @@
+use pinocchio::program_error::ProgramError;
+
+/// # Escrow errors
+///
+/// Every check in the escrow fails with `ProgramError::Custom(code)` for one of these, so a
+/// client can tell which one it tripped. Codes are append-only like discriminators: once
+/// shipped, an error keeps its number. Malformed instruction data, an unknown discriminator
+/// or data of the wrong length, stays `ProgramError::InvalidInstructionData`, and whatever a
+/// token program CPI fails with comes through as the token program's own error.
+///
+/// -- Decoding --
+/// `EscrowError::from_code` turns a custom code back into an error and `from_log` finds one
+/// in a transaction's logs, `message` is what goes into client logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum EscrowError {
+    /// Too few or too many accounts for the instruction
+    InvalidAccountCount = 1,
+    /// The escrow in `Make`, the maker in `Refund` or the Taker of a private offer didn't sign
+    MissingSignature = 2,
+    /// The Escrow account isn't as long as any Escrow layout, see `# State`
+    InvalidEscrowData = 3,
+    /// The Escrow account isn't owned by this program
+    InvalidAccountOwner = 4,
+    /// The maker isn't the Maker saved in the Escrow
+    MakerMismatch = 5,
+    /// maker_ta_b isn't the one saved in the Escrow, or maker_ta_a isn't the maker's
+    MakerTokenAccountMismatch = 6,
+    /// A mint, or the mint of a token account, isn't the one the offer is for
+    MintMismatch = 7,
+    /// A mint that isn't a mint of the token program or of Token-2022
+    InvalidMint = 8,
+    /// A mint with NonTransferable, PermanentDelegate or a TransferHook program
+    UnsupportedMintExtension = 9,
+    /// The token program passed for a mint isn't the one that owns it
+    IncorrectTokenProgram = 10,
+    /// An account that isn't a token account of either token program
+    InvalidTokenAccount = 11,
+    /// A fill of nothing, or of more than is left of the offer
+    InvalidAmount = 12,
+    /// `Take` at or after the offer's Expiry
+    OfferExpired = 13,
+    /// `RefundExpired` of an offer that never expires, or hasn't yet
+    OfferNotExpired = 14,
+    /// Someone other than the Taker of a private offer tried to take it
+    NotTheTaker = 15,
+    /// The fill would get the taker less than their MinReceived
+    SlippageExceeded = 16,
+    /// Grossing up amount_b for mint_b's transfer fee overflowed
+    ArithmeticOverflow = 17,
+}
+
+impl EscrowError {
+    const ALL: [Self; 17] = [
+        Self::InvalidAccountCount,
+        Self::MissingSignature,
+        Self::InvalidEscrowData,
+        Self::InvalidAccountOwner,
+        Self::MakerMismatch,
+        Self::MakerTokenAccountMismatch,
+        Self::MintMismatch,
+        Self::InvalidMint,
+        Self::UnsupportedMintExtension,
+        Self::IncorrectTokenProgram,
+        Self::InvalidTokenAccount,
+        Self::InvalidAmount,
+        Self::OfferExpired,
+        Self::OfferNotExpired,
+        Self::NotTheTaker,
+        Self::SlippageExceeded,
+        Self::ArithmeticOverflow,
+    ];
+
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|error| error.code() == code)
+    }
+
+    /// Finds the error in a runtime log line like
+    /// `Program <id> failed: custom program error: 0xd`.
+    pub fn from_log(log: &str) -> Option<Self> {
+        let (_, code) = log.split_once("custom program error: 0x")?;
+        Self::from_code(u32::from_str_radix(code.trim_end(), 16).ok()?)
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::InvalidAccountCount => "Invalid number of accounts",
+            Self::MissingSignature => "Missing required signature",
+            Self::InvalidEscrowData => "Invalid escrow data",
+            Self::InvalidAccountOwner => "Escrow is not owned by this program",
+            Self::MakerMismatch => "Maker does not match the escrow",
+            Self::MakerTokenAccountMismatch => "Maker token account does not match the escrow",
+            Self::MintMismatch => "Mint does not match the escrow",
+            Self::InvalidMint => "Invalid mint",
+            Self::UnsupportedMintExtension => "Mint has an unsupported extension",
+            Self::IncorrectTokenProgram => "Token program does not own the mint",
+            Self::InvalidTokenAccount => "Invalid token account",
+            Self::InvalidAmount => "Invalid fill amount",
+            Self::OfferExpired => "Offer has expired",
+            Self::OfferNotExpired => "Offer has not expired",
+            Self::NotTheTaker => "Offer is private to another taker",
+            Self::SlippageExceeded => "Fill is below the minimum received",
+            Self::ArithmeticOverflow => "Arithmetic overflow",
+        }
+    }
+}
+
+impl From<EscrowError> for ProgramError {
+    fn from(error: EscrowError) -> Self {
+        ProgramError::Custom(error.code())
+    }
+}
+
+impl core::fmt::Display for EscrowError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(self.message())
+    }
+}
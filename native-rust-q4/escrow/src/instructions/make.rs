@@ -2,6 +2,8 @@ use pinocchio::{
     account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, ProgramResult,
 };
 
+use crate::{error::EscrowError, state::Escrow, token::Mint};
+
 /// # Make
 ///
 /// -- Data scheme --
@@ -9,6 +11,8 @@ use pinocchio::{
 /// > mint_a [u8; 32]
 /// > mint_b [u8; 32]
 /// > receive [u8; 8]
+/// > expiry [u8; 8], the unix timestamp the offer expires at, 0 to never expire
+/// > taker [u8; 32], the only key that can take the offer, all zeroes for anyone
 ///
 /// -- Instruction Logic --
 /// By using a keypair instead of a PDA for the Escrow, we don't need to CPI to allocate
@@ -22,8 +26,10 @@ use pinocchio::{
 /// transfer CPI -> This works because if the maker actually doesn't deposit any token,
 /// nobody will want to exchange it for the other token.
 ///
-/// We don't need Mint B and Mint A accounts since we're not transferring tokens, we can
-/// just pass it as data and save it in the Escrow directly.
+/// We don't need Mint B and Mint A accounts to transfer tokens, the mints are passed as data
+/// and saved in the Escrow directly. We still read both accounts, once, to reject mints with
+/// extensions the escrow can't work with (see `crate::token`), so that no offer gets made
+/// that can't be taken or refunded. Either mint can belong to either token program.
 ///
 /// Note: every CPI costs 1000 CUs, so we should avoid it as much as possible.
 ///
@@ -34,28 +40,46 @@ use pinocchio::{
 /// - `create` and `transfer` for the Vault
 ///
 /// -- Account Optimization Logic --
-/// - 5 accounts from the Anchor Escrow (mint_a, mint_b, maker_ata_a, vault, token_program)
+/// - 3 accounts from the Anchor Escrow (maker_ata_a, vault, token_program)
 ///
 /// -- Checks --
 /// + Check that the Escrow is a Signer, so we know that that account hasn't been used before
 ///   avoid overwriting potential data inside of it.
+/// + Check that the data is exactly the 144 bytes we copy, and that the Escrow is exactly as
+///   long as what we write to it, so neither copy goes out of bounds.
+/// + Check that mint_a and mint_b are the mints in the data, are mints of a token program,
+///   and have no NonTransferable, PermanentDelegate or TransferHook program
 /// - Skip ProgramId check for Escrow, it will fail when we're adding data inside of it
 /// - Skip Space & Lamports check on the Escrow, it will fail on creation
 
 pub fn make(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
-    let [maker, escrow, _system_program] = accounts else {
-        return Err(ProgramError::NotEnoughAccountKeys);
+    let [maker, escrow, mint_a, mint_b, _system_program] = accounts else {
+        return Err(EscrowError::InvalidAccountCount.into());
     };
 
-    assert!(escrow.is_signer());
+    if !escrow.is_signer() {
+        return Err(EscrowError::MissingSignature.into());
+    }
+
+    let data: &[u8; 144] = data.try_into().map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    if escrow.data_len() != Escrow::LEN {
+        return Err(EscrowError::InvalidEscrowData.into());
+    }
+
+    // Check the mints are the ones in the data, and that the escrow can move their tokens
+    if mint_a.key().as_ref() != &data[32..64] || mint_b.key().as_ref() != &data[64..96] {
+        return Err(EscrowError::MintMismatch.into());
+    }
+    Mint::from_account_info(mint_a)?.check_extensions()?;
+    Mint::from_account_info(mint_b)?.check_extensions()?;
 
     // Copy maker key
     unsafe { *(escrow.borrow_mut_data_unchecked().as_mut_ptr() as *mut Pubkey) = *maker.key() };
 
     // Copy everything after maker
     unsafe {
-        *(escrow.borrow_mut_data_unchecked().as_mut_ptr().add(32) as *mut [u8; 104]) =
-            *(data.as_ptr() as *const [u8; 104]);
+        *(escrow.borrow_mut_data_unchecked().as_mut_ptr().add(32) as *mut [u8; 144]) = *data;
     }
 
     Ok(())
@@ -2,6 +2,7 @@ use pinocchio::program_error::ProgramError;
 
 pub mod make;
 pub mod refund;
+pub mod refund_expired;
 pub mod take;
 
 #[derive(Clone, Copy, Debug)]
@@ -9,6 +10,7 @@ pub enum EscrowInstruction {
     Make,
     Take,
     Refund,
+    RefundExpired,
 }
 
 impl TryFrom<&u8> for EscrowInstruction {
@@ -19,6 +21,7 @@ impl TryFrom<&u8> for EscrowInstruction {
             0 => Ok(EscrowInstruction::Make),
             1 => Ok(EscrowInstruction::Take),
             2 => Ok(EscrowInstruction::Refund),
+            3 => Ok(EscrowInstruction::RefundExpired),
             _ => Err(ProgramError::InvalidInstructionData),
         }
     }
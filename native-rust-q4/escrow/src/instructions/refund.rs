@@ -1,14 +1,17 @@
 use pinocchio::{
     account_info::AccountInfo,
     instruction::{Seed, Signer},
-    program_error::ProgramError,
     pubkey::Pubkey,
     ProgramResult,
 };
 
-use pinocchio_token::{instructions::{CloseAccount, Transfer}, state::TokenAccount};
+use pinocchio_token::state::TokenAccount;
 
-use crate::state::Escrow;
+use crate::{
+    error::EscrowError,
+    state::Escrow,
+    token::{check_token_account, check_token_program, close_vault, transfer_checked, Mint},
+};
 
 /// # Refund
 ///
@@ -28,55 +31,80 @@ use crate::state::Escrow;
 /// operation to optimize grabbing the amount of token inside of it:
 /// `TokenAccount::from_account_info_unchecked(vault).amount()`
 ///
+/// The transfer is a TransferChecked through the token program of mint_a, legacy or
+/// Token-2022, and closing the vault harvests what it withheld in transfer fees first.
+///
 /// Then we close the Escrow account by draining all the lamports and setting the data_len
 /// to 0 (data_len starts 8 bytes before the actual data of the account) to prevent
 /// reinitalization attack.
 ///
 /// -- Client Side Logic --
-/// Derive the authority account from the Escrow PublicKey and pass in the bump.
+/// Derive the authority account from the Escrow PublicKey and pass in the bump. Pass mint_a
+/// as writable if it has a transfer fee, for the harvest.
 ///
 /// -- Account Optimization Logic --
-/// - 2 accounts from the Anchor Escrow (mint_a, system_program)
+/// - 1 account from the Anchor Escrow (system_program)
 /// + 1 account from the Anchor Escrow (authority)
 ///
 /// -- Checks --
 /// + Check that Maker is a signer (since it's the owner of the tokens in the Vault)
 /// + Check the ownership of maker_ta_a (since we're transferring the funds to it)
+/// + Check that mint_a is the Escrow's, and token_program the token program that owns it
+/// + Check that the Escrow and the vault are long enough for the reads above, the Escrow
+///   also has to be ours
 
 pub fn refund(accounts: &[AccountInfo], bump: [u8; 1]) -> ProgramResult {
-    let [maker, maker_ta_a, escrow, vault, authority, _token_program] = accounts else {
-        return Err(ProgramError::NotEnoughAccountKeys);
+    let [maker, maker_ta_a, escrow, vault, authority, mint_a, token_program] = accounts else {
+        return Err(EscrowError::InvalidAccountCount.into());
     };
 
     // Ensure maker is signer
-    assert!(maker.is_signer());
+    if !maker.is_signer() {
+        return Err(EscrowError::MissingSignature.into());
+    }
 
     // Ensure maker matches escrow maker
-    let escrow_account = Escrow::from_account_info(escrow);
-    assert_eq!(&escrow_account.maker(), maker.key());
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    if &escrow_account.maker() != maker.key() {
+        return Err(EscrowError::MakerMismatch.into());
+    }
 
     // Derive the signer
     let seeds = [Seed::from(escrow.key().as_ref()), Seed::from(&bump)];
     let signer = [Signer::from(&seeds)];
 
+    // Check the mint and its token program
+    if mint_a.key() != &escrow_account.mint_a() {
+        return Err(EscrowError::MintMismatch.into());
+    }
+    let mint_a_account = Mint::from_account_info(mint_a)?;
+    check_token_program(token_program, mint_a)?;
+
+    check_token_account(vault)?;
     let amount = TokenAccount::from_account_info_unchecked(vault).amount();
 
     // Transfer all funds from the vault to maker_ta_a
-    Transfer {
-        from: vault,
-        to: maker_ta_a,
+    transfer_checked(
+        token_program,
+        vault,
+        mint_a,
+        maker_ta_a,
         authority,
         amount,
-    }
-    .invoke_signed(&signer)?;
+        mint_a_account.decimals(),
+        &signer,
+    )?;
 
     // Close vault
-    CloseAccount {
-        account: vault,
-        destination: maker,
+    close_vault(
+        token_program,
+        vault,
+        mint_a,
+        maker,
         authority,
-    }
-    .invoke_signed(&signer)?;
+        mint_a_account.transfer_fee()?,
+        &signer,
+    )?;
 
     // Close the Escrow account by draining the lamports and setting the data_len to 0
     unsafe {
@@ -0,0 +1,130 @@
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{Seed, Signer},
+    pubkey::Pubkey,
+    sysvars::{clock::Clock, Sysvar},
+    ProgramResult,
+};
+
+use pinocchio_token::state::TokenAccount;
+
+use crate::{
+    error::EscrowError,
+    state::Escrow,
+    token::{check_token_account, check_token_program, close_vault, transfer_checked, Mint},
+};
+
+/// Share of the Escrow's lamports the cranker gets, in basis points.
+pub const BOUNTY_BPS: u64 = 500;
+
+/// # RefundExpired
+///
+/// -- Data scheme --
+/// > bump [u8; 1]
+///
+/// -- Instruction Logic --
+/// The same as `Refund`, except that anyone can send it once the offer has expired, so
+/// offers don't stay open just because their maker went away.
+///
+/// The authority signs the CPIs that transfer the funds back to the maker_ta_a and close
+/// the vault, whose rent goes back to the maker. Like in `Refund`, both go through the token
+/// program of mint_a.
+///
+/// Then we close the Escrow account the same way `Refund` does, except that BOUNTY_BPS of
+/// its lamports go to the cranker, for the transaction fee and a bit more, and the rest to
+/// the maker.
+///
+/// -- Client Side Logic --
+/// Derive the authority account from the Escrow PublicKey and pass in the bump. The cranker
+/// doesn't have to sign, it's just whoever gets the bounty. Pass mint_a as writable if it has
+/// a transfer fee, for the harvest.
+///
+/// -- Checks --
+/// + Check that the offer has an Expiry and that it has passed
+/// + Check that maker is the maker saved in the Escrow, since it gets the rent
+/// + Check that maker_ta_a is a token account of mint_a owned by the maker, since the maker
+///   doesn't sign for where their funds go
+/// + Check that mint_a is the Escrow's, and token_program the token program that owns it
+/// + Check that the Escrow and the vault are long enough for the reads above, the Escrow
+///   also has to be ours
+
+pub fn refund_expired(accounts: &[AccountInfo], bump: [u8; 1]) -> ProgramResult {
+    let [cranker, maker, maker_ta_a, escrow, vault, authority, mint_a, token_program] = accounts else {
+        return Err(EscrowError::InvalidAccountCount.into());
+    };
+
+    let escrow_account = Escrow::from_account_info(escrow)?;
+
+    // Ensure the offer has expired
+    if !escrow_account.has_expired(Clock::get()?.unix_timestamp) {
+        return Err(EscrowError::OfferNotExpired.into());
+    }
+
+    // Ensure maker matches escrow maker
+    if &escrow_account.maker() != maker.key() {
+        return Err(EscrowError::MakerMismatch.into());
+    }
+
+    // Ensure the funds go back to the maker
+    check_token_account(maker_ta_a)?;
+    if TokenAccount::from_account_info_unchecked(maker_ta_a).mint() != escrow_account.mint_a() {
+        return Err(EscrowError::MakerTokenAccountMismatch.into());
+    }
+    // Token account: [mint 32][owner 32][amount 8]...
+    if unsafe { &maker_ta_a.borrow_data_unchecked()[32..64] } != maker.key().as_ref() {
+        return Err(EscrowError::MakerTokenAccountMismatch.into());
+    }
+
+    // Derive the signer
+    let seeds = [Seed::from(escrow.key().as_ref()), Seed::from(&bump)];
+    let signer = [Signer::from(&seeds)];
+
+    // Check the mint and its token program
+    if mint_a.key() != &escrow_account.mint_a() {
+        return Err(EscrowError::MintMismatch.into());
+    }
+    let mint_a_account = Mint::from_account_info(mint_a)?;
+    check_token_program(token_program, mint_a)?;
+
+    check_token_account(vault)?;
+    let amount = TokenAccount::from_account_info_unchecked(vault).amount();
+
+    // Transfer all funds from the vault to maker_ta_a
+    transfer_checked(
+        token_program,
+        vault,
+        mint_a,
+        maker_ta_a,
+        authority,
+        amount,
+        mint_a_account.decimals(),
+        &signer,
+    )?;
+
+    // Close vault
+    close_vault(
+        token_program,
+        vault,
+        mint_a,
+        maker,
+        authority,
+        mint_a_account.transfer_fee()?,
+        &signer,
+    )?;
+
+    // Close the Escrow account, splitting the lamports between the cranker and the maker
+    unsafe {
+        let lamports = *escrow.borrow_lamports_unchecked();
+        let bounty = (lamports as u128 * BOUNTY_BPS as u128 / 10_000) as u64;
+
+        *cranker.borrow_mut_lamports_unchecked() += bounty;
+        *maker.borrow_mut_lamports_unchecked() += lamports - bounty;
+        *escrow.borrow_mut_lamports_unchecked() = 0;
+
+        escrow.assign(&Pubkey::default());
+
+        *(escrow.borrow_mut_data_unchecked().as_mut_ptr().sub(8) as *mut u64) = 0;
+    }
+
+    Ok(())
+}
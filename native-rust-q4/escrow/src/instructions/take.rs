@@ -2,11 +2,17 @@ use pinocchio::{
     account_info::AccountInfo,
     instruction::{Seed, Signer},
     program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvars::{clock::Clock, Sysvar},
     ProgramResult,
 };
-use pinocchio_token::{instructions::{CloseAccount, Transfer}, state::TokenAccount};
+use pinocchio_token::state::TokenAccount;
 
-use crate::state::Escrow;
+use crate::{
+    error::EscrowError,
+    state::Escrow,
+    token::{check_token_account, check_token_program, close_vault, transfer_checked, Mint},
+};
 
 // use pinocchio_spl::{accounts::TokenAccount, CloseAccount, Transfer};
 
@@ -14,6 +20,8 @@ use crate::state::Escrow;
 ///
 /// -- Data scheme --
 /// > Bump [u8; 1]
+/// > AmountB [u8; 8], how much of what's left of the offer to fill
+/// > MinReceived [u8; 8], the least of mint_a the taker accepts for it
 ///
 /// -- Instruction Logic --
 /// We introduce an authority account (that is the owner of the Vault), that has
@@ -24,23 +32,42 @@ use crate::state::Escrow;
 /// Using the authority account permits to skip on a CPI for the creation of the TA
 /// as owner of itself since it's a system account with deterministc seeds.
 ///
-/// We then transfer the right amount of mint_b by getting it from the Escrow data to the
-/// maker_ata_b that is ownerd by the maker.
+/// We then transfer the amount of mint_b the taker fills to the maker_ata_b that is ownerd by
+/// the maker. The taker gets mint_a at the price the offer implies, vault amount over the
+/// amount_b left in the Escrow, rounded down so whatever doesn't divide stays with the maker.
+/// A fill that would get less than MinReceived fails instead.
+///
+/// Both transfers are TransferChecked through the token program of their mint, legacy or
+/// Token-2022. With a transfer fee on mint_b the taker pays the fee on top, so the maker gets
+/// exactly the amount_b they asked for. With one on mint_a the taker's fee comes out of what
+/// they get, and MinReceived holds for what's left after it.
+///
+/// A private offer, with a Taker in the Escrow, can only be taken by that key.
+///
+/// A partial fill leaves the Escrow open with amount_b lowered by what was filled, the vault
+/// keeps the rest of mint_a, so the price stays what the maker asked for.
 ///
 /// We created a new macro to deserialize both the Token Account and the Escrow using pointers
 /// and unsafe operation to optimize grabbing data from it. <T>::from_account_info(account)
 ///
-/// Then we close the Escrow account by draining all the lamports and setting the data_len
-/// to 0 (data_len starts 8 bytes before the actual data of the account) to prevent
-/// reinitalization attack.
+/// Once the whole offer is filled the vault is empty, so we close it (harvesting what it
+/// withheld in fees to the mint first, it can't close with any), then we close the Escrow
+/// account by draining all the lamports, handing it back to the system program and setting
+/// the data_len to 0 (data_len starts 8 bytes before the actual data of the account) to
+/// prevent reinitalization attack, as Refund does.
 ///
 /// -- Client Side Logic --
 /// - Derive the authority account from the Escrow PublicKey and pass in the bump.
 /// - Create a Token Account owned by Maker and with mint_b as Mint
+/// - Read amount_b from the Escrow and the vault amount to quote a fill, and pick MinReceived
+///   from that quote, less mint_a's transfer fee if it has one
+/// - Pass each mint's token program, the same one twice if they share it, and mint_a as
+///   writable if it has a transfer fee, for the harvest
 ///
 /// -- Account Optimization Logic --
-/// - 4 accounts from the Anchor Escrow (maker, mint_a, mint_b, system_program)
+/// - 2 accounts from the Anchor Escrow (maker, system_program)
 /// + 1 account from the Anchor Escrow (authority)
+/// + 1 account for the token program of mint_b, which doesn't have to be mint_a's
 ///
 /// -- Checks --
 /// + Check that the maker_ata_b has maker as authority to enforce that the receiver of
@@ -49,61 +76,141 @@ use crate::state::Escrow;
 ///   the right vault (we can't skip this since somebody could send the authority to a
 ///   worthless Token Account and the instruction will pass).
 /// + Check that the maker_ta_b is the same as the one saved in the Escrow
+/// + Check that the Escrow and the vault are long enough for the reads above, the Escrow
+///   also has to be ours
+/// + Check that AmountB is more than 0 and no more than is left
+/// + Check that the offer hasn't expired
+/// + Check that the taker is the Taker saved in the Escrow, and signs, if there is one
+/// + Check that mint_a and mint_b are the Escrow's, and token_program_a and token_program_b
+///   the token programs that own them
 
-pub fn take(accounts: &[AccountInfo], bump: [u8; 1]) -> ProgramResult {
-    let [taker, taker_ta_a, taker_ta_b, maker_ta_b, escrow, vault, authority, _token_program] =
-        accounts
+pub fn take(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let [
+        taker,
+        taker_ta_a,
+        taker_ta_b,
+        maker_ta_b,
+        escrow,
+        vault,
+        authority,
+        mint_a,
+        mint_b,
+        token_program_a,
+        token_program_b,
+    ] = accounts
     else {
-        return Err(ProgramError::NotEnoughAccountKeys);
+        return Err(EscrowError::InvalidAccountCount.into());
     };
 
+    let data: &[u8; 17] = data.try_into().map_err(|_| ProgramError::InvalidInstructionData)?;
+    let bump = [data[0]];
+    let amount_b = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    let min_received = u64::from_le_bytes(data[9..17].try_into().unwrap());
+
     // Get the escrow_account data for future checks
-    let escrow_account = Escrow::from_account_info(escrow);
+    let escrow_account = Escrow::from_account_info(escrow)?;
+    let remaining_b = escrow_account.amount_b();
+    if amount_b == 0 || amount_b > remaining_b {
+        return Err(EscrowError::InvalidAmount.into());
+    }
+
+    // Check the offer is still open
+    if escrow_account.has_expired(Clock::get()?.unix_timestamp) {
+        return Err(EscrowError::OfferExpired.into());
+    }
+
+    // Check a private offer is taken by its taker
+    let allowed_taker = escrow_account.taker();
+    if allowed_taker != Pubkey::default() {
+        if taker.key() != &allowed_taker {
+            return Err(EscrowError::NotTheTaker.into());
+        }
+        if !taker.is_signer() {
+            return Err(EscrowError::MissingSignature.into());
+        }
+    }
 
     // Check maker_ata_b matches our escrow account
-    assert_eq!(maker_ta_b.key(), &escrow_account.maker_ta_b());
+    if maker_ta_b.key() != &escrow_account.maker_ta_b() {
+        return Err(EscrowError::MakerTokenAccountMismatch.into());
+    }
+
+    // Check the mints and their token programs
+    if mint_a.key() != &escrow_account.mint_a() || mint_b.key() != &escrow_account.mint_b() {
+        return Err(EscrowError::MintMismatch.into());
+    }
+    let mint_a_account = Mint::from_account_info(mint_a)?;
+    let mint_b_account = Mint::from_account_info(mint_b)?;
+    check_token_program(token_program_a, mint_a)?;
+    check_token_program(token_program_b, mint_b)?;
 
     // Check vault mint
-    assert_eq!(
-        &TokenAccount::from_account_info(vault).mint(),
-        &escrow_account.mint_a()
-    );
+    check_token_account(vault)?;
+    if TokenAccount::from_account_info_unchecked(vault).mint() != escrow_account.mint_a() {
+        return Err(EscrowError::MintMismatch.into());
+    }
 
-    // Transfer out the Funds from the vault to the taker_ata_b to the maker_ata_b
-    Transfer {
-        from: taker_ta_b,
-        to: maker_ta_b,
-        authority: taker,
-        amount: escrow_account.amount_b(),
+    // What the fill is worth at the offer's price, rounded down in the maker's favour
+    let vault_amount = TokenAccount::from_account_info_unchecked(vault).amount();
+    let amount_a = (vault_amount as u128 * amount_b as u128 / remaining_b as u128) as u64;
+    let fee_a = mint_a_account.transfer_fee()?;
+    if amount_a - fee_a.map_or(0, |fee| fee.fee(amount_a)) < min_received {
+        return Err(EscrowError::SlippageExceeded.into());
     }
-    .invoke()?;
+
+    // The maker gets amount_b whatever mint_b's fee
+    let paid_b = match mint_b_account.transfer_fee()? {
+        Some(fee) => fee.pre_fee_amount(amount_b).ok_or(EscrowError::ArithmeticOverflow)?,
+        None => amount_b,
+    };
+
+    // Transfer out the Funds from the vault to the taker_ata_b to the maker_ata_b
+    transfer_checked(
+        token_program_b,
+        taker_ta_b,
+        mint_b,
+        maker_ta_b,
+        taker,
+        paid_b,
+        mint_b_account.decimals(),
+        &[],
+    )?;
 
     // Derive the signer
     let seeds = [Seed::from(escrow.key().as_ref()), Seed::from(&bump)];
     let signer = [Signer::from(&seeds)];
 
     // Transfer out the Funds from the vault to the vault to the taker_ata_a
-    Transfer {
-        from: vault,
-        to: taker_ta_a,
+    transfer_checked(
+        token_program_a,
+        vault,
+        mint_a,
+        taker_ta_a,
         authority,
-        amount: TokenAccount::from_account_info(vault).amount(),
+        amount_a,
+        mint_a_account.decimals(),
+        &signer,
+    )?;
+
+    // A partial fill leaves the rest of the offer open
+    if amount_b < remaining_b {
+        escrow_account.set_amount_b(remaining_b - amount_b);
+        return Ok(());
     }
-    .invoke_signed(&signer.clone())?;
 
     // Close vault
-    CloseAccount {
-        account: vault,
-        destination: taker,
-        authority,
-    }
-    .invoke_signed(&signer.clone())?;
+    close_vault(token_program_a, vault, mint_a, taker, authority, fee_a, &signer)?;
 
     // Close the Escrow account by draining the lamports and setting the data_len to 0
     unsafe {
-        *taker.borrow_mut_lamports_unchecked() += *escrow.borrow_lamports_unchecked();
+        *taker.borrow_mut_lamports_unchecked() = taker
+            .borrow_lamports_unchecked()
+            .checked_add(*escrow.borrow_lamports_unchecked())
+            .ok_or(EscrowError::ArithmeticOverflow)?;
         *escrow.borrow_mut_lamports_unchecked() = 0;
 
+        escrow.assign(&Pubkey::default());
+
         *(escrow.borrow_mut_data_unchecked().as_mut_ptr().sub(8) as *mut u64) = 0;
     }
 
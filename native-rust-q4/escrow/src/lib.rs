@@ -1,6 +1,8 @@
+pub mod error;
 mod instructions;
 use instructions::*;
 mod state;
+mod token;
 
 use make::make;
 use pinocchio::account_info::AccountInfo;
@@ -8,6 +10,7 @@ use pinocchio::entrypoint;
 use pinocchio::pubkey::Pubkey;
 use pinocchio::{program_error::ProgramError, ProgramResult};
 use refund::refund;
+use refund_expired::refund_expired;
 use take::take;
 
 mod tests;
@@ -16,8 +19,12 @@ entrypoint!(process_instruction);
 
 pub const PDA_MARKER: &[u8; 21] = b"ProgramDerivedAddress";
 
-pub const ID: [u8; 32] =
-    five8_const::decode_32_const("22222222222222222222222222222222222222222222");
+pub const TOKEN_PROGRAM_ID: Pubkey = five8_const::decode_32_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = five8_const::decode_32_const("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Set at build time by `ESCROW_PROGRAM_ID` or `ESCROW_PROGRAM_KEYPAIR`, see `../program_id.rs`.
+pub const ID: [u8; 32] = include!(concat!(env!("OUT_DIR"), "/program_id.rs"));
 
 fn process_instruction(
     _program_id: &Pubkey,
@@ -30,7 +37,17 @@ fn process_instruction(
 
     match EscrowInstruction::try_from(discriminator)? {
         EscrowInstruction::Make => make(accounts, data),
-        EscrowInstruction::Take => take(accounts, [data[0]]),
-        EscrowInstruction::Refund => refund(accounts, [data[0]]),
+        EscrowInstruction::Take => take(accounts, data),
+        EscrowInstruction::Refund => refund(accounts, bump(data)?),
+        EscrowInstruction::RefundExpired => refund_expired(accounts, bump(data)?),
     }
 }
+
+/// The bump that refund and refund_expired sign for the authority with, their only data.
+fn bump(data: &[u8]) -> Result<[u8; 1], ProgramError> {
+    let [bump] = data else {
+        return Err(ProgramError::InvalidInstructionData);
+    };
+
+    Ok([*bump])
+}
@@ -1,4 +1,6 @@
-use pinocchio::{account_info::AccountInfo, pubkey::Pubkey};
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::error::EscrowError;
 
 /// # State
 ///
@@ -8,24 +10,47 @@ use pinocchio::{account_info::AccountInfo, pubkey::Pubkey};
 /// > MintA: Pubkey
 /// > MintB: Pubkey
 /// > AmountB: u64
+/// > Expiry: i64, the unix timestamp the offer can't be taken from on, 0 if it never expires
+/// > Taker: Pubkey, the only key that can take the offer, all zeroes for a public one
 ///
 /// -- Data Logic --
 /// [...]
 ///
-pub struct Escrow(*const u8);
+/// The Taker sits at TAKER_OFFSET, so `getProgramAccounts` can find the private offers for
+/// a taker with a memcmp filter on their key there.
+///
+/// Escrows opened before Expiry and Taker were added are shorter, EXPIRY_OFFSET long. We
+/// still read them, with both fields as zero, so they never expire and stay public: only this
+/// program can sign for their vault, so rejecting them would lock the maker's tokens in for
+/// good.
+///
+pub struct Escrow(*mut u8, usize);
 
 impl Escrow {
-    pub const LEN: usize = 136;
+    pub const LEN: usize = 176;
+
+    pub const EXPIRY_OFFSET: usize = 136;
+
+    pub const TAKER_OFFSET: usize = 144;
 
     #[inline(always)]
     pub fn from_account_info_unchecked(account_info: &AccountInfo) -> Self {
-        unsafe { Self(account_info.borrow_data_unchecked().as_ptr()) }
+        unsafe {
+            Self(
+                account_info.borrow_mut_data_unchecked().as_mut_ptr(),
+                account_info.data_len(),
+            )
+        }
     }
 
-    pub fn from_account_info(account_info: &AccountInfo) -> Self {
-        assert_eq!(account_info.data_len(), Self::LEN);
-        assert_eq!(account_info.owner(), &crate::ID);
-        Self::from_account_info_unchecked(account_info)
+    pub fn from_account_info(account_info: &AccountInfo) -> Result<Self, ProgramError> {
+        if !matches!(account_info.data_len(), Self::EXPIRY_OFFSET | Self::LEN) {
+            return Err(EscrowError::InvalidEscrowData.into());
+        }
+        if account_info.owner() != &crate::ID {
+            return Err(EscrowError::InvalidAccountOwner.into());
+        }
+        Ok(Self::from_account_info_unchecked(account_info))
     }
 
     pub fn maker(&self) -> Pubkey {
@@ -40,12 +65,34 @@ impl Escrow {
         unsafe { *(self.0.add(64) as *const Pubkey) }
     }
 
-    // Mint B is only used for RPC lookups, so we can comment this out!
-    // pub fn mint_b(&self) -> Pubkey {
-    //     unsafe { *(self.0.add(96) as *const Pubkey) }
-    // }
+    pub fn mint_b(&self) -> Pubkey {
+        unsafe { *(self.0.add(96) as *const Pubkey) }
+    }
 
     pub fn amount_b(&self) -> u64 {
         unsafe { *(self.0.add(128) as *const u64) }
     }
+
+    pub fn set_amount_b(&self, amount_b: u64) {
+        unsafe { *(self.0.add(128) as *mut u64) = amount_b }
+    }
+
+    pub fn expiry(&self) -> i64 {
+        if self.1 < Self::LEN {
+            return 0;
+        }
+        unsafe { *(self.0.add(Self::EXPIRY_OFFSET) as *const i64) }
+    }
+
+    pub fn taker(&self) -> Pubkey {
+        if self.1 < Self::LEN {
+            return Pubkey::default();
+        }
+        unsafe { *(self.0.add(Self::TAKER_OFFSET) as *const Pubkey) }
+    }
+
+    /// Whether the offer has expired by `unix_timestamp`, never for an Expiry of 0.
+    pub fn has_expired(&self, unix_timestamp: i64) -> bool {
+        self.expiry() != 0 && unix_timestamp >= self.expiry()
+    }
 }
@@ -2,24 +2,23 @@
 mod tests {
     use std::mem;
 
-    use mollusk_svm::{program, Mollusk};
+    use mollusk_svm::{program, result::ProgramResult, Mollusk};
 
     use solana_sdk::{
-        account::{AccountSharedData, WritableAccount},
+        account::{AccountSharedData, ReadableAccount, WritableAccount},
         instruction::{AccountMeta, Instruction},
+        program_error::ProgramError,
         program_option::COption,
         program_pack::Pack,
         pubkey::Pubkey,
     };
     use spl_token::state::AccountState;
 
-    use crate::state::Escrow;
+    use crate::{error::EscrowError, state::Escrow, token::TransferFee};
 
     #[test]
     fn make() {
-        let program_id = Pubkey::new_from_array(five8_const::decode_32_const(
-            "22222222222222222222222222222222222222222222",
-        ));
+        let program_id = Pubkey::new_from_array(crate::ID);
 
         let mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
 
@@ -37,6 +36,8 @@ mod tests {
             mint_a.to_bytes().to_vec(),
             mint_b.to_bytes().to_vec(),
             1_000_000u64.to_le_bytes().to_vec(),
+            0i64.to_le_bytes().to_vec(),
+            Pubkey::default().to_bytes().to_vec(),
         ]
         .concat();
 
@@ -46,11 +47,13 @@ mod tests {
             vec![
                 AccountMeta::new(maker, true),
                 AccountMeta::new(escrow, true), // It should be a signer because this account shouldn't exist yet
+                AccountMeta::new_readonly(mint_a, false),
+                AccountMeta::new_readonly(mint_b, false),
                 AccountMeta::new_readonly(system_program, false),
             ],
         );
 
-        let lamports = mollusk.sysvars.rent.minimum_balance(136);
+        let lamports = mollusk.sysvars.rent.minimum_balance(Escrow::LEN);
 
         let result: mollusk_svm::result::InstructionResult = mollusk.process_instruction(
             &instruction,
@@ -59,7 +62,9 @@ mod tests {
                     maker,
                     AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
                 ),
-                (escrow, AccountSharedData::new(lamports, 136, &program_id)),
+                (escrow, AccountSharedData::new(lamports, Escrow::LEN, &program_id)),
+                (mint_a, mint_account(&mollusk)),
+                (mint_b, mint_account(&mollusk)),
                 (system_program, system_program_account),
             ],
         );
@@ -69,9 +74,7 @@ mod tests {
 
     #[test]
     fn refund() {
-        let program_id = Pubkey::new_from_array(five8_const::decode_32_const(
-            "22222222222222222222222222222222222222222222",
-        ));
+        let program_id = Pubkey::new_from_array(crate::ID);
 
         let mut mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
 
@@ -171,6 +174,8 @@ mod tests {
                 mint_a.to_bytes().to_vec(),
                 mint_b.to_bytes().to_vec(),
                 1_000_000u64.to_le_bytes().to_vec(),
+                0i64.to_le_bytes().to_vec(),
+                Pubkey::default().to_bytes().to_vec(),
             ]
             .concat(),
         );
@@ -188,6 +193,7 @@ mod tests {
                 AccountMeta::new(escrow, false),
                 AccountMeta::new(vault, false),
                 AccountMeta::new(authority, true),
+                AccountMeta::new(mint_a, false),
                 AccountMeta::new(token_program, false),
             ],
         );
@@ -203,6 +209,7 @@ mod tests {
                 (escrow, escrow_account),
                 (vault, vault_account),
                 (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (mint_a, mint_a_account),
                 (token_program, token_program_account),
             ],
         );
@@ -212,9 +219,7 @@ mod tests {
 
     #[test]
     fn take() {
-        let program_id = Pubkey::new_from_array(five8_const::decode_32_const(
-            "22222222222222222222222222222222222222222222",
-        ));
+        let program_id = Pubkey::new_from_array(crate::ID);
 
         let mut mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
 
@@ -382,12 +387,19 @@ mod tests {
                 mint_a.to_bytes().to_vec(),
                 mint_b.to_bytes().to_vec(),
                 1_000_000u64.to_le_bytes().to_vec(),
+                0i64.to_le_bytes().to_vec(),
+                Pubkey::default().to_bytes().to_vec(),
             ]
             .concat(),
         );
 
-        // Data
-        let data = [1, bump];
+        // Data, filling the whole offer
+        let data = [
+            vec![1, bump],
+            1_000_000u64.to_le_bytes().to_vec(),
+            0u64.to_le_bytes().to_vec(),
+        ]
+        .concat();
 
         // Instruction
         let instruction = Instruction::new_with_bytes(
@@ -401,7 +413,10 @@ mod tests {
                 AccountMeta::new(escrow, false),
                 AccountMeta::new(vault, false),
                 AccountMeta::new(authority, true),
+                AccountMeta::new(mint_a, false),
+                AccountMeta::new_readonly(mint_b, false),
                 AccountMeta::new(token_program, false),
+                AccountMeta::new_readonly(token_program, false),
             ],
         );
 
@@ -418,10 +433,1259 @@ mod tests {
                 (escrow, escrow_account),
                 (vault, vault_account),
                 (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (mint_a, mint_a_account),
+                (mint_b, mint_b_account),
                 (token_program, token_program_account),
             ],
         );
 
         assert!(!result.program_result.is_err());
+
+        // The filled offer's Escrow goes back to the system program, empty
+        let escrow_account = result.get_account(&escrow).unwrap();
+        assert_eq!(escrow_account.lamports(), 0);
+        assert_eq!(escrow_account.owner(), &Pubkey::default());
+        assert!(escrow_account.data().is_empty());
+    }
+
+    fn token_account(mollusk: &Mollusk, mint: Pubkey, owner: Pubkey, amount: u64) -> AccountSharedData {
+        let mut account = AccountSharedData::new(
+            mollusk.sysvars.rent.minimum_balance(spl_token::state::Account::LEN),
+            spl_token::state::Account::LEN,
+            &spl_token::ID,
+        );
+        solana_sdk::program_pack::Pack::pack(
+            spl_token::state::Account {
+                mint,
+                owner,
+                amount,
+                state: AccountState::Initialized,
+                ..Default::default()
+            },
+            account.data_as_mut_slice(),
+        )
+        .unwrap();
+
+        account
+    }
+
+    fn mint_account(mollusk: &Mollusk) -> AccountSharedData {
+        let mut account = AccountSharedData::new(
+            mollusk.sysvars.rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN,
+            &spl_token::ID,
+        );
+        solana_sdk::program_pack::Pack::pack(
+            spl_token::state::Mint {
+                decimals: 6,
+                is_initialized: true,
+                ..Default::default()
+            },
+            account.data_as_mut_slice(),
+        )
+        .unwrap();
+
+        account
+    }
+
+    /// A Token-2022 mint with `extensions`, as (type, value) pairs.
+    fn mint_2022_account(mollusk: &Mollusk, extensions: &[(u16, Vec<u8>)]) -> AccountSharedData {
+        // The base mint, padded to the length of a token account, then AccountType::Mint
+        let mut data = mint_account(mollusk).data().to_vec();
+        data.resize(spl_token::state::Account::LEN, 0);
+        data.push(1);
+
+        for (extension_type, value) in extensions {
+            data.extend(extension_type.to_le_bytes());
+            data.extend((value.len() as u16).to_le_bytes());
+            data.extend(value);
+        }
+
+        let mut account = AccountSharedData::new(
+            mollusk.sysvars.rent.minimum_balance(data.len()),
+            data.len(),
+            &Pubkey::new_from_array(crate::TOKEN_2022_PROGRAM_ID),
+        );
+        account.set_data_from_slice(&data);
+
+        account
+    }
+
+    /// A TransferFeeConfig of 1% up to 5_000 since epoch 0, with `withheld` harvested to the
+    /// mint so far.
+    fn transfer_fee_config(withheld: u64) -> Vec<u8> {
+        let transfer_fee = [0u64.to_le_bytes(), 5_000u64.to_le_bytes()].concat();
+
+        [
+            [0; 64].to_vec(),
+            withheld.to_le_bytes().to_vec(),
+            transfer_fee.clone(),
+            100u16.to_le_bytes().to_vec(),
+            transfer_fee,
+            100u16.to_le_bytes().to_vec(),
+        ]
+        .concat()
+    }
+
+    /// A Token-2022 token account of a mint with a transfer fee, so with the TransferFeeAmount
+    /// every such account needs, holding `withheld` in fees.
+    fn token_2022_account(
+        mollusk: &Mollusk,
+        mint: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+        withheld: u64,
+    ) -> AccountSharedData {
+        // The base account, then AccountType::Account and TransferFeeAmount
+        let mut data = token_account(mollusk, mint, owner, amount).data().to_vec();
+        data.push(2);
+        data.extend(2u16.to_le_bytes());
+        data.extend(8u16.to_le_bytes());
+        data.extend(withheld.to_le_bytes());
+
+        let mut account = AccountSharedData::new(
+            mollusk.sysvars.rent.minimum_balance(data.len()),
+            data.len(),
+            &Pubkey::new_from_array(crate::TOKEN_2022_PROGRAM_ID),
+        );
+        account.set_data_from_slice(&data);
+
+        account
+    }
+
+    /// Loads Token-2022 from `tests/fixtures/spl_token_2022.so`, dumped from mainnet with
+    /// `solana program dump -um TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb tests/fixtures/spl_token_2022.so`.
+    fn add_token_2022(mollusk: &mut Mollusk) -> (Pubkey, AccountSharedData) {
+        let token_2022_program = Pubkey::new_from_array(crate::TOKEN_2022_PROGRAM_ID);
+        mollusk.add_program(
+            &token_2022_program,
+            "spl_token_2022",
+            &program::loader_keys::LOADER_V3,
+        );
+
+        (
+            token_2022_program,
+            program::create_program_account_loader_v3(&token_2022_program),
+        )
+    }
+
+    /// What a token account of either token program holds, Token-2022 extensions aside.
+    fn token_amount(account: &AccountSharedData) -> u64 {
+        spl_token::state::Account::unpack(&account.data()[..spl_token::state::Account::LEN])
+            .unwrap()
+            .amount
+    }
+
+    /// What a Token-2022 token account, or a mint, with a transfer fee has withheld.
+    fn withheld_amount(account: &AccountSharedData) -> u64 {
+        let data = account.data();
+        // The account ends with TransferFeeAmount, the mint with TransferFeeConfig's withheld
+        // and then its two 18 byte TransferFees
+        let withheld = match data[spl_token::state::Account::LEN] {
+            2 => &data[data.len() - 8..],
+            _ => &data[data.len() - 44..data.len() - 36],
+        };
+
+        u64::from_le_bytes(withheld.try_into().unwrap())
+    }
+
+    fn failure(error: EscrowError) -> ProgramResult {
+        ProgramResult::Failure(ProgramError::Custom(error.code()))
+    }
+
+    #[test]
+    fn take_partially() {
+        let program_id = Pubkey::new_from_array(crate::ID);
+
+        let mut mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
+
+        mollusk_token::token::add_program(&mut mollusk);
+        let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+
+        // Accounts
+        let taker = Pubkey::new_unique();
+        let taker_ta_a = Pubkey::new_unique();
+        let taker_ta_b = Pubkey::new_unique();
+        let maker_ta_b = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let (authority, bump) =
+            Pubkey::try_find_program_address(&[escrow.as_ref()], &program_id).unwrap();
+        let maker = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+
+        // 999_999 A for 1_000_000 B, so fills don't divide evenly
+        let mut escrow_account = AccountSharedData::new(
+            mollusk.sysvars.rent.minimum_balance(Escrow::LEN),
+            Escrow::LEN,
+            &program_id,
+        );
+        escrow_account.set_data_from_slice(
+            &[
+                maker.to_bytes().to_vec(),
+                maker_ta_b.to_bytes().to_vec(),
+                mint_a.to_bytes().to_vec(),
+                mint_b.to_bytes().to_vec(),
+                1_000_000u64.to_le_bytes().to_vec(),
+                0i64.to_le_bytes().to_vec(),
+                Pubkey::default().to_bytes().to_vec(),
+            ]
+            .concat(),
+        );
+
+        let accounts = vec![
+            (
+                taker,
+                AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
+            ),
+            (taker_ta_a, token_account(&mollusk, mint_a, taker, 0)),
+            (taker_ta_b, token_account(&mollusk, mint_b, taker, 1_000_000)),
+            (maker_ta_b, token_account(&mollusk, mint_b, maker, 0)),
+            (escrow, escrow_account),
+            (vault, token_account(&mollusk, mint_a, authority, 999_999)),
+            (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (mint_a, mint_account(&mollusk)),
+            (mint_b, mint_account(&mollusk)),
+            (token_program, token_program_account),
+        ];
+
+        let instruction = |amount_b: u64, min_received: u64| {
+            Instruction::new_with_bytes(
+                program_id,
+                &[
+                    vec![1, bump],
+                    amount_b.to_le_bytes().to_vec(),
+                    min_received.to_le_bytes().to_vec(),
+                ]
+                .concat(),
+                vec![
+                    AccountMeta::new(taker, true),
+                    AccountMeta::new(taker_ta_a, false),
+                    AccountMeta::new(taker_ta_b, false),
+                    AccountMeta::new(maker_ta_b, false),
+                    AccountMeta::new(escrow, false),
+                    AccountMeta::new(vault, false),
+                    AccountMeta::new(authority, true),
+                    AccountMeta::new(mint_a, false),
+                    AccountMeta::new_readonly(mint_b, false),
+                    AccountMeta::new(token_program, false),
+                    AccountMeta::new_readonly(token_program, false),
+                ],
+            )
+        };
+
+        // 400_000 B is worth 399_999.6 A, the taker can't insist on the rounded up amount
+        let result = mollusk.process_instruction(&instruction(400_000, 400_000), &accounts);
+        assert_eq!(
+            result.program_result,
+            failure(EscrowError::SlippageExceeded)
+        );
+
+        // Filling nothing, or more than is offered, fails too
+        for amount_b in [0, 1_000_001] {
+            let result = mollusk.process_instruction(&instruction(amount_b, 0), &accounts);
+            assert_eq!(
+                result.program_result,
+                failure(EscrowError::InvalidAmount)
+            );
+        }
+
+        let result = mollusk.process_instruction(&instruction(400_000, 399_999), &accounts);
+        assert!(!result.program_result.is_err());
+
+        assert_eq!(token_amount(result.get_account(&taker_ta_a).unwrap()), 399_999);
+        assert_eq!(token_amount(result.get_account(&maker_ta_b).unwrap()), 400_000);
+        assert_eq!(token_amount(result.get_account(&vault).unwrap()), 600_000);
+
+        // The rest of the offer is still open, at the same price
+        let escrow_account = result.get_account(&escrow).unwrap();
+        assert_eq!(escrow_account.lamports(), accounts[4].1.lamports());
+        assert_eq!(escrow_account.data()[128..], 600_000u64.to_le_bytes());
+
+        // Filling the rest closes it
+        let result = mollusk.process_instruction(&instruction(600_000, 0), &result.resulting_accounts);
+        assert!(!result.program_result.is_err());
+
+        assert_eq!(token_amount(result.get_account(&taker_ta_a).unwrap()), 999_999);
+        assert_eq!(token_amount(result.get_account(&maker_ta_b).unwrap()), 1_000_000);
+        assert_eq!(result.get_account(&vault).unwrap().lamports(), 0);
+        assert_eq!(result.get_account(&escrow).unwrap().lamports(), 0);
+    }
+
+    fn escrow_account(
+        mollusk: &Mollusk,
+        maker: Pubkey,
+        maker_ta_b: Pubkey,
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+        expiry: i64,
+        taker: Pubkey,
+    ) -> AccountSharedData {
+        let mut account = AccountSharedData::new(
+            mollusk.sysvars.rent.minimum_balance(Escrow::LEN),
+            Escrow::LEN,
+            &Pubkey::new_from_array(crate::ID),
+        );
+        account.set_data_from_slice(
+            &[
+                maker.to_bytes().to_vec(),
+                maker_ta_b.to_bytes().to_vec(),
+                mint_a.to_bytes().to_vec(),
+                mint_b.to_bytes().to_vec(),
+                1_000_000u64.to_le_bytes().to_vec(),
+                expiry.to_le_bytes().to_vec(),
+                taker.to_bytes().to_vec(),
+            ]
+            .concat(),
+        );
+
+        account
+    }
+
+    #[test]
+    fn take_after_expiry() {
+        let program_id = Pubkey::new_from_array(crate::ID);
+
+        let mut mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
+
+        mollusk_token::token::add_program(&mut mollusk);
+        let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+
+        // Accounts
+        let taker = Pubkey::new_unique();
+        let taker_ta_a = Pubkey::new_unique();
+        let taker_ta_b = Pubkey::new_unique();
+        let maker_ta_b = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let (authority, bump) =
+            Pubkey::try_find_program_address(&[escrow.as_ref()], &program_id).unwrap();
+        let maker = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[
+                vec![1, bump],
+                1_000_000u64.to_le_bytes().to_vec(),
+                0u64.to_le_bytes().to_vec(),
+            ]
+            .concat(),
+            vec![
+                AccountMeta::new(taker, true),
+                AccountMeta::new(taker_ta_a, false),
+                AccountMeta::new(taker_ta_b, false),
+                AccountMeta::new(maker_ta_b, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new(authority, true),
+                AccountMeta::new(mint_a, false),
+                AccountMeta::new_readonly(mint_b, false),
+                AccountMeta::new(token_program, false),
+                AccountMeta::new_readonly(token_program, false),
+            ],
+        );
+        let accounts = vec![
+            (
+                taker,
+                AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
+            ),
+            (taker_ta_a, token_account(&mollusk, mint_a, taker, 0)),
+            (taker_ta_b, token_account(&mollusk, mint_b, taker, 1_000_000)),
+            (maker_ta_b, token_account(&mollusk, mint_b, maker, 0)),
+            (
+                escrow,
+                escrow_account(
+                    &mollusk,
+                    maker,
+                    maker_ta_b,
+                    mint_a,
+                    mint_b,
+                    1_700_000_000,
+                    Pubkey::default(),
+                ),
+            ),
+            (vault, token_account(&mollusk, mint_a, authority, 1_000_000)),
+            (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (mint_a, mint_account(&mollusk)),
+            (mint_b, mint_account(&mollusk)),
+            (token_program, token_program_account),
+        ];
+
+        // Up to the second before it expires
+        mollusk.sysvars.clock.unix_timestamp = 1_699_999_999;
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert!(!result.program_result.is_err());
+
+        mollusk.sysvars.clock.unix_timestamp = 1_700_000_000;
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert_eq!(
+            result.program_result,
+            failure(EscrowError::OfferExpired)
+        );
+    }
+
+    #[test]
+    fn refund_expired() {
+        let program_id = Pubkey::new_from_array(crate::ID);
+
+        let mut mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
+
+        mollusk_token::token::add_program(&mut mollusk);
+        let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+
+        // Accounts
+        let cranker = Pubkey::new_unique();
+        let maker = Pubkey::new_unique();
+        let maker_ta_a = Pubkey::new_unique();
+        let cranker_ta_a = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let (authority, bump) =
+            Pubkey::try_find_program_address(&[escrow.as_ref()], &program_id).unwrap();
+        let maker_ta_b = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+
+        let instruction = |maker_ta_a: Pubkey| {
+            Instruction::new_with_bytes(
+                program_id,
+                &[3, bump],
+                vec![
+                    AccountMeta::new(cranker, false),
+                    AccountMeta::new(maker, false),
+                    AccountMeta::new(maker_ta_a, false),
+                    AccountMeta::new(escrow, false),
+                    AccountMeta::new(vault, false),
+                    AccountMeta::new(authority, false),
+                    AccountMeta::new(mint_a, false),
+                    AccountMeta::new(token_program, false),
+                ],
+            )
+        };
+        let accounts = |expiry: i64| {
+            vec![
+                (cranker, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (maker, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (maker_ta_a, token_account(&mollusk, mint_a, maker, 0)),
+                (cranker_ta_a, token_account(&mollusk, mint_a, cranker, 0)),
+                (
+                    escrow,
+                    escrow_account(
+                        &mollusk,
+                        maker,
+                        maker_ta_b,
+                        mint_a,
+                        mint_b,
+                        expiry,
+                        Pubkey::default(),
+                    ),
+                ),
+                (vault, token_account(&mollusk, mint_a, authority, 1_000_000)),
+                (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (mint_a, mint_account(&mollusk)),
+                (token_program, token_program_account.clone()),
+            ]
+        };
+        let expired = accounts(1_700_000_000);
+        let never_expires = accounts(0);
+
+        // Before it expires the offer is only the maker's to refund
+        mollusk.sysvars.clock.unix_timestamp = 1_699_999_999;
+        let result = mollusk.process_instruction(&instruction(maker_ta_a), &expired);
+        assert_eq!(
+            result.program_result,
+            failure(EscrowError::OfferNotExpired)
+        );
+
+        mollusk.sysvars.clock.unix_timestamp = i64::MAX;
+        let result = mollusk.process_instruction(&instruction(maker_ta_a), &never_expires);
+        assert_eq!(
+            result.program_result,
+            failure(EscrowError::OfferNotExpired)
+        );
+
+        // The funds can only go back to the maker
+        mollusk.sysvars.clock.unix_timestamp = 1_700_000_000;
+        let result = mollusk.process_instruction(&instruction(cranker_ta_a), &expired);
+        assert_eq!(
+            result.program_result,
+            failure(EscrowError::MakerTokenAccountMismatch)
+        );
+
+        let result = mollusk.process_instruction(&instruction(maker_ta_a), &expired);
+        assert!(!result.program_result.is_err());
+
+        let escrow_lamports = expired[4].1.lamports();
+        let vault_lamports = expired[5].1.lamports();
+        let bounty = escrow_lamports * crate::refund_expired::BOUNTY_BPS / 10_000;
+
+        assert_eq!(token_amount(result.get_account(&maker_ta_a).unwrap()), 1_000_000);
+        assert_eq!(result.get_account(&cranker).unwrap().lamports(), bounty);
+        assert_eq!(
+            result.get_account(&maker).unwrap().lamports(),
+            escrow_lamports - bounty + vault_lamports
+        );
+        assert_eq!(result.get_account(&escrow).unwrap().lamports(), 0);
+        assert_eq!(result.get_account(&vault).unwrap().lamports(), 0);
+    }
+
+    #[test]
+    fn refund_legacy_escrow() {
+        let program_id = Pubkey::new_from_array(crate::ID);
+
+        let mut mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
+
+        mollusk_token::token::add_program(&mut mollusk);
+        let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+
+        // Accounts
+        let maker = Pubkey::new_unique();
+        let maker_ta_a = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let (authority, bump) =
+            Pubkey::try_find_program_address(&[escrow.as_ref()], &program_id).unwrap();
+        let maker_ta_b = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[2, bump],
+            vec![
+                AccountMeta::new(maker, true),
+                AccountMeta::new(maker_ta_a, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new(authority, false),
+                AccountMeta::new(mint_a, false),
+                AccountMeta::new_readonly(token_program, false),
+            ],
+        );
+
+        // Escrows opened before Expiry and Taker end where those start, anything between
+        // that and LEN was never deployed
+        for len in [Escrow::EXPIRY_OFFSET, Escrow::TAKER_OFFSET] {
+            let data = escrow_account(
+                &mollusk,
+                maker,
+                maker_ta_b,
+                mint_a,
+                mint_b,
+                0,
+                Pubkey::default(),
+            )
+            .data()[..len]
+                .to_vec();
+            let mut escrow_account =
+                AccountSharedData::new(mollusk.sysvars.rent.minimum_balance(len), len, &program_id);
+            escrow_account.set_data_from_slice(&data);
+
+            let result = mollusk.process_instruction(
+                &instruction,
+                &[
+                    (
+                        maker,
+                        AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
+                    ),
+                    (maker_ta_a, token_account(&mollusk, mint_a, maker, 0)),
+                    (escrow, escrow_account),
+                    (vault, token_account(&mollusk, mint_a, authority, 1_000_000)),
+                    (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+                    (mint_a, mint_account(&mollusk)),
+                    (token_program, token_program_account.clone()),
+                ],
+            );
+            if len == Escrow::TAKER_OFFSET {
+                assert_eq!(result.program_result, failure(EscrowError::InvalidEscrowData));
+                continue;
+            }
+            assert!(!result.program_result.is_err());
+
+            assert_eq!(token_amount(result.get_account(&maker_ta_a).unwrap()), 1_000_000);
+            assert_eq!(result.get_account(&escrow).unwrap().lamports(), 0);
+        }
+    }
+
+    #[test]
+    fn take_private_offer() {
+        let program_id = Pubkey::new_from_array(crate::ID);
+
+        let mut mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
+
+        mollusk_token::token::add_program(&mut mollusk);
+        let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+
+        // Accounts
+        let taker = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        let maker_ta_b = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let (authority, bump) =
+            Pubkey::try_find_program_address(&[escrow.as_ref()], &program_id).unwrap();
+        let maker = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+
+        let escrow_account =
+            escrow_account(&mollusk, maker, maker_ta_b, mint_a, mint_b, 0, taker);
+
+        // Clients find it by the taker's key
+        assert_eq!(
+            escrow_account.data()[Escrow::TAKER_OFFSET..Escrow::TAKER_OFFSET + 32],
+            taker.to_bytes()
+        );
+
+        // `signer` takes the whole offer, paying from and receiving into its own token accounts
+        let take = |signer: Pubkey, is_signer: bool| {
+            let signer_ta_a = Pubkey::new_unique();
+            let signer_ta_b = Pubkey::new_unique();
+
+            mollusk.process_instruction(
+                &Instruction::new_with_bytes(
+                    program_id,
+                    &[
+                        vec![1, bump],
+                        1_000_000u64.to_le_bytes().to_vec(),
+                        0u64.to_le_bytes().to_vec(),
+                    ]
+                    .concat(),
+                    vec![
+                        AccountMeta::new(signer, is_signer),
+                        AccountMeta::new(signer_ta_a, false),
+                        AccountMeta::new(signer_ta_b, false),
+                        AccountMeta::new(maker_ta_b, false),
+                        AccountMeta::new(escrow, false),
+                        AccountMeta::new(vault, false),
+                        AccountMeta::new(authority, false),
+                        AccountMeta::new(mint_a, false),
+                        AccountMeta::new_readonly(mint_b, false),
+                        AccountMeta::new(token_program, false),
+                        AccountMeta::new_readonly(token_program, false),
+                    ],
+                ),
+                &[
+                    (
+                        signer,
+                        AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
+                    ),
+                    (signer_ta_a, token_account(&mollusk, mint_a, signer, 0)),
+                    (signer_ta_b, token_account(&mollusk, mint_b, signer, 1_000_000)),
+                    (maker_ta_b, token_account(&mollusk, mint_b, maker, 0)),
+                    (escrow, escrow_account.clone()),
+                    (vault, token_account(&mollusk, mint_a, authority, 1_000_000)),
+                    (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+                    (mint_a, mint_account(&mollusk)),
+                    (mint_b, mint_account(&mollusk)),
+                    (token_program, token_program_account.clone()),
+                ],
+            )
+        };
+
+        assert_eq!(
+            take(stranger, true).program_result,
+            failure(EscrowError::NotTheTaker)
+        );
+        assert_eq!(
+            take(taker, false).program_result,
+            failure(EscrowError::MissingSignature)
+        );
+        assert!(!take(taker, true).program_result.is_err());
+    }
+
+    #[test]
+    fn make_with_token_2022_mints() {
+        let program_id = Pubkey::new_from_array(crate::ID);
+
+        let mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
+
+        let maker = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let maker_ta_b = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+
+        let (system_program, system_program_account) = program::keyed_account_for_system_program();
+
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[
+                vec![0],
+                maker_ta_b.to_bytes().to_vec(),
+                mint_a.to_bytes().to_vec(),
+                mint_b.to_bytes().to_vec(),
+                1_000_000u64.to_le_bytes().to_vec(),
+                0i64.to_le_bytes().to_vec(),
+                Pubkey::default().to_bytes().to_vec(),
+            ]
+            .concat(),
+            vec![
+                AccountMeta::new(maker, true),
+                AccountMeta::new(escrow, true),
+                AccountMeta::new_readonly(mint_a, false),
+                AccountMeta::new_readonly(mint_b, false),
+                AccountMeta::new_readonly(system_program, false),
+            ],
+        );
+
+        // Makes an offer of a legacy mint_a for a Token-2022 mint_b with `extensions`
+        let make = |extensions: &[(u16, Vec<u8>)]| {
+            mollusk.process_instruction(
+                &instruction,
+                &[
+                    (
+                        maker,
+                        AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
+                    ),
+                    (
+                        escrow,
+                        AccountSharedData::new(
+                            mollusk.sysvars.rent.minimum_balance(Escrow::LEN),
+                            Escrow::LEN,
+                            &program_id,
+                        ),
+                    ),
+                    (mint_a, mint_account(&mollusk)),
+                    (mint_b, mint_2022_account(&mollusk, extensions)),
+                    (system_program, system_program_account.clone()),
+                ],
+            )
+        };
+
+        assert!(!make(&[]).program_result.is_err());
+        assert!(!make(&[(1, transfer_fee_config(0))]).program_result.is_err());
+
+        // A TransferHook without a program
+        assert!(!make(&[(14, vec![0; 64])]).program_result.is_err());
+
+        for extensions in [
+            // NonTransferable
+            vec![(9, vec![])],
+            // PermanentDelegate
+            vec![(12, Pubkey::new_unique().to_bytes().to_vec())],
+            // TransferHook with a program
+            vec![(14, [[0; 32], Pubkey::new_unique().to_bytes()].concat())],
+        ] {
+            assert_eq!(
+                make(&extensions).program_result,
+                failure(EscrowError::UnsupportedMintExtension)
+            );
+        }
+    }
+
+    #[test]
+    fn take_with_transfer_fee() {
+        let program_id = Pubkey::new_from_array(crate::ID);
+
+        let mut mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
+
+        mollusk_token::token::add_program(&mut mollusk);
+        let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+        let (token_2022_program, token_2022_program_account) = add_token_2022(&mut mollusk);
+
+        // Accounts
+        let taker = Pubkey::new_unique();
+        let taker_ta_a = Pubkey::new_unique();
+        let taker_ta_b = Pubkey::new_unique();
+        let maker_ta_b = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let (authority, bump) =
+            Pubkey::try_find_program_address(&[escrow.as_ref()], &program_id).unwrap();
+        let maker = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[
+                vec![1, bump],
+                1_000_000u64.to_le_bytes().to_vec(),
+                0u64.to_le_bytes().to_vec(),
+            ]
+            .concat(),
+            vec![
+                AccountMeta::new(taker, true),
+                AccountMeta::new(taker_ta_a, false),
+                AccountMeta::new(taker_ta_b, false),
+                AccountMeta::new(maker_ta_b, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new(authority, false),
+                AccountMeta::new(mint_a, false),
+                AccountMeta::new_readonly(mint_b, false),
+                AccountMeta::new_readonly(token_program, false),
+                AccountMeta::new_readonly(token_2022_program, false),
+            ],
+        );
+
+        // mint_b takes 1% up to 5_000 of every transfer
+        let result = mollusk.process_instruction(
+            &instruction,
+            &[
+                (
+                    taker,
+                    AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
+                ),
+                (taker_ta_a, token_account(&mollusk, mint_a, taker, 0)),
+                (
+                    taker_ta_b,
+                    token_2022_account(&mollusk, mint_b, taker, 2_000_000, 0),
+                ),
+                (
+                    maker_ta_b,
+                    token_2022_account(&mollusk, mint_b, maker, 0, 0),
+                ),
+                (
+                    escrow,
+                    escrow_account(
+                        &mollusk,
+                        maker,
+                        maker_ta_b,
+                        mint_a,
+                        mint_b,
+                        0,
+                        Pubkey::default(),
+                    ),
+                ),
+                (vault, token_account(&mollusk, mint_a, authority, 1_000_000)),
+                (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (mint_a, mint_account(&mollusk)),
+                (
+                    mint_b,
+                    mint_2022_account(&mollusk, &[(1, transfer_fee_config(0))]),
+                ),
+                (token_program, token_program_account),
+                (token_2022_program, token_2022_program_account),
+            ],
+        );
+        assert!(!result.program_result.is_err());
+
+        // The taker paid the fee on top, the maker got all of amount_b
+        let maker_ta_b_account = result.get_account(&maker_ta_b).unwrap();
+        assert_eq!(token_amount(maker_ta_b_account), 1_000_000);
+        assert_eq!(withheld_amount(maker_ta_b_account), 5_000);
+        assert_eq!(
+            token_amount(result.get_account(&taker_ta_b).unwrap()),
+            995_000
+        );
+        assert_eq!(
+            token_amount(result.get_account(&taker_ta_a).unwrap()),
+            1_000_000
+        );
+    }
+
+    #[test]
+    fn refund_with_transfer_fee() {
+        let program_id = Pubkey::new_from_array(crate::ID);
+
+        let mut mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
+
+        let (token_2022_program, token_2022_program_account) = add_token_2022(&mut mollusk);
+
+        // Accounts
+        let maker = Pubkey::new_unique();
+        let maker_ta_a = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let (authority, bump) =
+            Pubkey::try_find_program_address(&[escrow.as_ref()], &program_id).unwrap();
+        let maker_ta_b = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[2, bump],
+            vec![
+                AccountMeta::new(maker, true),
+                AccountMeta::new(maker_ta_a, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new(authority, false),
+                AccountMeta::new(mint_a, false),
+                AccountMeta::new_readonly(token_2022_program, false),
+            ],
+        );
+
+        // The vault withheld 5_000 of what the maker funded it with, it can't close until
+        // that's harvested to mint_a
+        let result = mollusk.process_instruction(
+            &instruction,
+            &[
+                (
+                    maker,
+                    AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
+                ),
+                (
+                    maker_ta_a,
+                    token_2022_account(&mollusk, mint_a, maker, 0, 0),
+                ),
+                (
+                    escrow,
+                    escrow_account(
+                        &mollusk,
+                        maker,
+                        maker_ta_b,
+                        mint_a,
+                        mint_b,
+                        0,
+                        Pubkey::default(),
+                    ),
+                ),
+                (
+                    vault,
+                    token_2022_account(&mollusk, mint_a, authority, 1_000_000, 5_000),
+                ),
+                (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (
+                    mint_a,
+                    mint_2022_account(&mollusk, &[(1, transfer_fee_config(0))]),
+                ),
+                (token_2022_program, token_2022_program_account),
+            ],
+        );
+        assert!(!result.program_result.is_err());
+
+        // The refund itself pays the fee again
+        let maker_ta_a_account = result.get_account(&maker_ta_a).unwrap();
+        assert_eq!(token_amount(maker_ta_a_account), 995_000);
+        assert_eq!(withheld_amount(maker_ta_a_account), 5_000);
+        assert_eq!(withheld_amount(result.get_account(&mint_a).unwrap()), 5_000);
+        assert_eq!(result.get_account(&vault).unwrap().lamports(), 0);
+        assert_eq!(result.get_account(&escrow).unwrap().lamports(), 0);
+    }
+
+    #[test]
+    fn transfer_fee() {
+        let fee = TransferFee {
+            maximum_fee: 5_000,
+            basis_points: 100,
+        };
+
+        assert_eq!(fee.fee(0), 0);
+        assert_eq!(fee.fee(1), 1);
+        assert_eq!(fee.fee(100_000), 1_000);
+        assert_eq!(fee.fee(100_001), 1_001);
+        assert_eq!(fee.fee(10_000_000), 5_000);
+
+        // The smallest amount that gets what was asked for through, whatever the rounding
+        for post_fee_amount in [0, 1, 99, 100, 12_345, 999_999, 1_000_000, u64::MAX - 5_000] {
+            let pre_fee_amount = fee.pre_fee_amount(post_fee_amount).unwrap();
+
+            assert_eq!(pre_fee_amount - fee.fee(pre_fee_amount), post_fee_amount);
+            if pre_fee_amount > 0 {
+                let less = pre_fee_amount - 1;
+                assert!(less - fee.fee(less) < post_fee_amount);
+            }
+        }
+
+        assert_eq!(fee.pre_fee_amount(u64::MAX), None);
+        assert_eq!(
+            TransferFee {
+                maximum_fee: 5_000,
+                basis_points: 0
+            }
+            .pre_fee_amount(1_000),
+            Some(1_000)
+        );
+        assert_eq!(
+            TransferFee {
+                maximum_fee: 5_000,
+                basis_points: 10_000
+            }
+            .pre_fee_amount(1_000),
+            Some(6_000)
+        );
+    }
+
+    /// A take of the whole of a public offer that goes through as is, for the error tests to
+    /// break one thing in. The accounts are in the order the instruction lists them, with the
+    /// token program once.
+    fn take_case() -> (Mollusk, Instruction, Vec<(Pubkey, AccountSharedData)>) {
+        let program_id = Pubkey::new_from_array(crate::ID);
+
+        let mut mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
+
+        mollusk_token::token::add_program(&mut mollusk);
+        let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+
+        // Accounts
+        let taker = Pubkey::new_unique();
+        let taker_ta_a = Pubkey::new_unique();
+        let taker_ta_b = Pubkey::new_unique();
+        let maker_ta_b = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let (authority, bump) =
+            Pubkey::try_find_program_address(&[escrow.as_ref()], &program_id).unwrap();
+        let maker = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+
+        let instruction = Instruction::new_with_bytes(
+            program_id,
+            &[
+                vec![1, bump],
+                1_000_000u64.to_le_bytes().to_vec(),
+                0u64.to_le_bytes().to_vec(),
+            ]
+            .concat(),
+            vec![
+                AccountMeta::new(taker, true),
+                AccountMeta::new(taker_ta_a, false),
+                AccountMeta::new(taker_ta_b, false),
+                AccountMeta::new(maker_ta_b, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new(authority, false),
+                AccountMeta::new(mint_a, false),
+                AccountMeta::new_readonly(mint_b, false),
+                AccountMeta::new_readonly(token_program, false),
+                AccountMeta::new_readonly(token_program, false),
+            ],
+        );
+        let accounts = vec![
+            (
+                taker,
+                AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
+            ),
+            (taker_ta_a, token_account(&mollusk, mint_a, taker, 0)),
+            (taker_ta_b, token_account(&mollusk, mint_b, taker, 1_000_000)),
+            (maker_ta_b, token_account(&mollusk, mint_b, maker, 0)),
+            (
+                escrow,
+                escrow_account(
+                    &mollusk,
+                    maker,
+                    maker_ta_b,
+                    mint_a,
+                    mint_b,
+                    0,
+                    Pubkey::default(),
+                ),
+            ),
+            (vault, token_account(&mollusk, mint_a, authority, 1_000_000)),
+            (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (mint_a, mint_account(&mollusk)),
+            (mint_b, mint_account(&mollusk)),
+            (token_program, token_program_account),
+        ];
+
+        (mollusk, instruction, accounts)
+    }
+
+    #[test]
+    fn invalid_account_count() {
+        let (mollusk, mut instruction, accounts) = take_case();
+
+        instruction.accounts.pop();
+
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert_eq!(result.program_result, failure(EscrowError::InvalidAccountCount));
+    }
+
+    #[test]
+    fn invalid_escrow_data() {
+        let (mollusk, instruction, mut accounts) = take_case();
+
+        // One byte short of an Escrow
+        accounts[4].1 = AccountSharedData::new(
+            mollusk.sysvars.rent.minimum_balance(Escrow::LEN),
+            Escrow::LEN - 1,
+            &Pubkey::new_from_array(crate::ID),
+        );
+
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert_eq!(result.program_result, failure(EscrowError::InvalidEscrowData));
+    }
+
+    #[test]
+    fn invalid_account_owner() {
+        let (mollusk, instruction, mut accounts) = take_case();
+
+        // The same offer, in an account someone else owns
+        accounts[4].1.set_owner(Pubkey::default());
+
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert_eq!(result.program_result, failure(EscrowError::InvalidAccountOwner));
+    }
+
+    #[test]
+    fn maker_mismatch() {
+        let program_id = Pubkey::new_from_array(crate::ID);
+
+        let mut mollusk = Mollusk::new(&program_id, "target/deploy/native_escrow");
+
+        mollusk_token::token::add_program(&mut mollusk);
+        let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+
+        // Accounts
+        let maker = Pubkey::new_unique();
+        let stranger = Pubkey::new_unique();
+        let stranger_ta_a = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let (authority, bump) =
+            Pubkey::try_find_program_address(&[escrow.as_ref()], &program_id).unwrap();
+        let maker_ta_b = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+
+        // Someone else refunds the maker's offer to themselves
+        let result = mollusk.process_instruction(
+            &Instruction::new_with_bytes(
+                program_id,
+                &[2, bump],
+                vec![
+                    AccountMeta::new(stranger, true),
+                    AccountMeta::new(stranger_ta_a, false),
+                    AccountMeta::new(escrow, false),
+                    AccountMeta::new(vault, false),
+                    AccountMeta::new(authority, false),
+                    AccountMeta::new(mint_a, false),
+                    AccountMeta::new_readonly(token_program, false),
+                ],
+            ),
+            &[
+                (
+                    stranger,
+                    AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
+                ),
+                (stranger_ta_a, token_account(&mollusk, mint_a, stranger, 0)),
+                (
+                    escrow,
+                    escrow_account(
+                        &mollusk,
+                        maker,
+                        maker_ta_b,
+                        mint_a,
+                        mint_b,
+                        0,
+                        Pubkey::default(),
+                    ),
+                ),
+                (vault, token_account(&mollusk, mint_a, authority, 1_000_000)),
+                (authority, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (mint_a, mint_account(&mollusk)),
+                (token_program, token_program_account),
+            ],
+        );
+        assert_eq!(result.program_result, failure(EscrowError::MakerMismatch));
+    }
+
+    #[test]
+    fn mint_mismatch() {
+        let (mollusk, mut instruction, mut accounts) = take_case();
+
+        // Paying out of some other mint_a's vault
+        let other_mint = Pubkey::new_unique();
+        instruction.accounts[7].pubkey = other_mint;
+        accounts.push((other_mint, mint_account(&mollusk)));
+
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert_eq!(result.program_result, failure(EscrowError::MintMismatch));
+    }
+
+    #[test]
+    fn invalid_mint() {
+        let (mollusk, instruction, mut accounts) = take_case();
+
+        // A token account where mint_a should be
+        accounts[7].1 = accounts[1].1.clone();
+
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert_eq!(result.program_result, failure(EscrowError::InvalidMint));
+    }
+
+    #[test]
+    fn incorrect_token_program() {
+        let (mollusk, mut instruction, mut accounts) = take_case();
+
+        // mint_b's token program is the system program
+        let (system_program, system_program_account) = program::keyed_account_for_system_program();
+        instruction.accounts[10].pubkey = system_program;
+        accounts.push((system_program, system_program_account));
+
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert_eq!(result.program_result, failure(EscrowError::IncorrectTokenProgram));
+    }
+
+    #[test]
+    fn invalid_token_account() {
+        let (mollusk, instruction, mut accounts) = take_case();
+
+        // A mint where the vault should be
+        accounts[5].1 = mint_account(&mollusk);
+
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert_eq!(result.program_result, failure(EscrowError::InvalidTokenAccount));
+    }
+
+    #[test]
+    fn arithmetic_overflow() {
+        let (mollusk, mut instruction, mut accounts) = take_case();
+
+        // A Token-2022 mint_b with a 100% fee capped at u64::MAX, the maker can't be paid
+        // amount_b after it
+        let fee = [
+            0u64.to_le_bytes().to_vec(),
+            u64::MAX.to_le_bytes().to_vec(),
+            10_000u16.to_le_bytes().to_vec(),
+        ]
+        .concat();
+        accounts[8].1 = mint_2022_account(&mollusk, &[(1, [vec![0; 72], fee.clone(), fee].concat())]);
+
+        // It's never invoked, the fee is worked out before the transfers
+        let token_2022_program = Pubkey::new_from_array(crate::TOKEN_2022_PROGRAM_ID);
+        instruction.accounts[10].pubkey = token_2022_program;
+        accounts.push((token_2022_program, AccountSharedData::new(0, 0, &Pubkey::default())));
+
+        let result = mollusk.process_instruction(&instruction, &accounts);
+        assert_eq!(result.program_result, failure(EscrowError::ArithmeticOverflow));
+    }
+
+    #[test]
+    fn error_codes() {
+        // Codes are append-only, clients out there decode these
+        let codes = [
+            (1, EscrowError::InvalidAccountCount),
+            (2, EscrowError::MissingSignature),
+            (3, EscrowError::InvalidEscrowData),
+            (4, EscrowError::InvalidAccountOwner),
+            (5, EscrowError::MakerMismatch),
+            (6, EscrowError::MakerTokenAccountMismatch),
+            (7, EscrowError::MintMismatch),
+            (8, EscrowError::InvalidMint),
+            (9, EscrowError::UnsupportedMintExtension),
+            (10, EscrowError::IncorrectTokenProgram),
+            (11, EscrowError::InvalidTokenAccount),
+            (12, EscrowError::InvalidAmount),
+            (13, EscrowError::OfferExpired),
+            (14, EscrowError::OfferNotExpired),
+            (15, EscrowError::NotTheTaker),
+            (16, EscrowError::SlippageExceeded),
+            (17, EscrowError::ArithmeticOverflow),
+        ];
+
+        for (code, error) in codes {
+            assert_eq!(error.code(), code);
+            assert_eq!(EscrowError::from_code(code), Some(error));
+            assert_eq!(error.to_string(), error.message());
+        }
+        assert_eq!(EscrowError::from_code(0), None);
+        assert_eq!(EscrowError::from_code(18), None);
+
+        let program_id = Pubkey::new_from_array(crate::ID);
+        assert_eq!(
+            EscrowError::from_log(&format!("Program {program_id} failed: custom program error: 0xd")),
+            Some(EscrowError::OfferExpired)
+        );
+        assert_eq!(EscrowError::from_log("Program log: Offer has expired"), None);
+        assert_eq!(EscrowError::from_log("Program failed: custom program error: 0xff"), None);
     }
 }
@@ -0,0 +1,262 @@
+//! # Token
+//!
+//! Everything the escrow needs from the token programs, for both the legacy token program
+//! and Token-2022. The CPIs are built by hand, since they have to go to whichever of the
+//! two owns the mint.
+//!
+//! -- Layout --
+//! Token-2022 keeps the legacy layouts and appends to them: an extended account is padded
+//! to TOKEN_ACCOUNT_LEN, then has an AccountType byte, then its extensions as
+//! [type u16][length u16][value] entries.
+//!
+//! -- Extensions --
+//! TransferFeeConfig: the fee is withheld from what the receiver gets, so we gross up what
+//! the taker pays the maker, and harvest what the vault has withheld before closing it.
+//!
+//! NonTransferable and PermanentDelegate are rejected at make time, the first because the
+//! vault could never pay out and the second because the delegate could empty it. So is a
+//! TransferHook that names a program, we don't pass the hook's extra accounts so every
+//! transfer out of the vault would fail.
+
+use pinocchio::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction, Signer},
+    program::invoke_signed,
+    program_error::ProgramError,
+    sysvars::{clock::Clock, Sysvar},
+    ProgramResult,
+};
+
+use crate::{error::EscrowError, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+/// Length of an SPL Token account, and where Token-2022's AccountType byte goes.
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+
+/// Length of an SPL Token mint without extensions.
+pub const MINT_LEN: usize = 82;
+
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+const TRANSFER_FEE_CONFIG: u16 = 1;
+const NON_TRANSFERABLE: u16 = 9;
+const PERMANENT_DELEGATE: u16 = 12;
+const TRANSFER_HOOK: u16 = 14;
+
+/// Fails unless `token_program` owns `mint`, which `Mint::from_account_info` already checked
+/// is one of the two.
+pub fn check_token_program(token_program: &AccountInfo, mint: &AccountInfo) -> ProgramResult {
+    if token_program.key() != mint.owner() {
+        return Err(EscrowError::IncorrectTokenProgram.into());
+    }
+    Ok(())
+}
+
+/// Fails unless `account_info` is a token account, so that reading it with
+/// `TokenAccount::from_account_info_unchecked` stays in bounds.
+pub fn check_token_account(account_info: &AccountInfo) -> ProgramResult {
+    let data = unsafe { account_info.borrow_data_unchecked() };
+
+    match account_info.owner() {
+        owner if owner == &TOKEN_PROGRAM_ID && data.len() == TOKEN_ACCOUNT_LEN => Ok(()),
+        owner if owner == &TOKEN_2022_PROGRAM_ID
+            && (data.len() == TOKEN_ACCOUNT_LEN || data.get(TOKEN_ACCOUNT_LEN) == Some(&ACCOUNT_TYPE_ACCOUNT)) =>
+        {
+            Ok(())
+        }
+        _ => Err(EscrowError::InvalidTokenAccount.into()),
+    }
+}
+
+/// A mint of either token program, read straight from its data.
+pub struct Mint<'a>(&'a [u8]);
+
+impl<'a> Mint<'a> {
+    /// Fails unless `mint` is a mint of either token program.
+    pub fn from_account_info(mint: &'a AccountInfo) -> Result<Self, ProgramError> {
+        let data = unsafe { mint.borrow_data_unchecked() };
+
+        match mint.owner() {
+            owner if owner == &TOKEN_PROGRAM_ID && data.len() == MINT_LEN => {}
+            owner if owner == &TOKEN_2022_PROGRAM_ID
+                && (data.len() == MINT_LEN || data.get(TOKEN_ACCOUNT_LEN) == Some(&ACCOUNT_TYPE_MINT)) => {}
+            _ => return Err(EscrowError::InvalidMint.into()),
+        }
+
+        Ok(Self(data))
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.0[44]
+    }
+
+    /// The value of the extension of type `extension_type`, if the mint has one.
+    fn extension(&self, extension_type: u16) -> Result<Option<&'a [u8]>, ProgramError> {
+        let mut tlv = self.0.get(TOKEN_ACCOUNT_LEN + 1..).unwrap_or_default();
+
+        while let [t0, t1, l0, l1, rest @ ..] = tlv {
+            let length = u16::from_le_bytes([*l0, *l1]) as usize;
+            let value = rest.get(..length).ok_or(EscrowError::InvalidMint)?;
+
+            match u16::from_le_bytes([*t0, *t1]) {
+                // Uninitialized, the rest is padding
+                0 => break,
+                found if found == extension_type => return Ok(Some(value)),
+                _ => tlv = &rest[length..],
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Fails if the mint has an extension the escrow can't work with, see `# Token`.
+    pub fn check_extensions(&self) -> ProgramResult {
+        if self.extension(NON_TRANSFERABLE)?.is_some() || self.extension(PERMANENT_DELEGATE)?.is_some() {
+            return Err(EscrowError::UnsupportedMintExtension.into());
+        }
+
+        // TransferHook: [authority 32][program_id 32], a zero program_id is no hook
+        if let Some(hook) = self.extension(TRANSFER_HOOK)? {
+            if hook.get(32..64).ok_or(EscrowError::InvalidMint)? != [0; 32] {
+                return Err(EscrowError::UnsupportedMintExtension.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The transfer fee in effect this epoch, only reading the Clock for mints that have one.
+    pub fn transfer_fee(&self) -> Result<Option<TransferFee>, ProgramError> {
+        let Some(config) = self.extension(TRANSFER_FEE_CONFIG)? else {
+            return Ok(None);
+        };
+
+        // TransferFeeConfig: [authorities 64][withheld 8][older 18][newer 18]
+        // TransferFee: [epoch 8][maximum_fee 8][basis_points 2]
+        let config: &[u8; 108] = config.try_into().map_err(|_| EscrowError::InvalidMint)?;
+        let newer = &config[90..108];
+        let fee = match Clock::get()?.epoch >= u64::from_le_bytes(newer[0..8].try_into().unwrap()) {
+            true => newer,
+            false => &config[72..90],
+        };
+
+        Ok(Some(TransferFee {
+            maximum_fee: u64::from_le_bytes(fee[8..16].try_into().unwrap()),
+            basis_points: u16::from_le_bytes(fee[16..18].try_into().unwrap()),
+        }))
+    }
+}
+
+/// A Token-2022 transfer fee, worked out the way the token program does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransferFee {
+    pub maximum_fee: u64,
+    pub basis_points: u16,
+}
+
+impl TransferFee {
+    const ONE_IN_BASIS_POINTS: u128 = 10_000;
+
+    /// The fee withheld from a transfer of `amount`, rounded up.
+    pub fn fee(&self, amount: u64) -> u64 {
+        if self.basis_points == 0 || amount == 0 {
+            return 0;
+        }
+
+        let fee = (amount as u128 * self.basis_points as u128).div_ceil(Self::ONE_IN_BASIS_POINTS);
+        fee.min(self.maximum_fee as u128) as u64
+    }
+
+    /// The smallest transfer that gets `post_fee_amount` to the receiver, None on overflow.
+    pub fn pre_fee_amount(&self, post_fee_amount: u64) -> Option<u64> {
+        let basis_points = self.basis_points as u128;
+
+        match (basis_points, post_fee_amount) {
+            (0, _) | (_, 0) => Some(post_fee_amount),
+            (Self::ONE_IN_BASIS_POINTS, _) => post_fee_amount.checked_add(self.maximum_fee),
+            _ => {
+                let pre_fee_amount = (post_fee_amount as u128 * Self::ONE_IN_BASIS_POINTS)
+                    .div_ceil(Self::ONE_IN_BASIS_POINTS.checked_sub(basis_points)?);
+
+                if pre_fee_amount - post_fee_amount as u128 >= self.maximum_fee as u128 {
+                    post_fee_amount.checked_add(self.maximum_fee)
+                } else {
+                    u64::try_from(pre_fee_amount).ok()
+                }
+            }
+        }
+    }
+}
+
+/// `TransferChecked` of `amount` from `from` to `to`, through `token_program`.
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_checked(
+    token_program: &AccountInfo,
+    from: &AccountInfo,
+    mint: &AccountInfo,
+    to: &AccountInfo,
+    authority: &AccountInfo,
+    amount: u64,
+    decimals: u8,
+    signers: &[Signer],
+) -> ProgramResult {
+    // TransferChecked: [12u8, amount, decimals]
+    let mut data = [12u8; 10];
+    data[1..9].copy_from_slice(&amount.to_le_bytes());
+    data[9] = decimals;
+
+    invoke_signed(
+        &Instruction {
+            program_id: token_program.key(),
+            data: &data,
+            accounts: &[
+                AccountMeta::writable(from.key()),
+                AccountMeta::readonly(mint.key()),
+                AccountMeta::writable(to.key()),
+                AccountMeta::readonly_signer(authority.key()),
+            ],
+        },
+        &[from, mint, to, authority],
+        signers,
+    )
+}
+
+/// Closes the empty `vault` to `destination`, through `token_program`. With a transfer fee,
+/// whatever the vault withheld has to go to the mint first, the vault can't close with any.
+pub fn close_vault(
+    token_program: &AccountInfo,
+    vault: &AccountInfo,
+    mint: &AccountInfo,
+    destination: &AccountInfo,
+    authority: &AccountInfo,
+    transfer_fee: Option<TransferFee>,
+    signers: &[Signer],
+) -> ProgramResult {
+    if transfer_fee.is_some() {
+        // TransferFeeExtension HarvestWithheldTokensToMint: [26u8, 4u8], permissionless
+        invoke_signed(
+            &Instruction {
+                program_id: token_program.key(),
+                data: &[26, 4],
+                accounts: &[AccountMeta::writable(mint.key()), AccountMeta::writable(vault.key())],
+            },
+            &[mint, vault],
+            &[],
+        )?;
+    }
+
+    // CloseAccount: [9u8]
+    invoke_signed(
+        &Instruction {
+            program_id: token_program.key(),
+            data: &[9],
+            accounts: &[
+                AccountMeta::writable(vault.key()),
+                AccountMeta::writable(destination.key()),
+                AccountMeta::readonly_signer(authority.key()),
+            ],
+        },
+        &[vault, destination, authority],
+        signers,
+    )
+}
@@ -27,7 +27,7 @@ pub fn make(program_id: &Pubkey, accounts: &[AccountInfo], args: EscrowArgs) ->
     // assert_eq!(mint_a.owner, token_program.key);
     // assert_eq!(mint_b.owner, token_program.key);
     // assert_eq!(maker_ta_a.owner, token_program.key);
-    assert_eq!(vault.owner, system_program.key);
+    assert_eq!(vault.owner, token_program.key);
 
     let mint_unpacked = Mint::unpack(&mint_a.try_borrow_data()?)?;
 
@@ -56,10 +56,8 @@ pub fn make(program_id: &Pubkey, accounts: &[AccountInfo], args: EscrowArgs) ->
         bump: args.escrow_bump as u64,
     };
 
-    let mut escrow_data = *bytemuck::try_from_bytes_mut::<Escrow>(*escrow.data.borrow_mut())
-        .map_err(|_| ProgramError::AccountBorrowFailed)?;
-
-    escrow_data.clone_from(&new_escrow);
+    *bytemuck::try_from_bytes_mut::<Escrow>(*escrow.data.borrow_mut())
+        .map_err(|_| ProgramError::AccountBorrowFailed)? = new_escrow;
 
     // Transfer to vault
     invoke(
@@ -73,11 +71,7 @@ pub fn make(program_id: &Pubkey, accounts: &[AccountInfo], args: EscrowArgs) ->
             args.amount,
             mint_unpacked.decimals,
         )?,
-        &[
-            maker.clone(), 
-            maker_ta_a.clone(),
-            vault.clone()
-            ],
+        accounts,
     )?;
 
     Ok(())
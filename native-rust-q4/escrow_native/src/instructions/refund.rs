@@ -26,7 +26,7 @@ pub fn refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     };
 
     assert!(system_program::check_id(system_program.key));
-    assert!(spl_token::check_id(system_program.key));
+    assert!(spl_token::check_id(token_program.key));
     assert!(crate::check_id(program_id));
     assert!(maker.is_signer);
     assert!(maker.is_writable);
@@ -37,6 +37,8 @@ pub fn refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
         .map_err(|_| ProgramError::AccountBorrowFailed)?;
     let escrow_seeds = &[b"escrow", maker.key.as_ref(), &[escrow_data.bump as u8]];
 
+    let a_amount = spl_token::state::Account::unpack(&vault.try_borrow_data()?)?.amount;
+
     // Transfer A from vault back to maker_ta_a
     invoke_signed(
         &transfer_checked(
@@ -46,7 +48,7 @@ pub fn refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
             maker_ta_a.key,
             escrow.key,
             &[],
-            escrow_data.receive,
+            a_amount,
             mint_a_decimals,
         )?,
         accounts,
@@ -54,8 +56,7 @@ pub fn refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     )?;
 
     // close escrow
-    let mut escrow_data = escrow.data.borrow_mut();
-    escrow_data.fill(0);
+    escrow.data.borrow_mut().fill(0);
     let maker_orig_lamports = maker.lamports();
     **maker.lamports.borrow_mut() = maker_orig_lamports.checked_add(escrow.lamports()).ok_or(ProgramError::ArithmeticOverflow)?;
     **escrow.lamports.borrow_mut() = 0;
@@ -30,7 +30,7 @@ pub fn take(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     };
 
     assert!(system_program::check_id(system_program.key));
-    assert!(spl_token::check_id(system_program.key));
+    assert!(spl_token::check_id(token_program.key));
     assert!(crate::check_id(program_id));
     assert!(taker.is_signer);
     assert!(taker.is_writable);
@@ -76,8 +76,7 @@ pub fn take(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     )?;
 
     // close escrow
-    let mut escrow_data = escrow.data.borrow_mut();
-    escrow_data.fill(0);
+    escrow.data.borrow_mut().fill(0);
     let maker_orig_lamports = maker.lamports();
     **maker.lamports.borrow_mut() = maker_orig_lamports.checked_add(escrow.lamports()).ok_or(ProgramError::ArithmeticOverflow)?;
     **escrow.lamports.borrow_mut() = 0;
@@ -1,10 +1,22 @@
 use processor::process_instruction;
-use solana_program::{declare_id, entrypoint};
+use solana_program::{entrypoint, pubkey::Pubkey};
 
 mod instructions;
 mod processor;
 mod state;
+#[cfg(test)]
+mod tests;
 
-declare_id!("22222222222222222222222222222222222222222222");
+/// Set at build time by `ESCROW_NATIVE_PROGRAM_ID` or `ESCROW_NATIVE_PROGRAM_KEYPAIR`, see
+/// `../program_id.rs`. Stands in for `declare_id!`, which only takes a literal.
+pub const ID: Pubkey = Pubkey::new_from_array(include!(concat!(env!("OUT_DIR"), "/program_id.rs")));
+
+pub fn check_id(id: &Pubkey) -> bool {
+    id == &ID
+}
+
+pub const fn id() -> Pubkey {
+    ID
+}
 
 entrypoint!(process_instruction);
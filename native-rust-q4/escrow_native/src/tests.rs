@@ -0,0 +1,308 @@
+use mollusk_svm::{program, result::InstructionResult, Mollusk};
+use solana_sdk::{
+    account::{AccountSharedData, ReadableAccount, WritableAccount},
+    instruction::{AccountMeta, Instruction},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::state::AccountState;
+
+use crate::{state::Escrow, ID};
+
+/// Offers are for `AMOUNT` of A against `RECEIVE` of B, different so a test can tell which
+/// one moved.
+const AMOUNT: u64 = 1_000_000;
+const RECEIVE: u64 = 2_500_000;
+
+struct Setup {
+    mollusk: Mollusk,
+    maker: Pubkey,
+    escrow: Pubkey,
+    bump: u8,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    taker: Pubkey,
+    vault: Pubkey,
+    maker_ta_a: Pubkey,
+    maker_ta_b: Pubkey,
+    taker_ta_a: Pubkey,
+    taker_ta_b: Pubkey,
+    token_program: (Pubkey, AccountSharedData),
+    system_program: (Pubkey, AccountSharedData),
+}
+
+fn setup() -> Setup {
+    let mut mollusk = Mollusk::new(&ID, "target/deploy/escrow");
+    mollusk_token::token::add_program(&mut mollusk);
+
+    let maker = Pubkey::new_unique();
+    let (escrow, bump) = Pubkey::find_program_address(&[b"escrow", maker.as_ref()], &ID);
+
+    Setup {
+        mollusk,
+        maker,
+        escrow,
+        bump,
+        mint_a: Pubkey::new_unique(),
+        mint_b: Pubkey::new_unique(),
+        taker: Pubkey::new_unique(),
+        vault: Pubkey::new_unique(),
+        maker_ta_a: Pubkey::new_unique(),
+        maker_ta_b: Pubkey::new_unique(),
+        taker_ta_a: Pubkey::new_unique(),
+        taker_ta_b: Pubkey::new_unique(),
+        token_program: mollusk_token::token::keyed_account(),
+        system_program: program::keyed_account_for_system_program(),
+    }
+}
+
+fn wallet() -> AccountSharedData {
+    AccountSharedData::new(1_000_000_000, 0, &Pubkey::default())
+}
+
+fn mint(mollusk: &Mollusk) -> AccountSharedData {
+    let mut account = AccountSharedData::new(
+        mollusk.sysvars.rent.minimum_balance(spl_token::state::Mint::LEN),
+        spl_token::state::Mint::LEN,
+        &spl_token::ID,
+    );
+    Pack::pack(
+        spl_token::state::Mint {
+            mint_authority: COption::None,
+            supply: 100_000_000_000,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        account.data_as_mut_slice(),
+    )
+    .unwrap();
+    account
+}
+
+fn token_account(mollusk: &Mollusk, mint: Pubkey, owner: Pubkey, amount: u64) -> AccountSharedData {
+    let mut account = AccountSharedData::new(
+        mollusk.sysvars.rent.minimum_balance(spl_token::state::Account::LEN),
+        spl_token::state::Account::LEN,
+        &spl_token::ID,
+    );
+    Pack::pack(
+        spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        },
+        account.data_as_mut_slice(),
+    )
+    .unwrap();
+    account
+}
+
+fn token_amount(result: &InstructionResult, account: &Pubkey) -> u64 {
+    spl_token::state::Account::unpack(result.get_account(account).unwrap().data())
+        .unwrap()
+        .amount
+}
+
+/// An open offer as `Make` leaves it, with `RECEIVE` of B asked.
+fn escrow_account(setup: &Setup) -> AccountSharedData {
+    let mut account = AccountSharedData::new(
+        setup.mollusk.sysvars.rent.minimum_balance(Escrow::LEN),
+        Escrow::LEN,
+        &ID,
+    );
+    account.set_data_from_slice(
+        &[
+            setup.maker.as_ref(),
+            setup.mint_a.as_ref(),
+            setup.mint_b.as_ref(),
+            &RECEIVE.to_le_bytes(),
+            &(setup.bump as u64).to_le_bytes(),
+        ]
+        .concat(),
+    );
+    account
+}
+
+fn make(setup: &Setup, vault: AccountSharedData) -> InstructionResult {
+    // Borsh `Make(EscrowArgs { maker, amount, receive, escrow_bump })`
+    let data = [
+        &[0][..],
+        setup.maker.as_ref(),
+        &AMOUNT.to_le_bytes(),
+        &RECEIVE.to_le_bytes(),
+        &[setup.bump],
+    ]
+    .concat();
+
+    setup.mollusk.process_instruction(
+        &Instruction::new_with_bytes(
+            ID,
+            &data,
+            vec![
+                AccountMeta::new(setup.maker, true),
+                AccountMeta::new_readonly(setup.mint_a, false),
+                AccountMeta::new_readonly(setup.mint_b, false),
+                AccountMeta::new(setup.escrow, false),
+                AccountMeta::new(setup.maker_ta_a, false),
+                AccountMeta::new(setup.vault, false),
+                AccountMeta::new_readonly(setup.token_program.0, false),
+                AccountMeta::new_readonly(setup.system_program.0, false),
+            ],
+        ),
+        &[
+            (setup.maker, wallet()),
+            (setup.mint_a, mint(&setup.mollusk)),
+            (setup.mint_b, mint(&setup.mollusk)),
+            (setup.escrow, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (setup.maker_ta_a, token_account(&setup.mollusk, setup.mint_a, setup.maker, AMOUNT)),
+            (setup.vault, vault),
+            setup.token_program.clone(),
+            setup.system_program.clone(),
+        ],
+    )
+}
+
+/// Takes the offer with `vault` holding `vault_amount` of A.
+fn take(setup: &Setup, token_program: (Pubkey, AccountSharedData), vault_amount: u64) -> InstructionResult {
+    let Setup { taker, vault, maker_ta_b, taker_ta_a, taker_ta_b, .. } = *setup;
+
+    setup.mollusk.process_instruction(
+        &Instruction::new_with_bytes(
+            ID,
+            &[1],
+            vec![
+                AccountMeta::new(taker, true),
+                AccountMeta::new(setup.maker, false),
+                AccountMeta::new_readonly(setup.mint_a, false),
+                AccountMeta::new_readonly(setup.mint_b, false),
+                AccountMeta::new(setup.escrow, false),
+                AccountMeta::new(maker_ta_b, false),
+                AccountMeta::new(taker_ta_a, false),
+                AccountMeta::new(taker_ta_b, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(token_program.0, false),
+                AccountMeta::new_readonly(setup.system_program.0, false),
+            ],
+        ),
+        &[
+            (taker, wallet()),
+            (setup.maker, wallet()),
+            (setup.mint_a, mint(&setup.mollusk)),
+            (setup.mint_b, mint(&setup.mollusk)),
+            (setup.escrow, escrow_account(setup)),
+            (maker_ta_b, token_account(&setup.mollusk, setup.mint_b, setup.maker, 0)),
+            (taker_ta_a, token_account(&setup.mollusk, setup.mint_a, taker, 0)),
+            (taker_ta_b, token_account(&setup.mollusk, setup.mint_b, taker, RECEIVE)),
+            (vault, token_account(&setup.mollusk, setup.mint_a, setup.escrow, vault_amount)),
+            token_program,
+            setup.system_program.clone(),
+        ],
+    )
+}
+
+/// Refunds the offer with `vault` holding `vault_amount` of A.
+fn refund(setup: &Setup, token_program: (Pubkey, AccountSharedData), vault_amount: u64) -> InstructionResult {
+    let Setup { vault, maker_ta_a, .. } = *setup;
+
+    setup.mollusk.process_instruction(
+        &Instruction::new_with_bytes(
+            ID,
+            &[2],
+            vec![
+                AccountMeta::new(setup.maker, true),
+                AccountMeta::new_readonly(setup.mint_a, false),
+                AccountMeta::new(setup.escrow, false),
+                AccountMeta::new(maker_ta_a, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(token_program.0, false),
+                AccountMeta::new_readonly(setup.system_program.0, false),
+            ],
+        ),
+        &[
+            (setup.maker, wallet()),
+            (setup.mint_a, mint(&setup.mollusk)),
+            (setup.escrow, escrow_account(setup)),
+            (maker_ta_a, token_account(&setup.mollusk, setup.mint_a, setup.maker, 0)),
+            (vault, token_account(&setup.mollusk, setup.mint_a, setup.escrow, vault_amount)),
+            token_program,
+            setup.system_program.clone(),
+        ],
+    )
+}
+
+#[test]
+fn make_into_a_token_account() {
+    let setup = setup();
+
+    let result = make(&setup, token_account(&setup.mollusk, setup.mint_a, setup.escrow, 0));
+
+    assert!(!result.program_result.is_err());
+    assert_eq!(token_amount(&result, &setup.vault), AMOUNT);
+    assert_eq!(token_amount(&result, &setup.maker_ta_a), 0);
+
+    let escrow = result.get_account(&setup.escrow).unwrap();
+    assert_eq!(escrow.owner(), &ID);
+    let escrow = bytemuck::from_bytes::<Escrow>(escrow.data());
+    assert_eq!(escrow.maker, setup.maker);
+    assert_eq!(escrow.receive, RECEIVE);
+    assert_eq!(escrow.bump, setup.bump as u64);
+}
+
+#[test]
+fn make_into_a_vault_the_token_program_doesnt_own() {
+    let setup = setup();
+
+    let mut vault = token_account(&setup.mollusk, setup.mint_a, setup.escrow, 0);
+    vault.set_owner(Pubkey::default());
+
+    assert!(make(&setup, vault).program_result.is_err());
+}
+
+#[test]
+fn take_swaps_the_vault_for_receive() {
+    let setup = setup();
+
+    let result = take(&setup, setup.token_program.clone(), AMOUNT);
+
+    assert!(!result.program_result.is_err());
+    assert_eq!(token_amount(&result, &setup.taker_ta_a), AMOUNT);
+    assert_eq!(token_amount(&result, &setup.maker_ta_b), RECEIVE);
+    assert_eq!(token_amount(&result, &setup.taker_ta_b), 0);
+    assert_eq!(result.get_account(&setup.vault).unwrap().lamports(), 0);
+    assert_eq!(result.get_account(&setup.escrow).unwrap().lamports(), 0);
+}
+
+#[test]
+fn refund_returns_the_vault_balance() {
+    let setup = setup();
+
+    // `receive` is an amount of B, what goes back is whatever A the vault holds.
+    let result = refund(&setup, setup.token_program.clone(), AMOUNT);
+
+    assert!(!result.program_result.is_err());
+    assert_eq!(token_amount(&result, &setup.maker_ta_a), AMOUNT);
+    assert_eq!(result.get_account(&setup.vault).unwrap().lamports(), 0);
+    assert_eq!(result.get_account(&setup.escrow).unwrap().lamports(), 0);
+    assert_eq!(
+        result.get_account(&setup.maker).unwrap().lamports(),
+        1_000_000_000
+            + setup.mollusk.sysvars.rent.minimum_balance(Escrow::LEN)
+            + setup.mollusk.sysvars.rent.minimum_balance(spl_token::state::Account::LEN)
+    );
+}
+
+#[test]
+fn take_and_refund_with_another_token_program() {
+    let setup = setup();
+    let token_program = (Pubkey::new_unique(), setup.token_program.1.clone());
+
+    assert!(take(&setup, token_program.clone(), AMOUNT).program_result.is_err());
+    assert!(refund(&setup, token_program, AMOUNT).program_result.is_err());
+}
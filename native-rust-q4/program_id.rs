@@ -0,0 +1,104 @@
+//! # Program IDs
+//!
+//! Shared by the build scripts of every program crate and the bench, so a program's ID is
+//! decided in exactly one place: its on-chain constant, the PDAs derived from it and the
+//! Mollusk tests all `include!` what gets generated here and can't drift apart.
+//!
+//! -- Sources --
+//! For a program like `vault`, the ID comes from one of:
+//! > `VAULT_PROGRAM_ID`: a base58 address
+//! > `VAULT_PROGRAM_KEYPAIR`: a keypair file like the ones `solana-keygen` and `cargo build-sbf`
+//! > write, relative to the crate being built
+//! > the address in `DEFAULTS`, when neither is set
+//!
+//! Setting both variables is an error rather than a guess. The generated file is just the
+//! `[u8; 32]` array, each crate wraps it in whatever its SDK calls a pubkey.
+
+use std::{env, fs, path::Path};
+
+/// Programs and the addresses they build with when nothing else is set.
+pub const DEFAULTS: [(&str, &str); 3] = [
+    ("vault", "9HFegTZnvebYjf9kSa6k3WBm93hRfogWB5B1goUrq1oL"),
+    ("escrow", "22222222222222222222222222222222222222222222"),
+    ("escrow_native", "22222222222222222222222222222222222222222222"),
+];
+
+const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Writes the ID of `program` to `$OUT_DIR/<file>` and tells cargo what to rebuild it on.
+pub fn generate(program: &str, file: &str) {
+    let id = resolve(program);
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join(file);
+
+    fs::write(out, format!("{id:?}")).unwrap();
+}
+
+/// The ID `program` builds with, see the module docs for where it comes from.
+pub fn resolve(program: &str) -> [u8; 32] {
+    let prefix = program.to_uppercase();
+    let id_var = format!("{prefix}_PROGRAM_ID");
+    let keypair_var = format!("{prefix}_PROGRAM_KEYPAIR");
+
+    println!("cargo:rerun-if-env-changed={id_var}");
+    println!("cargo:rerun-if-env-changed={keypair_var}");
+
+    match (env::var(&id_var), env::var(&keypair_var)) {
+        (Ok(_), Ok(_)) => panic!("set either {id_var} or {keypair_var}, not both"),
+        (Ok(address), _) => {
+            decode(address.trim()).unwrap_or_else(|| panic!("{id_var} isn't a base58 address: {address}"))
+        }
+        (_, Ok(path)) => {
+            println!("cargo:rerun-if-changed={path}");
+
+            let keypair = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{keypair_var}: {path}: {e}"));
+            pubkey_of(&keypair).unwrap_or_else(|| panic!("{keypair_var}: {path} isn't a keypair file"))
+        }
+        _ => {
+            let (_, address) = DEFAULTS
+                .iter()
+                .find(|(name, _)| *name == program)
+                .unwrap_or_else(|| panic!("no default ID for {program}"));
+
+            decode(address).unwrap()
+        }
+    }
+}
+
+/// Decodes a base58 address, `None` unless it is exactly 32 bytes.
+pub fn decode(address: &str) -> Option<[u8; 32]> {
+    // Big-endian, most significant byte first
+    let mut bytes = [0u8; 32];
+
+    for c in address.bytes() {
+        let mut carry = ALPHABET.iter().position(|&a| a == c)? as u32;
+
+        for byte in bytes.iter_mut().rev() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+
+        if carry != 0 {
+            return None;
+        }
+    }
+
+    // Every leading '1' is a leading zero byte, so a short address doesn't get padded into one
+    let ones = address.bytes().take_while(|&c| c == b'1').count();
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+
+    (ones == zeros).then_some(bytes)
+}
+
+/// The public key of a keypair file: a JSON array of 64 bytes, the last 32 being the pubkey.
+pub fn pubkey_of(keypair: &str) -> Option<[u8; 32]> {
+    let bytes = keypair
+        .trim()
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .split(',')
+        .map(|byte| byte.trim().parse::<u8>().ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    (bytes.len() == 64).then(|| bytes[32..].try_into().unwrap())
+}
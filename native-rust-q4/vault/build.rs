@@ -0,0 +1,7 @@
+#[path = "../program_id.rs"]
+mod program_id;
+
+fn main() {
+    println!("cargo:rerun-if-changed=../program_id.rs");
+    program_id::generate("vault", "program_id.rs");
+}
@@ -1,84 +1,544 @@
-pub const ID: [u8; 32] = [
-    0x7b, 0x07, 0x5a, 0x4f, 0xca, 0x15, 0x61, 0x6e, 
-    0xbe, 0x53, 0xc1, 0xa8, 0x43, 0x6f, 0x42, 0x89, 
-    0x2b, 0x02, 0x1a, 0xb6, 0x62, 0x5a, 0x2a, 0x02, 
-    0x2a, 0x68, 0x9a, 0xef, 0xbd, 0xed, 0x26, 0xef
-];
+const SYSTEM_PROGRAM_ID: [u8; 32] = [0; 32];
+
+use crate::{
+    ID,
+    error::VaultError,
+    input::{Account, Input, LAMPORTS, DATA},
+    instruction::VaultInstruction,
+    state::{minimum_balance, Delegate, VaultState},
+};
 
 #[allow(unused)]
 extern "C" {
     fn sol_sha256(vals: *const u8, val_len: u64, hash_result: *mut [u8;32]) -> u64;
     fn sol_log_(input: *const u8, len: u64) -> u64;
+    fn sol_get_clock_sysvar(addr: *mut u8) -> u64;
+    fn sol_get_rent_sysvar(addr: *mut u8) -> u64;
+    #[cfg(feature = "canonical-bump")]
+    fn sol_try_find_program_address(
+        seeds: *const u8,
+        seeds_len: u64,
+        program_id: *const [u8; 32],
+        address: *mut [u8; 32],
+        bump: *mut u8,
+    ) -> u64;
+    fn sol_invoke_signed_c(
+        instruction: *const SolInstruction,
+        account_infos: *const SolAccountInfo,
+        account_infos_len: u64,
+        signers_seeds: *const SolSignerSeeds,
+        signers_seeds_len: u64,
+    ) -> u64;
+}
+
+// The C ABI structs `sol_invoke_signed_c` expects, see `sdk/sbf/c/inc/sol/cpi.h`.
+#[repr(C)]
+struct SolInstruction {
+    program_id: *const [u8; 32],
+    accounts: *const SolAccountMeta,
+    account_len: u64,
+    data: *const u8,
+    data_len: u64,
+}
+
+#[repr(C)]
+struct SolAccountMeta {
+    pubkey: *const [u8; 32],
+    is_writable: bool,
+    is_signer: bool,
+}
+
+#[repr(C)]
+struct SolAccountInfo {
+    key: *const [u8; 32],
+    lamports: *mut u64,
+    data_len: u64,
+    data: *mut u8,
+    owner: *const [u8; 32],
+    rent_epoch: u64,
+    is_signer: bool,
+    is_writable: bool,
+    executable: bool,
+}
+
+#[repr(C)]
+struct SolSignerSeed {
+    addr: *const u8,
+    len: u64,
+}
+
+#[repr(C)]
+struct SolSignerSeeds {
+    addr: *const SolSignerSeed,
+    len: u64,
+}
+
+/// Builds the CPI view of a serialized account.
+#[inline(always)]
+unsafe fn account_info(account: Account) -> SolAccountInfo {
+    SolAccountInfo {
+        key: account.key(),
+        lamports: account.as_ptr().add(LAMPORTS) as *mut u64,
+        data_len: account.data_len() as u64,
+        data: account.as_ptr().add(DATA),
+        owner: account.owner(),
+        rent_epoch: 0,
+        is_signer: account.is_signer(),
+        is_writable: account.is_writable(),
+        executable: account.executable(),
+    }
 }
 
 #[no_mangle]
 /// # Safety
 /// Where we're going, we don't need memory safety.
-pub unsafe extern "C" fn entrypoint(input: *mut u8) {
-    use core::mem::MaybeUninit;
+///
+/// -- Accounts --
+/// > signer/payer, a signer
+/// > vault, no duplicate, with 0, `VaultState::LOCK_LEN`, or `VaultState::LEN` plus
+/// > delegates bytes data
+/// > the system program (deposit) or a destination (withdraw), if any
+///
+/// `Input` walks the accounts wherever they sit, so any of them can carry data and the
+/// destination can be a duplicate of the signer or the vault.
+///
+/// Multisig, token and stream instructions aren't implemented here and fail with
+/// `UnsupportedInstruction` whatever the accounts, the other backends handle them.
+pub unsafe extern "C" fn entrypoint(input: *mut u8) -> u64 {
+    let input = Input::<3>::parse(input);
 
-    if *(input as *const u64) != 2 {
-        sol_log_("Invalid number of accounts".as_ptr(), 26);
-        core::arch::asm!("lddw r0, 1");
-        return;
+    let Ok(instruction) = VaultInstruction::try_from(input.data()) else {
+        sol_log_("Invalid instruction data".as_ptr(), 24);
+        // ProgramError::InvalidInstructionData
+        return 0x3_0000_0000;
+    };
+
+    match process(&input, instruction) {
+        Ok(()) => 0,
+        Err(error) => fail(error),
+    }
+}
+
+/// Logs `error` and returns its code for the runtime.
+#[inline(always)]
+unsafe fn fail(error: VaultError) -> u64 {
+    let message = error.message();
+    sol_log_(message.as_ptr(), message.len() as u64);
+    error.code() as u64
+}
+
+#[inline(always)]
+unsafe fn process(input: &Input<3>, instruction: VaultInstruction) -> Result<(), VaultError> {
+    if matches!(
+        instruction,
+        VaultInstruction::CreateMultisig { .. }
+            | VaultInstruction::RotateMembers { .. }
+            | VaultInstruction::WithdrawMultisig { .. }
+            | VaultInstruction::DepositToken { .. }
+            | VaultInstruction::WithdrawToken { .. }
+            | VaultInstruction::CreateStream { .. }
+            | VaultInstruction::WithdrawStream { .. }
+            | VaultInstruction::CancelStream { .. }
+    ) {
+        return Err(VaultError::UnsupportedInstruction);
     }
 
-    // Ensure signer had 0 bytes data length
-    if *(input.add(0x0058) as *const u64) != 0 {
-        sol_log_("Invalid account length Signer".as_ptr(), 29);
-        core::arch::asm!("lddw r0, 2");
-        return;  
+    let ([signer, vault] | [signer, vault, _]) = input.accounts() else {
+        return Err(VaultError::InvalidAccountCount);
     };
 
-    // Ensure signer is a mutable no-duplicate signer
-    if *(input.add(0x0008) as *const u32) != 0x0101ff {
-        sol_log_("Signer is not a mutable nodup signer".as_ptr(), 36);
-        core::arch::asm!("lddw r0, 5");
-        return;  
+    if input.account_count() > 3 {
+        return Err(VaultError::InvalidAccountCount);
     }
 
-    // // Ensure PDA has 0 bytes data length
-    if *(input.add(0x28b8) as *const u64) != 0 {
-        sol_log_("Invalid account length Vault".as_ptr(), 28);
-        core::arch::asm!("lddw r0, 3");
-        return;  
+    // Ensure signer signed, coming first it's never a duplicate. Like the other backends we
+    // leave it to the runtime to refuse lamports moving into a readonly one
+    if !signer.is_signer() {
+        return Err(VaultError::MissingSignature);
     }
 
-    unsafe {
-        // Get the signer key
-        let signer: [u8; 32] = *(input.add(0x0010) as *const [u8; 32]);
-    
-        // Get the bump
-        let bump = *input.add(0x50d8) as u8;
+    let (signer, vault) = (*signer, *vault);
 
-        let data = [
-            signer.as_ref(),
-            &[bump],
-            ID.as_ref(),
-            b"ProgramDerivedAddress",
-        ];
+    match (input.accounts().get(2), instruction) {
+        (destination, VaultInstruction::Withdraw { lamports, bump }) => {
+            withdraw(signer, vault, destination.copied(), lamports, bump, false)
+        }
+        (destination, VaultInstruction::WithdrawKeepRentExempt { lamports, bump }) => {
+            withdraw(signer, vault, destination.copied(), lamports, bump, true)
+        }
+        (Some(_), VaultInstruction::Deposit { lamports, bump, owner }) if !input.is_duplicate(2) => {
+            deposit(signer, vault, lamports, bump, &owner)
+        }
+        (None, VaultInstruction::Close { bump }) => close(signer, vault, bump),
+        (None, VaultInstruction::SetLock { unlock_timestamp, unlock_slot, bump }) => {
+            set_lock(signer, vault, unlock_timestamp, unlock_slot, bump)
+        }
+        (None, VaultInstruction::SetRateLimit { limit, bump }) => set_rate_limit(signer, vault, limit, bump),
+        (None, VaultInstruction::SetDelegate { delegate, bump }) => set_delegate(signer, vault, &delegate, bump),
+        (None, VaultInstruction::RevokeDelegate { delegate, bump }) => {
+            revoke_delegate(signer, vault, &delegate, bump)
+        }
+        _ => Err(VaultError::InvalidAccountCount),
+    }
+}
+
+/// Checks that `vault` is the PDA of `[owner, bump]`. With `canonical-bump` the bump also has
+/// to be the canonical one, so an owner has exactly one vault.
+#[inline(always)]
+unsafe fn check_pda(owner: &[u8; 32], bump: &[u8; 1], vault: Account) -> Result<(), VaultError> {
+    use core::mem::MaybeUninit;
+
+    let data = [
+        owner.as_ref(),
+        bump.as_ref(),
+        ID.as_ref(),
+        b"ProgramDerivedAddress",
+    ];
+
+    let mut pda = MaybeUninit::<[u8; 32]>::uninit();
+    sol_sha256(
+        &data as *const _ as *const u8,
+        4,
+        pda.as_mut_ptr(),
+    );
+
+    if vault.key() != &*pda.as_ptr() {
+        return Err(VaultError::InvalidPda);
+    }
 
-        let mut pda = MaybeUninit::<[u8; 32]>::uninit();
-        sol_sha256(
-            &data as *const _ as *const u8,
-            4,
+    #[cfg(feature = "canonical-bump")]
+    {
+        let seeds = [owner.as_ref()];
+        let mut canonical = 0u8;
+
+        if sol_try_find_program_address(
+            &seeds as *const _ as *const u8,
+            1,
+            &ID,
             pda.as_mut_ptr(),
+            &mut canonical,
+        ) != 0 {
+            return Err(VaultError::InvalidPda);
+        }
+
+        if bump[0] != canonical {
+            return Err(VaultError::NonCanonicalBump);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks what the PDA doesn't say about `vault`: it isn't a duplicate of `signer` and holds
+/// either only lamports or a `VaultState`. Only after the PDA, like the other backends, so a
+/// wrong vault fails the same way everywhere.
+#[inline(always)]
+fn check_vault(signer: Account, vault: Account) -> Result<(), VaultError> {
+    if vault == signer {
+        return Err(VaultError::InvalidAccount);
+    }
+
+    if !VaultState::is_valid_len(vault.data_len()) {
+        return Err(VaultError::InvalidVaultData);
+    }
+
+    Ok(())
+}
+
+/// Reads the `VaultState` `check_vault` already checked the length of.
+#[inline(always)]
+unsafe fn vault_state(vault: Account) -> VaultState {
+    VaultState::load(vault.data()).unwrap_unchecked()
+}
+
+/// Clock: [slot, epoch_start_timestamp, epoch, leader_schedule_epoch, unix_timestamp]
+#[inline(always)]
+unsafe fn clock() -> [u64; 5] {
+    let mut clock = [0u64; 5];
+    sol_get_clock_sysvar(clock.as_mut_ptr() as *mut u8);
+    clock
+}
+
+/// Rent: [lamports_per_byte_year, exemption_threshold (f64), burn_percent (u8)]
+#[inline(always)]
+unsafe fn rent_exempt_minimum(data_len: usize) -> u64 {
+    let mut rent = [0u64; 3];
+    sol_get_rent_sysvar(rent.as_mut_ptr() as *mut u8);
+    minimum_balance(rent[0], f64::from_bits(rent[1]), data_len)
+}
+
+/// Moves `lamports` out of `vault` into `destination`, which can be the vault itself. With
+/// `keep_rent_exempt`, a vault holding data can't go below its rent-exempt minimum.
+#[inline(always)]
+unsafe fn move_lamports(vault: Account, destination: Account, lamports: u64, keep_rent_exempt: bool) -> Result<(), VaultError> {
+    let balance = vault.lamports().checked_sub(lamports).ok_or(VaultError::InsufficientFunds)?;
+
+    if keep_rent_exempt && vault.data_len() != 0 && balance < rent_exempt_minimum(vault.data_len()) {
+        return Err(VaultError::BelowRentExempt);
+    }
+
+    vault.set_lamports(balance);
+
+    let credited = destination.lamports().checked_add(lamports).ok_or(VaultError::ArithmeticOverflow)?;
+    destination.set_lamports(credited);
+
+    Ok(())
+}
+
+/// Checks that the vault isn't time-locked and that `lamports` fit in its rate limit, and
+/// counts them against the limit. Withdrawals by a `delegate` also have to fit in its
+/// allowance. Owners of vaults without data never read the Clock.
+#[inline(always)]
+unsafe fn check_withdrawal(vault: Account, lamports: u64, delegate: Option<&[u8; 32]>) -> Result<(), VaultError> {
+    if vault.data_len() == 0 && delegate.is_none() {
+        return Ok(());
+    }
+
+    let mut state = vault_state(vault);
+    let [slot, _, epoch, _, unix_timestamp] = clock();
+
+    if !state.is_unlocked(unix_timestamp as i64, slot) {
+        return Err(VaultError::VaultLocked);
+    }
+
+    state.record_withdrawal(lamports, epoch, slot)?;
+
+    let data = vault.data_mut();
+    state.store(data);
+
+    if let Some(key) = delegate {
+        let offset = VaultState::find_delegate(data, key).ok_or(VaultError::NotADelegate)?;
+
+        let mut delegate = Delegate::load(&data[offset..])?;
+        if !delegate.spend(lamports, unix_timestamp as i64) {
+            return Err(VaultError::AllowanceExceeded);
+        }
+        delegate.store(&mut data[offset..]);
+    }
+
+    Ok(())
+}
+
+/// # Withdraw
+///
+/// Moves `lamports` from the vault to `destination`, or without one to the signer that seeds
+/// it. A signer with a destination that doesn't seed the vault may still be a delegate.
+#[inline(always)]
+unsafe fn withdraw(signer: Account, vault: Account, destination: Option<Account>, lamports: u64, bump: u8, keep_rent_exempt: bool) -> Result<(), VaultError> {
+    // Check PDA address
+    match (check_pda(signer.key(), &[bump], vault), destination) {
+        (Ok(()), _) => {}
+        (Err(VaultError::InvalidPda), Some(destination)) => {
+            return withdraw_delegated(signer, vault, destination, lamports, keep_rent_exempt);
+        }
+        (Err(error), _) => return Err(error),
+    }
+
+    check_vault(signer, vault)?;
+
+    check_withdrawal(vault, lamports, None)?;
+
+    move_lamports(vault, destination.unwrap_or(signer), lamports, keep_rent_exempt)
+}
+
+/// # Withdraw Delegated
+///
+/// Moves `lamports` from the vault to `destination` for one of its delegates. Only the owner
+/// adds delegates, so a vault of ours listing the signer is all there is to check.
+#[inline(always)]
+unsafe fn withdraw_delegated(delegate: Account, vault: Account, destination: Account, lamports: u64, keep_rent_exempt: bool) -> Result<(), VaultError> {
+    // Multisig configs are ours too, but never pass the vault length check
+    if vault.owner() != &ID {
+        return Err(VaultError::InvalidAccountOwner);
+    }
+
+    check_vault(delegate, vault)?;
+
+    check_withdrawal(vault, lamports, Some(delegate.key()))?;
+
+    move_lamports(vault, destination, lamports, keep_rent_exempt)
+}
+
+/// # Deposit
+///
+/// Transfers from the payer with a system program CPI, and assigns the vault to us on its
+/// first deposit so `withdraw` can debit it later.
+#[inline(always)]
+unsafe fn deposit(payer: Account, vault: Account, lamports: u64, bump: u8, owner: &[u8; 32]) -> Result<(), VaultError> {
+    let bump = [bump];
+
+    // Check PDA address
+    check_pda(owner, &bump, vault)?;
+    check_vault(payer, vault)?;
+
+    let accounts = [account_info(payer), account_info(vault)];
+
+    // System program `Transfer`: [2u32, lamports]
+    let mut transfer = [0u8; 12];
+    transfer[0] = 2;
+    *(transfer.as_mut_ptr().add(4) as *mut [u8; 8]) = lamports.to_le_bytes();
+
+    let metas = [
+        SolAccountMeta { pubkey: accounts[0].key, is_writable: true, is_signer: true },
+        SolAccountMeta { pubkey: accounts[1].key, is_writable: true, is_signer: false },
+    ];
+
+    sol_invoke_signed_c(
+        &SolInstruction {
+            program_id: &SYSTEM_PROGRAM_ID,
+            accounts: metas.as_ptr(),
+            account_len: 2,
+            data: transfer.as_ptr(),
+            data_len: 12,
+        },
+        accounts.as_ptr(),
+        2,
+        core::ptr::null(),
+        0,
+    );
+
+    // Claim the vault on its first deposit
+    if vault.owner() != &ID {
+        // System program `Assign`: [1u32, owner]
+        let mut assign = [0u8; 36];
+        assign[0] = 1;
+        *(assign.as_mut_ptr().add(4) as *mut [u8; 32]) = ID;
+
+        let metas = [SolAccountMeta { pubkey: accounts[1].key, is_writable: true, is_signer: true }];
+        let signer_seeds = [
+            SolSignerSeed { addr: owner.as_ptr(), len: 32 },
+            SolSignerSeed { addr: bump.as_ptr(), len: 1 },
+        ];
+
+        sol_invoke_signed_c(
+            &SolInstruction {
+                program_id: &SYSTEM_PROGRAM_ID,
+                accounts: metas.as_ptr(),
+                account_len: 1,
+                data: assign.as_ptr(),
+                data_len: 36,
+            },
+            accounts.as_ptr().add(1),
+            1,
+            &SolSignerSeeds { addr: signer_seeds.as_ptr(), len: 2 },
+            1,
         );
+    }
 
-        // Check PDA address
-        if *(input.add(0x2870) as *const [u8; 32]) != *pda.as_ptr() {
-            {
-                sol_log_("Invalid PDA address".as_ptr(), 29);
-                core::arch::asm!("lddw r0, 4");
-            }
-            return;  
-        };
+    Ok(())
+}
+
+/// # Close
+///
+/// Sends everything in the vault back to the signer that seeds it, leaving the PDA with 0
+/// lamports so the runtime garbage collects it. Its data is zeroed and dropped and it goes
+/// back to the system program, nothing of the vault survives for a later instruction.
+#[inline(always)]
+unsafe fn close(signer: Account, vault: Account, bump: u8) -> Result<(), VaultError> {
+    // Check PDA address
+    check_pda(signer.key(), &[bump], vault)?;
+    check_vault(signer, vault)?;
+
+    check_withdrawal(vault, vault.lamports(), None)?;
+
+    // Move every lamport from PDA to Signer
+    move_lamports(vault, signer, vault.lamports(), false)?;
+
+    vault.data_mut().fill(0);
+    vault.resize(0)?;
+    vault.assign(&SYSTEM_PROGRAM_ID);
+
+    Ok(())
+}
+
+/// # Set Lock
+///
+/// Stores the lock in the vault, growing it to `VaultState::LOCK_LEN` bytes on its first lock.
+/// The serialized data is followed by 10 KiB of realloc padding, so growing it is just
+/// bumping its length in place.
+#[inline(always)]
+unsafe fn set_lock(signer: Account, vault: Account, unlock_timestamp: i64, unlock_slot: u64, bump: u8) -> Result<(), VaultError> {
+    // Check PDA address
+    check_pda(signer.key(), &[bump], vault)?;
+    check_vault(signer, vault)?;
+
+    let mut state = vault_state(vault);
+    if !state.extend_lock(unlock_timestamp, unlock_slot) {
+        return Err(VaultError::LockNotExtended);
     }
-    
-    let lamports: u64 = unsafe { *(input.add(0x50d0) as *const u64) };
 
-    // Deduct lamports from PDA
-    *(input.add(0x28b0) as *mut u64) -= lamports;
-    // Add lamports to Signer
-    *(input.add(0x0050) as *mut u64) += lamports;
-}
\ No newline at end of file
+    vault.resize(vault.data_len().max(VaultState::LOCK_LEN))?;
+    state.store(vault.data_mut());
+
+    Ok(())
+}
+
+/// # Set Rate Limit
+///
+/// Stores the limit in the vault, growing it to `VaultState::LEN` bytes on its first limit.
+#[inline(always)]
+unsafe fn set_rate_limit(signer: Account, vault: Account, limit: u64, bump: u8) -> Result<(), VaultError> {
+    // Check PDA address
+    check_pda(signer.key(), &[bump], vault)?;
+    check_vault(signer, vault)?;
+
+    let mut state = vault_state(vault);
+    state.set_limit(limit, clock()[0]);
+
+    vault.resize(vault.data_len().max(VaultState::LEN))?;
+    state.store(vault.data_mut());
+
+    Ok(())
+}
+
+/// # Set Delegate
+///
+/// Adds `delegate` to the vault or replaces its allowance and expiry, growing the vault by
+/// `Delegate::LEN` bytes past `VaultState::LEN` for every new one.
+#[inline(always)]
+unsafe fn set_delegate(signer: Account, vault: Account, delegate: &Delegate, bump: u8) -> Result<(), VaultError> {
+    // Check PDA address
+    check_pda(signer.key(), &[bump], vault)?;
+    check_vault(signer, vault)?;
+
+    let state = vault_state(vault);
+
+    let offset = match VaultState::find_delegate(vault.data(), &delegate.key) {
+        Some(offset) => offset,
+        None => {
+            let len = vault.data_len().max(VaultState::LEN);
+            if len == VaultState::LEN + VaultState::MAX_DELEGATES * Delegate::LEN {
+                return Err(VaultError::TooManyDelegates);
+            }
+
+            vault.resize(len + Delegate::LEN)?;
+            len
+        }
+    };
+
+    let data = vault.data_mut();
+    state.store(data);
+    delegate.store(&mut data[offset..]);
+
+    Ok(())
+}
+
+/// # Revoke Delegate
+///
+/// Moves the last delegate into the place of `delegate` and shrinks the vault by
+/// `Delegate::LEN` bytes.
+#[inline(always)]
+unsafe fn revoke_delegate(signer: Account, vault: Account, delegate: &[u8; 32], bump: u8) -> Result<(), VaultError> {
+    // Check PDA address
+    check_pda(signer.key(), &[bump], vault)?;
+    check_vault(signer, vault)?;
+
+    let data = vault.data_mut();
+    let offset = VaultState::find_delegate(data, delegate).ok_or(VaultError::NotADelegate)?;
+
+    let last = data.len() - Delegate::LEN;
+    data.copy_within(last.., offset);
+    vault.resize(last)?;
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use crate::state::{InvalidVaultData, RateLimitExceeded};
+
+/// # Vault errors
+///
+/// Every backend fails with `ProgramError::Custom(code)` for these, so a client sees the
+/// same code whichever build it talks to. Like discriminators, codes are append-only: once
+/// shipped, an error keeps its number. Malformed instruction data is the exception and stays
+/// `ProgramError::InvalidInstructionData`.
+///
+/// -- Decoding --
+/// `VaultError::from_code` turns a custom code back into an error and `from_log` finds one in
+/// a transaction's logs, `message` is what goes into client logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum VaultError {
+    /// Too few or too many accounts for the instruction
+    InvalidAccountCount = 1,
+    /// A duplicate, or the wrong program, where the instruction needs a specific account
+    InvalidAccount = 2,
+    /// Vault or multisig config data that isn't a `VaultState` or a `MultisigConfig`
+    InvalidVaultData = 3,
+    /// The vault isn't the PDA of its seeds
+    InvalidPda = 4,
+    /// The signer didn't sign
+    MissingSignature = 5,
+    VaultLocked = 6,
+    LockNotExtended = 7,
+    RateLimitExceeded = 8,
+    /// The signer isn't one of the vault's delegates
+    NotADelegate = 9,
+    /// The delegate's allowance doesn't cover the withdrawal, or it expired
+    AllowanceExceeded = 10,
+    TooManyDelegates = 11,
+    /// The vault holds fewer lamports than the withdrawal
+    InsufficientFunds = 12,
+    /// An account that has to be owned by this program isn't
+    InvalidAccountOwner = 13,
+    /// Fewer members than the threshold signed
+    NotApproved = 14,
+    MultisigAlreadyCreated = 15,
+    /// A token account that isn't held by the vault
+    InvalidTokenAccount = 16,
+    /// Crediting the lamports would overflow the destination's balance
+    ArithmeticOverflow = 17,
+    /// The withdrawal would leave a vault holding data below its rent-exempt minimum
+    BelowRentExempt = 18,
+    /// A vault PDA with a bump other than the canonical one, with `canonical-bump` only
+    NonCanonicalBump = 19,
+    /// An instruction this backend doesn't implement, see the backend for which
+    UnsupportedInstruction = 20,
+    /// `based` growing an account past its realloc padding, the other backends fail with
+    /// `ProgramError::InvalidRealloc` for this
+    InvalidRealloc = 21,
+}
+
+impl VaultError {
+    const ALL: [Self; 21] = [
+        Self::InvalidAccountCount,
+        Self::InvalidAccount,
+        Self::InvalidVaultData,
+        Self::InvalidPda,
+        Self::MissingSignature,
+        Self::VaultLocked,
+        Self::LockNotExtended,
+        Self::RateLimitExceeded,
+        Self::NotADelegate,
+        Self::AllowanceExceeded,
+        Self::TooManyDelegates,
+        Self::InsufficientFunds,
+        Self::InvalidAccountOwner,
+        Self::NotApproved,
+        Self::MultisigAlreadyCreated,
+        Self::InvalidTokenAccount,
+        Self::ArithmeticOverflow,
+        Self::BelowRentExempt,
+        Self::NonCanonicalBump,
+        Self::UnsupportedInstruction,
+        Self::InvalidRealloc,
+    ];
+
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|error| error.code() == code)
+    }
+
+    /// Finds the error in a runtime log line like
+    /// `Program <id> failed: custom program error: 0x8`.
+    pub fn from_log(log: &str) -> Option<Self> {
+        let (_, code) = log.split_once("custom program error: 0x")?;
+        Self::from_code(u32::from_str_radix(code.trim_end(), 16).ok()?)
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::InvalidAccountCount => "Invalid number of accounts",
+            Self::InvalidAccount => "Invalid account",
+            Self::InvalidVaultData => "Invalid vault data",
+            Self::InvalidPda => "Invalid PDA address",
+            Self::MissingSignature => "Missing required signature",
+            Self::VaultLocked => "Vault is locked",
+            Self::LockNotExtended => "Lock can only be extended",
+            Self::RateLimitExceeded => "Rate limit exceeded",
+            Self::NotADelegate => "Not a delegate",
+            Self::AllowanceExceeded => "Allowance exceeded or expired",
+            Self::TooManyDelegates => "Too many delegates",
+            Self::InsufficientFunds => "Insufficient funds",
+            Self::InvalidAccountOwner => "Invalid account owner",
+            Self::NotApproved => "Not approved by enough members",
+            Self::MultisigAlreadyCreated => "Multisig already created",
+            Self::InvalidTokenAccount => "Invalid token account",
+            Self::ArithmeticOverflow => "Arithmetic overflow",
+            Self::BelowRentExempt => "Vault would fall below rent-exempt",
+            Self::NonCanonicalBump => "Bump is not canonical",
+            Self::UnsupportedInstruction => "Instruction is not supported by this build",
+            Self::InvalidRealloc => "Account data can't grow that far",
+        }
+    }
+}
+
+impl From<InvalidVaultData> for VaultError {
+    fn from(_: InvalidVaultData) -> Self {
+        Self::InvalidVaultData
+    }
+}
+
+impl From<RateLimitExceeded> for VaultError {
+    fn from(_: RateLimitExceeded) -> Self {
+        Self::RateLimitExceeded
+    }
+}
+
+impl core::fmt::Display for VaultError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+/// Returns `error` unless `condition` holds, converted into the backend's error type.
+#[cfg(any(feature = "native", feature = "optimized"))]
+macro_rules! require {
+    ($condition:expr, $error:expr) => {
+        if !$condition {
+            return Err($error.into());
+        }
+    };
+}
+
+#[cfg(any(feature = "native", feature = "optimized"))]
+pub(crate) use require;
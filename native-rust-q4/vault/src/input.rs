@@ -0,0 +1,235 @@
+//! # Input
+//!
+//! Walks the serialized input the runtime hands to the `based` entrypoint, so accounts and
+//! instruction data are found wherever they are instead of at offsets that only hold for
+//! one account layout. Nothing here allocates or depends on anything but `core`.
+//!
+//! -- Layout --
+//! > account_count [u8; 8]
+//! > accounts [Account; account_count]
+//! > instruction_data_len [u8; 8]
+//! > instruction_data [u8; instruction_data_len]
+//! > program_id [u8; 32]
+//!
+//! -- Account --
+//! > dup_marker [u8; 1], `NON_DUP_MARKER`
+//! > is_signer [u8; 1]
+//! > is_writable [u8; 1]
+//! > executable [u8; 1]
+//! > original_data_len [u8; 4]
+//! > key [u8; 32]
+//! > owner [u8; 32]
+//! > lamports [u8; 8]
+//! > data_len [u8; 8]
+//! > data [u8; data_len]
+//! > realloc padding [u8; MAX_PERMITTED_DATA_INCREASE], aligned to 8
+//! > rent_epoch [u8; 8]
+//!
+//! A duplicate of an earlier account is only its index, padded to 8. `original_data_len`
+//! is padding as far as the runtime is concerned, `Input::parse` keeps the length the
+//! account came in with there so `Account::resize` knows how far it can grow.
+
+use core::mem::MaybeUninit;
+
+use crate::error::VaultError;
+
+/// How much an account's data can grow in one instruction.
+pub const MAX_PERMITTED_DATA_INCREASE: usize = 10 * 1024;
+
+/// Marks an account that isn't a duplicate of an earlier one.
+pub const NON_DUP_MARKER: u8 = 0xff;
+
+pub const IS_SIGNER: usize = 0x01;
+pub const IS_WRITABLE: usize = 0x02;
+pub const EXECUTABLE: usize = 0x03;
+pub const ORIGINAL_DATA_LEN: usize = 0x04;
+pub const KEY: usize = 0x08;
+pub const OWNER: usize = 0x28;
+pub const LAMPORTS: usize = 0x48;
+pub const DATA_LEN: usize = 0x50;
+pub const DATA: usize = 0x58;
+
+/// # Account
+///
+/// A view of one serialized account, pointing at its dup marker. Duplicates are views of
+/// the account they duplicate, so two views are equal exactly when they are the same
+/// account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Account(*mut u8);
+
+impl Account {
+    /// The dup marker of the account.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.0
+    }
+
+    pub fn is_signer(&self) -> bool {
+        unsafe { *self.0.add(IS_SIGNER) != 0 }
+    }
+
+    pub fn is_writable(&self) -> bool {
+        unsafe { *self.0.add(IS_WRITABLE) != 0 }
+    }
+
+    pub fn executable(&self) -> bool {
+        unsafe { *self.0.add(EXECUTABLE) != 0 }
+    }
+
+    pub fn key(&self) -> &[u8; 32] {
+        unsafe { &*(self.0.add(KEY) as *const [u8; 32]) }
+    }
+
+    pub fn owner(&self) -> &[u8; 32] {
+        unsafe { &*(self.0.add(OWNER) as *const [u8; 32]) }
+    }
+
+    pub fn assign(&self, owner: &[u8; 32]) {
+        unsafe { *(self.0.add(OWNER) as *mut [u8; 32]) = *owner }
+    }
+
+    pub fn lamports(&self) -> u64 {
+        unsafe { *(self.0.add(LAMPORTS) as *const u64) }
+    }
+
+    pub fn set_lamports(&self, lamports: u64) {
+        unsafe { *(self.0.add(LAMPORTS) as *mut u64) = lamports }
+    }
+
+    pub fn data_len(&self) -> usize {
+        unsafe { *(self.0.add(DATA_LEN) as *const u64) as usize }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.0.add(DATA), self.data_len()) }
+    }
+
+    /// # Safety
+    /// Views are `Copy`, nothing else may be holding on to the data of the same account.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn data_mut(&self) -> &mut [u8] {
+        core::slice::from_raw_parts_mut(self.0.add(DATA), self.data_len())
+    }
+
+    /// Grows or shrinks the data in place, which is followed by its realloc padding. Fails
+    /// past `MAX_PERMITTED_DATA_INCREASE` bytes more than the account came in with. Bytes it
+    /// grows into are zeroed, like `realloc(.., true)`, so nothing shrunk away comes back.
+    pub fn resize(&self, len: usize) -> Result<(), VaultError> {
+        unsafe {
+            let original = *(self.0.add(ORIGINAL_DATA_LEN) as *const u32) as usize;
+            if len > original + MAX_PERMITTED_DATA_INCREASE {
+                return Err(VaultError::InvalidRealloc);
+            }
+
+            let current = self.data_len();
+            if len > current {
+                core::ptr::write_bytes(self.0.add(DATA + current), 0, len - current);
+            }
+
+            *(self.0.add(DATA_LEN) as *mut u64) = len as u64;
+        }
+        Ok(())
+    }
+}
+
+/// # Input
+///
+/// The parsed input, keeping views of the first `N` accounts. Any accounts past those are
+/// still walked over to find the instruction data, `account_count` says how many there are.
+pub struct Input<const N: usize> {
+    accounts: [MaybeUninit<Account>; N],
+    markers: [u8; N],
+    account_count: usize,
+    data: *const u8,
+    data_len: usize,
+}
+
+impl<const N: usize> Input<N> {
+    /// # Safety
+    /// `input` has to be the buffer the runtime passed to the entrypoint, untouched.
+    pub unsafe fn parse(input: *mut u8) -> Self {
+        let account_count = *(input as *const u64) as usize;
+
+        // An array of `MaybeUninit` doesn't need initializing
+        let mut accounts: [MaybeUninit<Account>; N] = MaybeUninit::uninit().assume_init();
+        let mut markers = [NON_DUP_MARKER; N];
+        let mut cursor = input.add(8);
+
+        for i in 0..account_count {
+            let marker = *cursor;
+
+            if marker == NON_DUP_MARKER {
+                let account = Account(cursor);
+
+                *(cursor.add(ORIGINAL_DATA_LEN) as *mut u32) = account.data_len() as u32;
+                if i < N {
+                    accounts[i].write(account);
+                }
+            } else if i < N {
+                // Duplicates always point back, so the original is already in `accounts`
+                accounts[i].write(accounts[marker as usize].assume_init());
+                markers[i] = marker;
+            }
+
+            cursor = skip_account(cursor);
+        }
+
+        Self {
+            accounts,
+            markers,
+            account_count,
+            data: cursor.add(8),
+            data_len: *(cursor as *const u64) as usize,
+        }
+    }
+
+    /// How many accounts were passed, which can be more than `N`.
+    pub fn account_count(&self) -> usize {
+        self.account_count
+    }
+
+    /// Views of the first `N` accounts.
+    pub fn accounts(&self) -> &[Account] {
+        unsafe {
+            core::slice::from_raw_parts(self.accounts.as_ptr() as *const Account, self.account_count.min(N))
+        }
+    }
+
+    /// Whether account `i` duplicates an earlier one.
+    pub fn is_duplicate(&self, i: usize) -> bool {
+        self.markers[i] != NON_DUP_MARKER
+    }
+
+    pub fn data(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.data, self.data_len) }
+    }
+
+    pub fn program_id(&self) -> &[u8; 32] {
+        unsafe { &*(self.data.add(self.data_len) as *const [u8; 32]) }
+    }
+}
+
+/// Finds the instruction data by stepping over the accounts, without keeping or writing to
+/// any of them. The `lazy` entrypoint uses it to see the instruction before reading a single
+/// account.
+///
+/// # Safety
+/// `input` has to be the buffer the runtime passed to the entrypoint.
+pub unsafe fn instruction_data<'a>(input: *mut u8) -> &'a [u8] {
+    let mut cursor = input.add(8);
+
+    for _ in 0..*(input as *const u64) {
+        cursor = skip_account(cursor);
+    }
+
+    core::slice::from_raw_parts(cursor.add(8), *(cursor as *const u64) as usize)
+}
+
+/// Where the next account, or the instruction data after the last one, starts.
+#[inline(always)]
+unsafe fn skip_account(cursor: *mut u8) -> *mut u8 {
+    if *cursor == NON_DUP_MARKER {
+        cursor.add(DATA + ((Account(cursor).data_len() + MAX_PERMITTED_DATA_INCREASE + 7) & !7) + 8)
+    } else {
+        cursor.add(8)
+    }
+}
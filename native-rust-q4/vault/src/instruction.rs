@@ -0,0 +1,330 @@
+use crate::state::{Delegate, MultisigConfig, Stream};
+
+/// # Instructions
+///
+/// -- Wire format --
+/// > discriminator [u8; 1]
+/// > arguments, packed and little-endian, see each variant
+///
+/// -- Versioning --
+/// Discriminators are append-only: once shipped, an instruction keeps its number and its
+/// argument layout. Changing what an instruction takes means adding a new discriminator.
+///
+/// -- Decoding --
+/// Every backend (`optimized`, `native` and `based`) decodes through
+/// `VaultInstruction::try_from`, so they all agree on what is valid. The data has to be
+/// exactly as long as the variant needs, anything shorter or longer is rejected instead of
+/// read out of bounds or silently ignored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VaultInstruction {
+    /// Accounts: [signer, vault] for the owner, [signer, vault, destination] for the owner
+    /// paying out to `destination` instead, [delegate, vault, destination] for one of its
+    /// delegates, which don't need the bump
+    ///
+    /// > lamports [u8; 8]
+    /// > bump [u8; 1]
+    Withdraw { lamports: u64, bump: u8 },
+    /// Accounts: [payer, vault, system_program]
+    ///
+    /// > lamports [u8; 8]
+    /// > bump [u8; 1]
+    /// > owner [u8; 32]
+    Deposit { lamports: u64, bump: u8, owner: [u8; 32] },
+    /// Accounts: [signer, vault]
+    ///
+    /// > bump [u8; 1]
+    Close { bump: u8 },
+    /// Accounts: [signer, vault]
+    ///
+    /// > unlock_timestamp [u8; 8]
+    /// > unlock_slot [u8; 8]
+    /// > bump [u8; 1]
+    SetLock { unlock_timestamp: i64, unlock_slot: u64, bump: u8 },
+    /// Accounts: [config]
+    ///
+    /// > threshold [u8; 1]
+    /// > member_count [u8; 1]
+    /// > members [[u8; 32]; member_count]
+    CreateMultisig { config: MultisigConfig },
+    /// Accounts: [config, ..members]
+    ///
+    /// > threshold [u8; 1]
+    /// > member_count [u8; 1]
+    /// > members [[u8; 32]; member_count]
+    RotateMembers { config: MultisigConfig },
+    /// Accounts: [config, vault, destination, system_program, ..members]
+    ///
+    /// > lamports [u8; 8]
+    /// > bump [u8; 1]
+    WithdrawMultisig { lamports: u64, bump: u8 },
+    /// Accounts: [payer, payer_token_account, vault_token_account, token_program]
+    ///
+    /// > amount [u8; 8]
+    /// > bump [u8; 1]
+    /// > owner [u8; 32]
+    DepositToken { amount: u64, bump: u8, owner: [u8; 32] },
+    /// Accounts: [signer, vault, vault_token_account, destination_token_account, token_program]
+    ///
+    /// > amount [u8; 8]
+    /// > bump [u8; 1]
+    WithdrawToken { amount: u64, bump: u8 },
+    /// Accounts: [signer, vault]
+    ///
+    /// > limit [u8; 8]
+    /// > bump [u8; 1]
+    SetRateLimit { limit: u64, bump: u8 },
+    /// Accounts: [signer, vault]
+    ///
+    /// > delegate [u8; 32]
+    /// > allowance [u8; 8]
+    /// > expires_at [u8; 8]
+    /// > bump [u8; 1]
+    SetDelegate { delegate: Delegate, bump: u8 },
+    /// Accounts: [signer, vault]
+    ///
+    /// > delegate [u8; 32]
+    /// > bump [u8; 1]
+    RevokeDelegate { delegate: [u8; 32], bump: u8 },
+    /// Accounts: as `Withdraw`
+    ///
+    /// > lamports [u8; 8]
+    /// > bump [u8; 1]
+    ///
+    /// `Withdraw`, except that it fails instead of leaving a vault that holds data below the
+    /// rent-exempt minimum for it.
+    WithdrawKeepRentExempt { lamports: u64, bump: u8 },
+    /// Accounts: [owner, stream, system_program]
+    ///
+    /// > recipient [u8; 32]
+    /// > amount [u8; 8]
+    /// > start [u8; 8]
+    /// > cliff [u8; 8]
+    /// > end [u8; 8]
+    /// > bump [u8; 1]
+    ///
+    /// Schedules that could never pay anything out are malformed.
+    CreateStream { recipient: [u8; 32], stream: Stream, bump: u8 },
+    /// Accounts: [recipient, stream]
+    ///
+    /// > owner [u8; 32]
+    /// > bump [u8; 1]
+    WithdrawStream { owner: [u8; 32], bump: u8 },
+    /// Accounts: [owner, stream, recipient]
+    ///
+    /// > bump [u8; 1]
+    CancelStream { bump: u8 },
+}
+
+impl VaultInstruction {
+    pub const WITHDRAW: u8 = 0;
+    pub const DEPOSIT: u8 = 1;
+    pub const CLOSE: u8 = 2;
+    pub const SET_LOCK: u8 = 3;
+    pub const CREATE_MULTISIG: u8 = 4;
+    pub const ROTATE_MEMBERS: u8 = 5;
+    pub const WITHDRAW_MULTISIG: u8 = 6;
+    pub const DEPOSIT_TOKEN: u8 = 7;
+    pub const WITHDRAW_TOKEN: u8 = 8;
+    pub const SET_RATE_LIMIT: u8 = 9;
+    pub const SET_DELEGATE: u8 = 10;
+    pub const REVOKE_DELEGATE: u8 = 11;
+    pub const WITHDRAW_KEEP_RENT_EXEMPT: u8 = 12;
+    pub const CREATE_STREAM: u8 = 13;
+    pub const WITHDRAW_STREAM: u8 = 14;
+    pub const CANCEL_STREAM: u8 = 15;
+
+    /// The most accounts an instruction with `discriminator` takes, `None` for one that
+    /// doesn't exist. Multisig members count up to `MultisigConfig::MAX_MEMBERS`.
+    pub fn max_accounts(discriminator: u8) -> Option<usize> {
+        match discriminator {
+            Self::CLOSE
+            | Self::SET_LOCK
+            | Self::SET_RATE_LIMIT
+            | Self::SET_DELEGATE
+            | Self::REVOKE_DELEGATE
+            | Self::WITHDRAW_STREAM => Some(2),
+            Self::WITHDRAW
+            | Self::DEPOSIT
+            | Self::WITHDRAW_KEEP_RENT_EXEMPT
+            | Self::CREATE_STREAM
+            | Self::CANCEL_STREAM => Some(3),
+            Self::CREATE_MULTISIG => Some(1),
+            Self::ROTATE_MEMBERS => Some(1 + MultisigConfig::MAX_MEMBERS),
+            Self::WITHDRAW_MULTISIG => Some(4 + MultisigConfig::MAX_MEMBERS),
+            Self::DEPOSIT_TOKEN => Some(4),
+            Self::WITHDRAW_TOKEN => Some(5),
+            _ => None,
+        }
+    }
+
+    /// Encodes the instruction, the inverse of `try_from`. Only clients and tests build
+    /// instructions, the backends just decode them.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match *self {
+            Self::Withdraw { lamports, bump } => [&[Self::WITHDRAW][..], &lamports.to_le_bytes(), &[bump]].concat(),
+            Self::Deposit { lamports, bump, owner } => {
+                [&[Self::DEPOSIT][..], &lamports.to_le_bytes(), &[bump], &owner].concat()
+            }
+            Self::Close { bump } => vec![Self::CLOSE, bump],
+            Self::SetLock { unlock_timestamp, unlock_slot, bump } => [
+                &[Self::SET_LOCK][..],
+                &unlock_timestamp.to_le_bytes(),
+                &unlock_slot.to_le_bytes(),
+                &[bump],
+            ]
+            .concat(),
+            Self::CreateMultisig { config } => write_multisig(Self::CREATE_MULTISIG, &config),
+            Self::RotateMembers { config } => write_multisig(Self::ROTATE_MEMBERS, &config),
+            Self::WithdrawMultisig { lamports, bump } => {
+                [&[Self::WITHDRAW_MULTISIG][..], &lamports.to_le_bytes(), &[bump]].concat()
+            }
+            Self::DepositToken { amount, bump, owner } => {
+                [&[Self::DEPOSIT_TOKEN][..], &amount.to_le_bytes(), &[bump], &owner].concat()
+            }
+            Self::WithdrawToken { amount, bump } => {
+                [&[Self::WITHDRAW_TOKEN][..], &amount.to_le_bytes(), &[bump]].concat()
+            }
+            Self::SetRateLimit { limit, bump } => [&[Self::SET_RATE_LIMIT][..], &limit.to_le_bytes(), &[bump]].concat(),
+            Self::SetDelegate { delegate, bump } => [
+                &[Self::SET_DELEGATE][..],
+                &delegate.key,
+                &delegate.allowance.to_le_bytes(),
+                &delegate.expires_at.to_le_bytes(),
+                &[bump],
+            ]
+            .concat(),
+            Self::RevokeDelegate { delegate, bump } => [&[Self::REVOKE_DELEGATE][..], &delegate, &[bump]].concat(),
+            Self::WithdrawKeepRentExempt { lamports, bump } => {
+                [&[Self::WITHDRAW_KEEP_RENT_EXEMPT][..], &lamports.to_le_bytes(), &[bump]].concat()
+            }
+            Self::CreateStream { recipient, stream, bump } => [
+                &[Self::CREATE_STREAM][..],
+                &recipient,
+                &stream.amount.to_le_bytes(),
+                &stream.start.to_le_bytes(),
+                &stream.cliff.to_le_bytes(),
+                &stream.end.to_le_bytes(),
+                &[bump],
+            ]
+            .concat(),
+            Self::WithdrawStream { owner, bump } => [&[Self::WITHDRAW_STREAM][..], &owner, &[bump]].concat(),
+            Self::CancelStream { bump } => vec![Self::CANCEL_STREAM, bump],
+        }
+    }
+}
+
+/// Instruction data that doesn't match any `VaultInstruction`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidInstructionData;
+
+impl TryFrom<&[u8]> for VaultInstruction {
+    type Error = InvalidInstructionData;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let (discriminator, data) = data.split_first().ok_or(InvalidInstructionData)?;
+
+        match (*discriminator, data.len()) {
+            (Self::WITHDRAW, 9) => Ok(Self::Withdraw {
+                lamports: read_u64(data),
+                bump: data[8],
+            }),
+            (Self::DEPOSIT, 41) => Ok(Self::Deposit {
+                lamports: read_u64(data),
+                bump: data[8],
+                owner: read_pubkey(&data[9..]),
+            }),
+            (Self::CLOSE, 1) => Ok(Self::Close { bump: data[0] }),
+            (Self::SET_LOCK, 17) => Ok(Self::SetLock {
+                unlock_timestamp: read_u64(data) as i64,
+                unlock_slot: read_u64(&data[8..]),
+                bump: data[16],
+            }),
+            (Self::CREATE_MULTISIG, _) => Ok(Self::CreateMultisig { config: read_multisig(data)? }),
+            (Self::ROTATE_MEMBERS, _) => Ok(Self::RotateMembers { config: read_multisig(data)? }),
+            (Self::WITHDRAW_MULTISIG, 9) => Ok(Self::WithdrawMultisig {
+                lamports: read_u64(data),
+                bump: data[8],
+            }),
+            (Self::DEPOSIT_TOKEN, 41) => Ok(Self::DepositToken {
+                amount: read_u64(data),
+                bump: data[8],
+                owner: read_pubkey(&data[9..]),
+            }),
+            (Self::WITHDRAW_TOKEN, 9) => Ok(Self::WithdrawToken {
+                amount: read_u64(data),
+                bump: data[8],
+            }),
+            (Self::SET_RATE_LIMIT, 9) => Ok(Self::SetRateLimit {
+                limit: read_u64(data),
+                bump: data[8],
+            }),
+            (Self::SET_DELEGATE, 49) => Ok(Self::SetDelegate {
+                delegate: Delegate {
+                    key: read_pubkey(data),
+                    allowance: read_u64(&data[32..]),
+                    expires_at: read_u64(&data[40..]) as i64,
+                },
+                bump: data[48],
+            }),
+            (Self::REVOKE_DELEGATE, 33) => Ok(Self::RevokeDelegate {
+                delegate: read_pubkey(data),
+                bump: data[32],
+            }),
+            (Self::WITHDRAW_KEEP_RENT_EXEMPT, 9) => Ok(Self::WithdrawKeepRentExempt {
+                lamports: read_u64(data),
+                bump: data[8],
+            }),
+            (Self::CREATE_STREAM, 65) => Ok(Self::CreateStream {
+                recipient: read_pubkey(data),
+                stream: Stream::new(
+                    read_u64(&data[32..]),
+                    read_u64(&data[40..]) as i64,
+                    read_u64(&data[48..]) as i64,
+                    read_u64(&data[56..]) as i64,
+                )
+                .map_err(|_| InvalidInstructionData)?,
+                bump: data[64],
+            }),
+            (Self::WITHDRAW_STREAM, 33) => Ok(Self::WithdrawStream {
+                owner: read_pubkey(data),
+                bump: data[32],
+            }),
+            (Self::CANCEL_STREAM, 1) => Ok(Self::CancelStream { bump: data[0] }),
+            _ => Err(InvalidInstructionData),
+        }
+    }
+}
+
+// Both readers are only called once the length has been checked above.
+#[inline(always)]
+fn read_u64(data: &[u8]) -> u64 {
+    unsafe { (data.as_ptr() as *const u64).read_unaligned() }
+}
+
+#[inline(always)]
+fn read_pubkey(data: &[u8]) -> [u8; 32] {
+    unsafe { *(data.as_ptr() as *const [u8; 32]) }
+}
+
+/// A member set is variable length, so this one checks the length itself. Member sets that
+/// could never approve anything are malformed too.
+fn read_multisig(data: &[u8]) -> Result<MultisigConfig, InvalidInstructionData> {
+    let [threshold, member_count, members @ ..] = data else {
+        return Err(InvalidInstructionData);
+    };
+
+    if members.len() != *member_count as usize * 32 {
+        return Err(InvalidInstructionData);
+    }
+
+    let members: &[[u8; 32]] =
+        unsafe { core::slice::from_raw_parts(members.as_ptr() as *const [u8; 32], *member_count as usize) };
+
+    MultisigConfig::new(*threshold, members).map_err(|_| InvalidInstructionData)
+}
+
+fn write_multisig(discriminator: u8, config: &MultisigConfig) -> Vec<u8> {
+    let mut data = vec![discriminator, config.threshold(), config.members().len() as u8];
+    data.extend(config.members().iter().flatten());
+    data
+}
@@ -0,0 +1,79 @@
+//! # Lazy
+//!
+//! The `optimized` backend behind pinocchio's lazy `InstructionContext` instead of
+//! `entrypoint!`. Every instruction is handled by the same `optimized` code, on the same
+//! `AccountInfo`s, but only as many accounts as the instruction can take are ever read.
+//!
+//! -- Reading order --
+//! The runtime serializes the instruction data after the last account, so the entrypoint
+//! first steps over the accounts to find it with `input::instruction_data`, which only hops
+//! from one length to the next. Its discriminator says how many accounts the instruction
+//! takes at most, see `VaultInstruction::max_accounts`. That many are read into
+//! `AccountInfo`s, and anything still `remaining()` after them fails the instruction
+//! without being read. Fewer than that fail in the `optimized` handler, like they do behind
+//! `entrypoint!`.
+
+use core::mem::MaybeUninit;
+
+use pinocchio::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    lazy_entrypoint::{InstructionContext, MaybeAccount},
+    program_error::ProgramError,
+    SUCCESS,
+};
+
+use crate::{
+    ID,
+    error::{require, VaultError},
+    input::instruction_data,
+    instruction::VaultInstruction,
+    optimized::process_instruction,
+    state::MultisigConfig,
+};
+
+/// The most accounts any instruction takes, a multisig withdraw signed by every member.
+const MAX_ACCOUNTS: usize = 4 + MultisigConfig::MAX_MEMBERS;
+
+/// # Safety
+/// `input` has to be the buffer the runtime passed to the entrypoint, untouched.
+#[no_mangle]
+pub unsafe extern "C" fn entrypoint(input: *mut u8) -> u64 {
+    let data = instruction_data(input);
+
+    match process(InstructionContext::new(input), data) {
+        Ok(()) => SUCCESS,
+        Err(error) => error.into(),
+    }
+}
+
+fn process(mut context: InstructionContext, data: &[u8]) -> ProgramResult {
+    let max = data
+        .first()
+        .and_then(|discriminator| VaultInstruction::max_accounts(*discriminator))
+        .ok_or(ProgramError::InvalidInstructionData)?;
+
+    let count = (context.remaining() as usize).min(max);
+
+    const UNINIT: MaybeUninit<AccountInfo> = MaybeUninit::uninit();
+    let mut accounts = [UNINIT; MAX_ACCOUNTS];
+
+    for index in 0..count {
+        let account = match context.next_account()? {
+            MaybeAccount::Account(account) => account,
+            // The runtime only ever points a duplicate back at an account before it
+            MaybeAccount::Duplicated(original) => unsafe { accounts[original as usize].assume_init_ref().clone() },
+        };
+
+        accounts[index].write(account);
+    }
+
+    // Whatever is left is more than the instruction takes
+    require!(context.remaining() == 0, VaultError::InvalidAccountCount);
+
+    // The first `count` were all written above
+    let accounts = unsafe { core::slice::from_raw_parts(accounts.as_ptr() as *const AccountInfo, count) };
+
+    // None of the handlers look at the program ID, the runtime already picked us by it
+    process_instruction(&ID, accounts, data)
+}
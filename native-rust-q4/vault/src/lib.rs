@@ -1,19 +1,28 @@
 #![cfg_attr(target_os = "solana", feature(asm_experimental_arch))]
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests;
 
+/// The program ID, set at build time by `VAULT_PROGRAM_ID` or `VAULT_PROGRAM_KEYPAIR` (see
+/// `../program_id.rs`). Every backend and the tests use this one.
+pub const ID: [u8; 32] = include!(concat!(env!("OUT_DIR"), "/program_id.rs"));
+
+pub mod error;
+pub mod instruction;
+pub mod state;
+
+#[cfg(any(test, feature = "based", feature = "lazy"))]
+pub mod input;
+
 #[cfg(all(target_os = "solana", feature = "based"))]
 mod based;
-#[cfg(all(target_os = "solana", feature = "based"))]
-use based::*;
 
 #[cfg(feature = "optimized")]
 mod optimized;
-#[cfg(feature = "optimized")]
-use optimized::*;
+
+#[cfg(feature = "lazy")]
+mod lazy;
 
 #[cfg(all(target_os = "solana", feature = "native"))]
 mod native;
-#[cfg(all(target_os = "solana", feature = "native"))]
-use native::*;
\ No newline at end of file
@@ -1,34 +1,522 @@
 use solana_program::entrypoint;
 use solana_program::{
-    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
-    pubkey::Pubkey, hash::hashv
+    account_info::AccountInfo, entrypoint::ProgramResult, program::{invoke, invoke_signed},
+    program_error::ProgramError, pubkey::Pubkey, hash::hashv, system_instruction, system_program,
+    clock::Clock, rent::Rent, sysvar::Sysvar, program_pack::Pack
 };
 
-const ID: Pubkey = Pubkey::new_from_array([
-    0x7b, 0x07, 0x5a, 0x4f, 0xca, 0x15, 0x61, 0x6e, 
-    0xbe, 0x53, 0xc1, 0xa8, 0x43, 0x6f, 0x42, 0x89, 
-    0x2b, 0x02, 0x1a, 0xb6, 0x62, 0x5a, 0x2a, 0x02, 
-    0x2a, 0x68, 0x9a, 0xef, 0xbd, 0xed, 0x26, 0xef
-]);
+use crate::{
+    error::{require, VaultError},
+    instruction::{InvalidInstructionData, VaultInstruction},
+    state::{Delegate, InvalidVaultData, MultisigConfig, RateLimitExceeded, VaultState},
+};
+
+const ID: Pubkey = Pubkey::new_from_array(crate::ID);
 
 const PDA_MARKER: &[u8; 21] = b"ProgramDerivedAddress";
 
 entrypoint!(process_instruction);
 
+impl From<InvalidInstructionData> for ProgramError {
+    fn from(_: InvalidInstructionData) -> Self {
+        ProgramError::InvalidInstructionData
+    }
+}
+
+impl From<VaultError> for ProgramError {
+    fn from(error: VaultError) -> Self {
+        ProgramError::Custom(error.code())
+    }
+}
+
+impl From<InvalidVaultData> for ProgramError {
+    fn from(error: InvalidVaultData) -> Self {
+        VaultError::from(error).into()
+    }
+}
+
+impl From<RateLimitExceeded> for ProgramError {
+    fn from(error: RateLimitExceeded) -> Self {
+        VaultError::from(error).into()
+    }
+}
+
+pub fn process_instruction(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    match VaultInstruction::try_from(data)? {
+        VaultInstruction::Withdraw { lamports, bump } => withdraw(accounts, lamports, bump, false),
+        VaultInstruction::WithdrawKeepRentExempt { lamports, bump } => withdraw(accounts, lamports, bump, true),
+        VaultInstruction::Deposit { lamports, bump, owner } => deposit(accounts, lamports, bump, &owner),
+        VaultInstruction::Close { bump } => close(accounts, bump),
+        VaultInstruction::SetLock { unlock_timestamp, unlock_slot, bump } => {
+            set_lock(accounts, unlock_timestamp, unlock_slot, bump)
+        }
+        VaultInstruction::CreateMultisig { config } => create_multisig(accounts, &config),
+        VaultInstruction::RotateMembers { config } => rotate_members(accounts, &config),
+        VaultInstruction::WithdrawMultisig { lamports, bump } => withdraw_multisig(accounts, lamports, bump),
+        VaultInstruction::DepositToken { amount, bump, owner } => deposit_token(accounts, amount, bump, &owner),
+        VaultInstruction::WithdrawToken { amount, bump } => withdraw_token(accounts, amount, bump),
+        VaultInstruction::SetRateLimit { limit, bump } => set_rate_limit(accounts, limit, bump),
+        VaultInstruction::SetDelegate { delegate, bump } => set_delegate(accounts, &delegate, bump),
+        VaultInstruction::RevokeDelegate { delegate, bump } => revoke_delegate(accounts, &delegate, bump),
+        // Streams only exist in the optimized backend
+        VaultInstruction::CreateStream { .. }
+        | VaultInstruction::WithdrawStream { .. }
+        | VaultInstruction::CancelStream { .. } => Err(VaultError::UnsupportedInstruction.into()),
+    }
+}
+
+/// Fails while `vault` is time-locked. Vaults without a lock never read the Clock sysvar.
+fn check_unlocked(vault: &AccountInfo) -> ProgramResult {
+    let state = VaultState::load(&vault.try_borrow_data()?)?;
+
+    if state != VaultState::default() {
+        let clock = Clock::get()?;
+        require!(state.is_unlocked(clock.unix_timestamp, clock.slot), VaultError::VaultLocked);
+    }
+    Ok(())
+}
+
+/// Fails while `vault` is time-locked or if `lamports` don't fit in its rate limit, and
+/// counts them against the limit otherwise. Withdrawals by a `delegate` also have to fit in
+/// its allowance. Owners of vaults without data never read the Clock sysvar.
+fn check_withdrawal(vault: &AccountInfo, lamports: u64, delegate: Option<&Pubkey>) -> ProgramResult {
+    let mut data = vault.try_borrow_mut_data()?;
+    if data.is_empty() && delegate.is_none() {
+        return Ok(());
+    }
+
+    let mut state = VaultState::load(&data)?;
+    let clock = Clock::get()?;
+
+    require!(state.is_unlocked(clock.unix_timestamp, clock.slot), VaultError::VaultLocked);
+    state.record_withdrawal(lamports, clock.epoch, clock.slot)?;
+    state.store(&mut data);
+
+    if let Some(key) = delegate {
+        let offset = VaultState::find_delegate(&data, key.as_ref()).ok_or(VaultError::NotADelegate)?;
+
+        let mut delegate = Delegate::load(&data[offset..])?;
+        require!(delegate.spend(lamports, clock.unix_timestamp), VaultError::AllowanceExceeded);
+        delegate.store(&mut data[offset..]);
+    }
+
+    Ok(())
+}
+
+/// Moves `lamports` out of `vault` into `destination`, which can be the vault itself. With
+/// `keep_rent_exempt`, a vault holding data can't go below its rent-exempt minimum.
+fn move_lamports(vault: &AccountInfo, destination: &AccountInfo, lamports: u64, keep_rent_exempt: bool) -> ProgramResult {
+    let balance = vault.lamports().checked_sub(lamports).ok_or(VaultError::InsufficientFunds)?;
+
+    if keep_rent_exempt && vault.data_len() != 0 {
+        require!(balance >= Rent::get()?.minimum_balance(vault.data_len()), VaultError::BelowRentExempt);
+    }
+
+    **vault.try_borrow_mut_lamports()? = balance;
+
+    let mut destination = destination.try_borrow_mut_lamports()?;
+    **destination = destination.checked_add(lamports).ok_or(VaultError::ArithmeticOverflow)?;
+
+    Ok(())
+}
+
+/// With `canonical-bump`, fails unless `bump` is the canonical bump of `seeds`, so an owner
+/// has exactly one vault. Without it any bump that hashes to the vault goes, and this is free.
+#[inline(always)]
+fn check_canonical_bump(seeds: &[&[u8]], bump: u8) -> ProgramResult {
+    #[cfg(feature = "canonical-bump")]
+    {
+        let (_, canonical) = Pubkey::try_find_program_address(seeds, &ID).ok_or(VaultError::InvalidPda)?;
+        require!(bump == canonical, VaultError::NonCanonicalBump);
+    }
+
+    #[cfg(not(feature = "canonical-bump"))]
+    let _ = (seeds, bump);
+
+    Ok(())
+}
+
 /// # Withdraw
 ///
-/// Handles withdrawing funds from a PDA that has previously had lamports deposited to it.
-pub fn process_instruction(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+/// Handles withdrawing funds from a PDA that has previously had lamports deposited to it,
+/// to the signer or to a destination passed as a third account.
+///
+/// With a destination, a signer that doesn't seed the vault is taken for one of its delegates
+/// and handed to `withdraw_delegated`.
+pub fn withdraw(accounts: &[AccountInfo], lamports: u64, bump: u8, keep_rent_exempt: bool) -> ProgramResult {
+    let ([signer, vault] | [signer, vault, _]) = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(signer.is_signer, VaultError::MissingSignature);
+
+    let pda = hashv(&[
+        signer.key.as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    let seeds_vault = pda.to_bytes() == vault.key.to_bytes();
+
+    let destination = match accounts {
+        [_, _, destination] if seeds_vault => destination,
+        [_, _, _] => return withdraw_delegated(accounts, lamports, keep_rent_exempt),
+        _ => signer,
+    };
+
+    require!(seeds_vault, VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key.as_ref()], bump)?;
+
+    check_withdrawal(vault, lamports, None)?;
+
+    move_lamports(vault, destination, lamports, keep_rent_exempt)
+}
+
+/// # Withdraw Delegated
+///
+/// Handles a delegate withdrawing from a vault to any destination, out of the allowance its
+/// owner gave it. Only the owner can add delegates to a vault, so finding the signer among
+/// them is enough and the owner's key isn't needed to check the PDA.
+pub fn withdraw_delegated(accounts: &[AccountInfo], lamports: u64, keep_rent_exempt: bool) -> ProgramResult {
+    let [delegate, vault, destination] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(delegate.is_signer, VaultError::MissingSignature);
+
+    // Multisig configs are ours too, but never pass for vault data
+    require!(vault.owner == &ID, VaultError::InvalidAccountOwner);
+
+    check_withdrawal(vault, lamports, Some(delegate.key))?;
+
+    move_lamports(vault, destination, lamports, keep_rent_exempt)
+}
+
+/// # Deposit
+///
+/// Handles topping up the vault of `owner` with lamports from `payer`. Anyone can deposit
+/// into anybody's vault, so the owner travels as data instead of as an extra account.
+///
+/// The first deposit into a vault also assigns it to this program, signing for the PDA with
+/// the `[owner, bump]` seeds. Without that, `withdraw` couldn't debit the vault directly.
+pub fn deposit(accounts: &[AccountInfo], lamports: u64, bump: u8, owner: &[u8; 32]) -> ProgramResult {
+    let [payer, vault, system_program] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(payer.is_signer, VaultError::MissingSignature);
+
+    let pda = hashv(&[
+        owner.as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(pda.to_bytes() == vault.key.to_bytes(), VaultError::InvalidPda);
+    check_canonical_bump(&[owner.as_ref()], bump)?;
+
+    // Locked or not, anything else than a vault's own state is rejected
+    VaultState::load(&vault.try_borrow_data()?)?;
+
+    invoke(
+        &system_instruction::transfer(payer.key, vault.key, lamports),
+        &[payer.clone(), vault.clone(), system_program.clone()],
+    )?;
+
+    if vault.owner != &ID {
+        invoke_signed(
+            &system_instruction::assign(vault.key, &ID),
+            &[vault.clone(), system_program.clone()],
+            &[&[owner.as_ref(), &[bump]]],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// # Close
+///
+/// Handles shutting a vault down by sending every lamport it holds back to its owner. With
+/// no lamports left the runtime garbage collects the PDA at the end of the transaction.
+///
+/// Until then the PDA is a plain system account: its data is zeroed and dropped and it goes
+/// back to the system program, so a later instruction can't pick up the old lock, limit or
+/// delegates. Depositing into it again starts a new vault.
+pub fn close(accounts: &[AccountInfo], bump: u8) -> ProgramResult {
+    let [signer, vault] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(signer.is_signer, VaultError::MissingSignature);
+
+    let pda = hashv(&[
+        signer.key.as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(pda.to_bytes() == vault.key.to_bytes(), VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key.as_ref()], bump)?;
+
+    check_withdrawal(vault, vault.lamports(), None)?;
+
+    move_lamports(vault, signer, vault.lamports(), false)?;
+
+    vault.try_borrow_mut_data()?.fill(0);
+    vault.realloc(0, false)?;
+    vault.assign(&system_program::ID);
+
+    Ok(())
+}
+
+/// # Set Lock
+///
+/// Handles time-locking a vault until both `unlock_timestamp` and `unlock_slot` have been
+/// reached. The first lock grows the vault to `VaultState::LOCK_LEN` bytes, so it has to
+/// hold enough lamports to stay rent-exempt with them. Locks can only be extended, never
+/// lifted.
+pub fn set_lock(accounts: &[AccountInfo], unlock_timestamp: i64, unlock_slot: u64, bump: u8) -> ProgramResult {
+    let [signer, vault] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(signer.is_signer, VaultError::MissingSignature);
+
+    let pda = hashv(&[
+        signer.key.as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(pda.to_bytes() == vault.key.to_bytes(), VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key.as_ref()], bump)?;
+
+    let mut state = VaultState::load(&vault.try_borrow_data()?)?;
+    require!(state.extend_lock(unlock_timestamp, unlock_slot), VaultError::LockNotExtended);
+
+    vault.realloc(vault.data_len().max(VaultState::LOCK_LEN), false)?;
+    state.store(&mut vault.try_borrow_mut_data()?);
+
+    Ok(())
+}
+
+/// Fails unless `config` belongs to us and enough of its members signed. Members can come in
+/// any order after the fixed accounts, anything that isn't a signing member is ignored.
+fn check_approved(config: &AccountInfo, members: &[AccountInfo]) -> Result<MultisigConfig, ProgramError> {
+    require!(config.owner == &ID, VaultError::InvalidAccountOwner);
+
+    let multisig = MultisigConfig::load(&config.try_borrow_data()?)?;
+    require!(
+        multisig.is_approved_by(members.iter().filter(|member| member.is_signer).map(|member| member.key)),
+        VaultError::NotApproved
+    );
+
+    Ok(multisig)
+}
+
+/// # Create Multisig
+///
+/// Handles writing the member set into a fresh config account. The client creates it with
+/// `MultisigConfig::LEN` bytes and this program as owner in the same transaction, and it has
+/// to sign here so nobody else can claim it in between.
+pub fn create_multisig(accounts: &[AccountInfo], config: &MultisigConfig) -> ProgramResult {
+    let [config_account] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(config_account.is_signer, VaultError::MissingSignature);
+    require!(config_account.owner == &ID, VaultError::InvalidAccountOwner);
+    require!(
+        MultisigConfig::is_uncreated(&config_account.try_borrow_data()?),
+        VaultError::MultisigAlreadyCreated
+    );
+
+    config.store(&mut config_account.try_borrow_mut_data()?);
+
+    Ok(())
+}
+
+/// # Rotate Members
+///
+/// Handles replacing the member set, approved by the threshold of the current one. The
+/// vault is seeded by the config account, so it stays where it is.
+pub fn rotate_members(accounts: &[AccountInfo], config: &MultisigConfig) -> ProgramResult {
+    let [config_account, members @ ..] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    check_approved(config_account, members)?;
+
+    config.store(&mut config_account.try_borrow_mut_data()?);
+
+    Ok(())
+}
+
+/// # Withdraw Multisig
+///
+/// Handles withdrawing from the vault of a member set to any destination. Multisig vaults
+/// are never assigned to this program, anyone can fund them with a plain transfer, so the
+/// lamports leave through the system program with the vault signing.
+pub fn withdraw_multisig(accounts: &[AccountInfo], lamports: u64, bump: u8) -> ProgramResult {
+    let [config, vault, destination, system_program, members @ ..] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    check_approved(config, members)?;
+
+    let pda = hashv(&[
+        MultisigConfig::SEED,
+        config.key.as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(pda.to_bytes() == vault.key.to_bytes(), VaultError::InvalidPda);
+    check_canonical_bump(&[MultisigConfig::SEED, config.key.as_ref()], bump)?;
+
+    invoke_signed(
+        &system_instruction::transfer(vault.key, destination.key, lamports),
+        &[vault.clone(), destination.clone(), system_program.clone()],
+        &[&[MultisigConfig::SEED, config.key.as_ref(), &[bump]]],
+    )
+}
+
+/// Fails unless `token_account` is an SPL token account held by `vault`.
+fn check_token_vault(token_account: &AccountInfo, vault: &[u8; 32]) -> ProgramResult {
+    require!(spl_token::check_id(token_account.owner), VaultError::InvalidTokenAccount);
+
+    let token_account = spl_token::state::Account::unpack(&token_account.try_borrow_data()?)
+        .map_err(|_| VaultError::InvalidTokenAccount)?;
+    require!(token_account.owner.as_ref() == vault, VaultError::InvalidTokenAccount);
+
+    Ok(())
+}
+
+/// # Deposit Token
+///
+/// Handles moving tokens from `payer` into the vault of `owner`. The vault holds tokens in
+/// any token account whose authority is its PDA (usually its associated token account), and
+/// the PDA itself doesn't have to exist for that.
+pub fn deposit_token(accounts: &[AccountInfo], amount: u64, bump: u8, owner: &[u8; 32]) -> ProgramResult {
+    let [payer, payer_token_account, vault_token_account, token_program] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(payer.is_signer, VaultError::MissingSignature);
+    require!(spl_token::check_id(token_program.key), VaultError::InvalidAccount);
+
+    let pda = hashv(&[
+        owner.as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    check_canonical_bump(&[owner.as_ref()], bump)?;
+    check_token_vault(vault_token_account, &pda.to_bytes())?;
+
+    invoke(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            payer_token_account.key,
+            vault_token_account.key,
+            payer.key,
+            &[],
+            amount,
+        )?,
+        &[payer_token_account.clone(), vault_token_account.clone(), payer.clone(), token_program.clone()],
+    )
+}
+
+/// # Withdraw Token
+///
+/// Handles withdrawing tokens from the vault to any token account, the vault PDA signing
+/// for its token account with the `[signer, bump]` seeds. Locks hold for tokens too.
+pub fn withdraw_token(accounts: &[AccountInfo], amount: u64, bump: u8) -> ProgramResult {
+    let [signer, vault, vault_token_account, destination, token_program] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(signer.is_signer, VaultError::MissingSignature);
+    require!(spl_token::check_id(token_program.key), VaultError::InvalidAccount);
+
+    let pda = hashv(&[
+        signer.key.as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(pda.to_bytes() == vault.key.to_bytes(), VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key.as_ref()], bump)?;
+
+    check_unlocked(vault)?;
+    check_token_vault(vault_token_account, &vault.key.to_bytes())?;
+
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            vault_token_account.key,
+            destination.key,
+            vault.key,
+            &[],
+            amount,
+        )?,
+        &[vault_token_account.clone(), destination.clone(), vault.clone(), token_program.clone()],
+        &[&[signer.key.as_ref(), &[bump]]],
+    )
+}
+
+/// # Set Rate Limit
+///
+/// Handles capping how many lamports can leave the vault per epoch, with 0 lifting the cap.
+/// A lower limit applies right away, a higher one after `RATE_LIMIT_RAISE_DELAY` slots. The
+/// first limit grows the vault to `VaultState::LEN` bytes, which its lamports have to keep
+/// rent-exempt.
+pub fn set_rate_limit(accounts: &[AccountInfo], limit: u64, bump: u8) -> ProgramResult {
     let [signer, vault] = accounts else {
-        return Err(ProgramError::NotEnoughAccountKeys);
+        return Err(VaultError::InvalidAccountCount.into());
     };
 
-    assert!(signer.is_signer);
+    require!(signer.is_signer, VaultError::MissingSignature);
 
-    let lamports: u64 = u64::from_le_bytes([
-        data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
+    let pda = hashv(&[
+        signer.key.as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
     ]);
-    let bump = data[8];
+
+    require!(pda.to_bytes() == vault.key.to_bytes(), VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key.as_ref()], bump)?;
+
+    let mut state = VaultState::load(&vault.try_borrow_data()?)?;
+    state.set_limit(limit, Clock::get()?.slot);
+
+    vault.realloc(vault.data_len().max(VaultState::LEN), false)?;
+    state.store(&mut vault.try_borrow_mut_data()?);
+
+    Ok(())
+}
+
+/// # Set Delegate
+///
+/// Handles letting `delegate` withdraw up to its allowance from the vault until it expires,
+/// or replacing the allowance and expiry of a delegate the vault already has. Every new
+/// delegate grows the vault by `Delegate::LEN` bytes past `VaultState::LEN`, which its
+/// lamports have to keep rent-exempt.
+pub fn set_delegate(accounts: &[AccountInfo], delegate: &Delegate, bump: u8) -> ProgramResult {
+    let [signer, vault] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(signer.is_signer, VaultError::MissingSignature);
+
     let pda = hashv(&[
         signer.key.as_ref(),
         &[bump],
@@ -36,10 +524,62 @@ pub fn process_instruction(_program_id: &Pubkey, accounts: &[AccountInfo], data:
         PDA_MARKER,
     ]);
 
-    assert_eq!(pda.to_bytes(), vault.key.as_ref());
+    require!(pda.to_bytes() == vault.key.to_bytes(), VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key.as_ref()], bump)?;
 
-    **vault.try_borrow_mut_lamports()? -= lamports;
-    **signer.try_borrow_mut_lamports()? += lamports;
+    let (state, found) = {
+        let data = vault.try_borrow_data()?;
+        (VaultState::load(&data)?, VaultState::find_delegate(&data, &delegate.key))
+    };
+
+    let offset = match found {
+        Some(offset) => offset,
+        None => {
+            let len = vault.data_len().max(VaultState::LEN);
+            require!(len < VaultState::LEN + VaultState::MAX_DELEGATES * Delegate::LEN, VaultError::TooManyDelegates);
+
+            vault.realloc(len + Delegate::LEN, false)?;
+            len
+        }
+    };
+
+    let mut data = vault.try_borrow_mut_data()?;
+    state.store(&mut data);
+    delegate.store(&mut data[offset..]);
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// # Revoke Delegate
+///
+/// Handles taking a delegate off the vault, whatever is left of its allowance. The last
+/// delegate moves into its place and the vault shrinks back by `Delegate::LEN` bytes.
+pub fn revoke_delegate(accounts: &[AccountInfo], delegate: &[u8; 32], bump: u8) -> ProgramResult {
+    let [signer, vault] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(signer.is_signer, VaultError::MissingSignature);
+
+    let pda = hashv(&[
+        signer.key.as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(pda.to_bytes() == vault.key.to_bytes(), VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key.as_ref()], bump)?;
+
+    let last = {
+        let mut data = vault.try_borrow_mut_data()?;
+        VaultState::load(&data)?;
+
+        let offset = VaultState::find_delegate(&data, delegate).ok_or(VaultError::NotADelegate)?;
+        let last = data.len() - Delegate::LEN;
+        data.copy_within(last.., offset);
+        last
+    };
+
+    vault.realloc(last, false)
+}
@@ -1,29 +1,297 @@
 use five8_const::decode_32_const;
-use pinocchio::{entrypoint, msg};
+#[cfg(not(feature = "lazy"))]
+use pinocchio::entrypoint;
+use pinocchio_token::instructions::Transfer;
 use pinocchio::{
-    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
-    pubkey::Pubkey
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, Seed, Signer},
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvars::{clock::Clock, rent::Rent, Sysvar},
 };
 use solana_nostd_sha256::hashv;
 
-const ID: [u8; 32] = decode_32_const("9HFegTZnvebYjf9kSa6k3WBm93hRfogWB5B1goUrq1oL");
+use crate::{
+    ID,
+    error::{require, VaultError},
+    instruction::{InvalidInstructionData, VaultInstruction},
+    state::{Delegate, InvalidVaultData, MultisigConfig, RateLimitExceeded, Stream, VaultState},
+};
+
+const SYSTEM_PROGRAM_ID: Pubkey = [0; 32];
+
+const TOKEN_PROGRAM_ID: Pubkey = decode_32_const("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
 
 const PDA_MARKER: &[u8; 21] = b"ProgramDerivedAddress";
 
-entrypoint!(withdraw);
+// `lazy` brings its own entrypoint and hands every instruction to `process_instruction`
+#[cfg(not(feature = "lazy"))]
+entrypoint!(process_instruction);
+
+impl From<InvalidInstructionData> for ProgramError {
+    fn from(_: InvalidInstructionData) -> Self {
+        ProgramError::InvalidInstructionData
+    }
+}
+
+impl From<VaultError> for ProgramError {
+    fn from(error: VaultError) -> Self {
+        ProgramError::Custom(error.code())
+    }
+}
+
+impl From<InvalidVaultData> for ProgramError {
+    fn from(error: InvalidVaultData) -> Self {
+        VaultError::from(error).into()
+    }
+}
+
+impl From<RateLimitExceeded> for ProgramError {
+    fn from(error: RateLimitExceeded) -> Self {
+        VaultError::from(error).into()
+    }
+}
+
+pub fn process_instruction(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    match VaultInstruction::try_from(data)? {
+        VaultInstruction::Withdraw { lamports, bump } => withdraw(accounts, lamports, bump, false),
+        VaultInstruction::WithdrawKeepRentExempt { lamports, bump } => withdraw(accounts, lamports, bump, true),
+        VaultInstruction::Deposit { lamports, bump, owner } => deposit(accounts, lamports, bump, &owner),
+        VaultInstruction::Close { bump } => close(accounts, bump),
+        VaultInstruction::SetLock { unlock_timestamp, unlock_slot, bump } => {
+            set_lock(accounts, unlock_timestamp, unlock_slot, bump)
+        }
+        VaultInstruction::CreateMultisig { config } => create_multisig(accounts, &config),
+        VaultInstruction::RotateMembers { config } => rotate_members(accounts, &config),
+        VaultInstruction::WithdrawMultisig { lamports, bump } => withdraw_multisig(accounts, lamports, bump),
+        VaultInstruction::DepositToken { amount, bump, owner } => deposit_token(accounts, amount, bump, &owner),
+        VaultInstruction::WithdrawToken { amount, bump } => withdraw_token(accounts, amount, bump),
+        VaultInstruction::SetRateLimit { limit, bump } => set_rate_limit(accounts, limit, bump),
+        VaultInstruction::SetDelegate { delegate, bump } => set_delegate(accounts, &delegate, bump),
+        VaultInstruction::RevokeDelegate { delegate, bump } => revoke_delegate(accounts, &delegate, bump),
+        VaultInstruction::CreateStream { recipient, stream, bump } => {
+            create_stream(accounts, &recipient, &stream, bump)
+        }
+        VaultInstruction::WithdrawStream { owner, bump } => withdraw_stream(accounts, &owner, bump),
+        VaultInstruction::CancelStream { bump } => cancel_stream(accounts, bump),
+    }
+}
+
+/// Fails while `vault` is time-locked. Vaults without a lock never read the Clock sysvar.
+fn check_unlocked(vault: &AccountInfo) -> ProgramResult {
+    let state = VaultState::load(unsafe { vault.borrow_data_unchecked() })?;
+
+    if state != VaultState::default() {
+        let clock = Clock::get()?;
+        require!(state.is_unlocked(clock.unix_timestamp, clock.slot), VaultError::VaultLocked);
+    }
+    Ok(())
+}
+
+/// Fails while `vault` is time-locked or if `lamports` don't fit in its rate limit, and
+/// counts them against the limit otherwise. Withdrawals by a `delegate` also have to fit in
+/// its allowance. Owners of vaults without data never read the Clock sysvar.
+fn check_withdrawal(vault: &AccountInfo, lamports: u64, delegate: Option<&Pubkey>) -> ProgramResult {
+    let data = unsafe { vault.borrow_mut_data_unchecked() };
+    if data.is_empty() && delegate.is_none() {
+        return Ok(());
+    }
+
+    let mut state = VaultState::load(data)?;
+    let clock = Clock::get()?;
+
+    require!(state.is_unlocked(clock.unix_timestamp, clock.slot), VaultError::VaultLocked);
+    state.record_withdrawal(lamports, clock.epoch, clock.slot)?;
+    state.store(data);
+
+    if let Some(key) = delegate {
+        let offset = VaultState::find_delegate(data, key).ok_or(VaultError::NotADelegate)?;
+
+        let mut delegate = Delegate::load(&data[offset..])?;
+        require!(delegate.spend(lamports, clock.unix_timestamp), VaultError::AllowanceExceeded);
+        delegate.store(&mut data[offset..]);
+    }
+
+    Ok(())
+}
+
+/// Moves `lamports` out of `vault` into `destination`, which can be the vault itself. With
+/// `keep_rent_exempt`, a vault holding data can't go below its rent-exempt minimum.
+fn move_lamports(vault: &AccountInfo, destination: &AccountInfo, lamports: u64, keep_rent_exempt: bool) -> ProgramResult {
+    let balance = unsafe { *vault.borrow_lamports_unchecked() }
+        .checked_sub(lamports)
+        .ok_or(VaultError::InsufficientFunds)?;
+
+    if keep_rent_exempt && vault.data_len() != 0 {
+        require!(balance >= Rent::get()?.minimum_balance(vault.data_len()), VaultError::BelowRentExempt);
+    }
+
+    unsafe {
+        *vault.borrow_mut_lamports_unchecked() = balance;
+
+        let destination = destination.borrow_mut_lamports_unchecked();
+        *destination = destination.checked_add(lamports).ok_or(VaultError::ArithmeticOverflow)?;
+    }
+    Ok(())
+}
+
+/// With `canonical-bump`, fails unless `bump` is the canonical bump of `seeds`, so an owner
+/// has exactly one vault. Without it any bump that hashes to the vault goes, and this is free.
+#[inline(always)]
+fn check_canonical_bump(seeds: &[&[u8]], bump: u8) -> ProgramResult {
+    #[cfg(feature = "canonical-bump")]
+    {
+        let (_, canonical) = pinocchio::pubkey::try_find_program_address(seeds, &ID).ok_or(VaultError::InvalidPda)?;
+        require!(bump == canonical, VaultError::NonCanonicalBump);
+    }
+
+    #[cfg(not(feature = "canonical-bump"))]
+    let _ = (seeds, bump);
+
+    Ok(())
+}
 
 /// # Withdraw
 ///
-/// Handles withdrawing funds from a PDA that has previously had lamports deposited to it.
-pub fn withdraw(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+/// Handles withdrawing funds from a PDA that has previously had lamports deposited to it,
+/// to the signer or to a destination passed as a third account.
+///
+/// With a destination, a signer that doesn't seed the vault is taken for one of its delegates
+/// and handed to `withdraw_delegated`.
+pub fn withdraw(accounts: &[AccountInfo], lamports: u64, bump: u8, keep_rent_exempt: bool) -> ProgramResult {
+    let ([signer, vault] | [signer, vault, _]) = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(signer.is_signer(), VaultError::MissingSignature);
+
+    let pda = hashv(&[
+        signer.key().as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    let seeds_vault = &pda == vault.key();
+
+    let destination = match accounts {
+        [_, _, destination] if seeds_vault => destination,
+        [_, _, _] => return withdraw_delegated(accounts, lamports, keep_rent_exempt),
+        _ => signer,
+    };
+
+    require!(seeds_vault, VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key().as_ref()], bump)?;
+
+    check_withdrawal(vault, lamports, None)?;
+
+    move_lamports(vault, destination, lamports, keep_rent_exempt)
+}
+
+/// # Withdraw Delegated
+///
+/// Handles a delegate withdrawing from a vault to any destination, out of the allowance its
+/// owner gave it. Only the owner can add delegates to a vault, so finding the signer among
+/// them is enough and the owner's key isn't needed to check the PDA.
+pub fn withdraw_delegated(accounts: &[AccountInfo], lamports: u64, keep_rent_exempt: bool) -> ProgramResult {
+    let [delegate, vault, destination] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(delegate.is_signer(), VaultError::MissingSignature);
+
+    // Multisig configs are ours too, but never pass for vault data
+    require!(vault.owner() == &ID, VaultError::InvalidAccountOwner);
+
+    check_withdrawal(vault, lamports, Some(delegate.key()))?;
+
+    move_lamports(vault, destination, lamports, keep_rent_exempt)
+}
+
+/// # Deposit
+///
+/// Handles topping up the vault of `owner` with lamports from `payer`. Anyone can deposit
+/// into anybody's vault, so the owner travels as data instead of as an extra account.
+///
+/// The first deposit into a vault also assigns it to this program, signing for the PDA with
+/// the `[owner, bump]` seeds. Without that, `withdraw` couldn't debit the vault directly.
+pub fn deposit(accounts: &[AccountInfo], lamports: u64, bump: u8, owner: &Pubkey) -> ProgramResult {
+    let [payer, vault, _system_program] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(payer.is_signer(), VaultError::MissingSignature);
+
+    let bump = [bump];
+    let pda = hashv(&[
+        owner.as_ref(),
+        &bump,
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(&pda == vault.key(), VaultError::InvalidPda);
+    check_canonical_bump(&[owner.as_ref()], bump[0])?;
+
+    // Locked or not, anything else than a vault's own state is rejected
+    VaultState::load(unsafe { vault.borrow_data_unchecked() })?;
+
+    // System program `Transfer`: [2u32, lamports]
+    let mut transfer = [0u8; 12];
+    transfer[0] = 2;
+    transfer[4..].copy_from_slice(&lamports.to_le_bytes());
+
+    invoke(
+        &Instruction {
+            program_id: &SYSTEM_PROGRAM_ID,
+            data: &transfer,
+            accounts: &[
+                AccountMeta::writable_signer(payer.key()),
+                AccountMeta::writable(vault.key()),
+            ],
+        },
+        &[payer, vault],
+    )?;
+
+    if vault.owner() != &ID {
+        // System program `Assign`: [1u32, owner]
+        let mut assign = [0u8; 36];
+        assign[0] = 1;
+        assign[4..].copy_from_slice(&ID);
+
+        let seeds = [Seed::from(owner.as_ref()), Seed::from(&bump)];
+
+        invoke_signed(
+            &Instruction {
+                program_id: &SYSTEM_PROGRAM_ID,
+                data: &assign,
+                accounts: &[AccountMeta::writable_signer(vault.key())],
+            },
+            &[vault],
+            &[Signer::from(&seeds)],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// # Close
+///
+/// Handles shutting a vault down by sending every lamport it holds back to its owner. With
+/// no lamports left the runtime garbage collects the PDA at the end of the transaction.
+///
+/// Until then the PDA is a plain system account: its data is zeroed and dropped and it goes
+/// back to the system program, so a later instruction can't pick up the old lock, limit or
+/// delegates. Depositing into it again starts a new vault.
+pub fn close(accounts: &[AccountInfo], bump: u8) -> ProgramResult {
     let [signer, vault] = accounts else {
-        return Err(ProgramError::NotEnoughAccountKeys);
+        return Err(VaultError::InvalidAccountCount.into());
     };
 
-    assert!(signer.is_signer());
+    require!(signer.is_signer(), VaultError::MissingSignature);
 
-    let lamports: u64 = unsafe { *(data.as_ptr() as *const u64) };
-    let bump = data[8];
     let pda = hashv(&[
         signer.key().as_ref(),
         &[bump],
@@ -31,11 +299,485 @@ pub fn withdraw(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) ->
         PDA_MARKER,
     ]);
 
-    assert_eq!(&pda, vault.key().as_ref());
+    require!(&pda == vault.key(), VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key().as_ref()], bump)?;
 
-    unsafe {
-        *vault.borrow_mut_lamports_unchecked() -= lamports;
-        *signer.borrow_mut_lamports_unchecked() += lamports;
+    let lamports = unsafe { *vault.borrow_lamports_unchecked() };
+    check_withdrawal(vault, lamports, None)?;
+
+    move_lamports(vault, signer, lamports, false)?;
+
+    unsafe { vault.borrow_mut_data_unchecked() }.fill(0);
+    vault.realloc(0, false)?;
+    vault.assign(&SYSTEM_PROGRAM_ID);
+
+    Ok(())
+}
+
+/// # Set Lock
+///
+/// Handles time-locking a vault until both `unlock_timestamp` and `unlock_slot` have been
+/// reached. The first lock grows the vault to `VaultState::LOCK_LEN` bytes, so it has to
+/// hold enough lamports to stay rent-exempt with them. Locks can only be extended, never
+/// lifted.
+pub fn set_lock(accounts: &[AccountInfo], unlock_timestamp: i64, unlock_slot: u64, bump: u8) -> ProgramResult {
+    let [signer, vault] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(signer.is_signer(), VaultError::MissingSignature);
+
+    let pda = hashv(&[
+        signer.key().as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(&pda == vault.key(), VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key().as_ref()], bump)?;
+
+    let mut state = VaultState::load(unsafe { vault.borrow_data_unchecked() })?;
+    require!(state.extend_lock(unlock_timestamp, unlock_slot), VaultError::LockNotExtended);
+
+    vault.realloc(vault.data_len().max(VaultState::LOCK_LEN), false)?;
+    state.store(unsafe { vault.borrow_mut_data_unchecked() });
+
+    Ok(())
+}
+
+/// Fails unless `config` belongs to us and enough of its members signed. Members can come in
+/// any order after the fixed accounts, anything that isn't a signing member is ignored.
+fn check_approved(config: &AccountInfo, members: &[AccountInfo]) -> Result<MultisigConfig, ProgramError> {
+    require!(config.owner() == &ID, VaultError::InvalidAccountOwner);
+
+    let multisig = MultisigConfig::load(unsafe { config.borrow_data_unchecked() })?;
+    require!(
+        multisig.is_approved_by(members.iter().filter(|member| member.is_signer()).map(|member| member.key())),
+        VaultError::NotApproved
+    );
+
+    Ok(multisig)
+}
+
+/// # Create Multisig
+///
+/// Handles writing the member set into a fresh config account. The client creates it with
+/// `MultisigConfig::LEN` bytes and this program as owner in the same transaction, and it has
+/// to sign here so nobody else can claim it in between.
+pub fn create_multisig(accounts: &[AccountInfo], config: &MultisigConfig) -> ProgramResult {
+    let [config_account] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(config_account.is_signer(), VaultError::MissingSignature);
+    require!(config_account.owner() == &ID, VaultError::InvalidAccountOwner);
+    require!(
+        MultisigConfig::is_uncreated(unsafe { config_account.borrow_data_unchecked() }),
+        VaultError::MultisigAlreadyCreated
+    );
+
+    config.store(unsafe { config_account.borrow_mut_data_unchecked() });
+
+    Ok(())
+}
+
+/// # Rotate Members
+///
+/// Handles replacing the member set, approved by the threshold of the current one. The
+/// vault is seeded by the config account, so it stays where it is.
+pub fn rotate_members(accounts: &[AccountInfo], config: &MultisigConfig) -> ProgramResult {
+    let [config_account, members @ ..] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    check_approved(config_account, members)?;
+
+    config.store(unsafe { config_account.borrow_mut_data_unchecked() });
+
+    Ok(())
+}
+
+/// # Withdraw Multisig
+///
+/// Handles withdrawing from the vault of a member set to any destination. Multisig vaults
+/// are never assigned to this program, anyone can fund them with a plain transfer, so the
+/// lamports leave through the system program with the vault signing.
+pub fn withdraw_multisig(accounts: &[AccountInfo], lamports: u64, bump: u8) -> ProgramResult {
+    let [config, vault, destination, _system_program, members @ ..] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    check_approved(config, members)?;
+
+    let bump = [bump];
+    let pda = hashv(&[
+        MultisigConfig::SEED,
+        config.key().as_ref(),
+        &bump,
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(&pda == vault.key(), VaultError::InvalidPda);
+    check_canonical_bump(&[MultisigConfig::SEED, config.key().as_ref()], bump[0])?;
+
+    // System program `Transfer`: [2u32, lamports]
+    let mut transfer = [0u8; 12];
+    transfer[0] = 2;
+    transfer[4..].copy_from_slice(&lamports.to_le_bytes());
+
+    let seeds = [
+        Seed::from(MultisigConfig::SEED),
+        Seed::from(config.key().as_ref()),
+        Seed::from(&bump),
+    ];
+
+    invoke_signed(
+        &Instruction {
+            program_id: &SYSTEM_PROGRAM_ID,
+            data: &transfer,
+            accounts: &[
+                AccountMeta::writable_signer(vault.key()),
+                AccountMeta::writable(destination.key()),
+            ],
+        },
+        &[vault, destination],
+        &[Signer::from(&seeds)],
+    )
+}
+
+/// Fails unless `token_account` is an SPL token account held by `vault`.
+fn check_token_vault(token_account: &AccountInfo, vault: &Pubkey) -> ProgramResult {
+    require!(token_account.owner() == &TOKEN_PROGRAM_ID, VaultError::InvalidTokenAccount);
+
+    // Token account: [mint 32][owner 32][amount 8]..., 165 bytes
+    let data = unsafe { token_account.borrow_data_unchecked() };
+    require!(data.len() == 165 && &data[32..64] == vault.as_ref(), VaultError::InvalidTokenAccount);
+
+    Ok(())
+}
+
+/// # Deposit Token
+///
+/// Handles moving tokens from `payer` into the vault of `owner`. The vault holds tokens in
+/// any token account whose authority is its PDA (usually its associated token account), and
+/// the PDA itself doesn't have to exist for that.
+pub fn deposit_token(accounts: &[AccountInfo], amount: u64, bump: u8, owner: &Pubkey) -> ProgramResult {
+    let [payer, payer_token_account, vault_token_account, token_program] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(payer.is_signer(), VaultError::MissingSignature);
+    require!(token_program.key() == &TOKEN_PROGRAM_ID, VaultError::InvalidAccount);
+
+    let pda = hashv(&[
+        owner.as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    check_canonical_bump(&[owner.as_ref()], bump)?;
+    check_token_vault(vault_token_account, &pda)?;
+
+    Transfer {
+        from: payer_token_account,
+        to: vault_token_account,
+        authority: payer,
+        amount,
+    }
+    .invoke()
+}
+
+/// # Withdraw Token
+///
+/// Handles withdrawing tokens from the vault to any token account, the vault PDA signing
+/// for its token account with the `[signer, bump]` seeds. Locks hold for tokens too.
+pub fn withdraw_token(accounts: &[AccountInfo], amount: u64, bump: u8) -> ProgramResult {
+    let [signer, vault, vault_token_account, destination, token_program] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(signer.is_signer(), VaultError::MissingSignature);
+    require!(token_program.key() == &TOKEN_PROGRAM_ID, VaultError::InvalidAccount);
+
+    let bump = [bump];
+    let pda = hashv(&[
+        signer.key().as_ref(),
+        &bump,
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(&pda == vault.key(), VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key().as_ref()], bump[0])?;
+
+    check_unlocked(vault)?;
+    check_token_vault(vault_token_account, vault.key())?;
+
+    let seeds = [Seed::from(signer.key().as_ref()), Seed::from(&bump)];
+
+    Transfer {
+        from: vault_token_account,
+        to: destination,
+        authority: vault,
+        amount,
     }
+    .invoke_signed(&[Signer::from(&seeds)])
+}
+
+/// # Set Rate Limit
+///
+/// Handles capping how many lamports can leave the vault per epoch, with 0 lifting the cap.
+/// A lower limit applies right away, a higher one after `RATE_LIMIT_RAISE_DELAY` slots. The
+/// first limit grows the vault to `VaultState::LEN` bytes, which its lamports have to keep
+/// rent-exempt.
+pub fn set_rate_limit(accounts: &[AccountInfo], limit: u64, bump: u8) -> ProgramResult {
+    let [signer, vault] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(signer.is_signer(), VaultError::MissingSignature);
+
+    let pda = hashv(&[
+        signer.key().as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(&pda == vault.key(), VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key().as_ref()], bump)?;
+
+    let mut state = VaultState::load(unsafe { vault.borrow_data_unchecked() })?;
+    state.set_limit(limit, Clock::get()?.slot);
+
+    vault.realloc(vault.data_len().max(VaultState::LEN), false)?;
+    state.store(unsafe { vault.borrow_mut_data_unchecked() });
+
+    Ok(())
+}
+
+/// # Set Delegate
+///
+/// Handles letting `delegate` withdraw up to its allowance from the vault until it expires,
+/// or replacing the allowance and expiry of a delegate the vault already has. Every new
+/// delegate grows the vault by `Delegate::LEN` bytes past `VaultState::LEN`, which its
+/// lamports have to keep rent-exempt.
+pub fn set_delegate(accounts: &[AccountInfo], delegate: &Delegate, bump: u8) -> ProgramResult {
+    let [signer, vault] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(signer.is_signer(), VaultError::MissingSignature);
+
+    let pda = hashv(&[
+        signer.key().as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(&pda == vault.key(), VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key().as_ref()], bump)?;
+
+    let data = unsafe { vault.borrow_data_unchecked() };
+    let state = VaultState::load(data)?;
+
+    let offset = match VaultState::find_delegate(data, &delegate.key) {
+        Some(offset) => offset,
+        None => {
+            let len = data.len().max(VaultState::LEN);
+            require!(len < VaultState::LEN + VaultState::MAX_DELEGATES * Delegate::LEN, VaultError::TooManyDelegates);
+
+            vault.realloc(len + Delegate::LEN, false)?;
+            len
+        }
+    };
+
+    let data = unsafe { vault.borrow_mut_data_unchecked() };
+    state.store(data);
+    delegate.store(&mut data[offset..]);
+
+    Ok(())
+}
+
+/// # Revoke Delegate
+///
+/// Handles taking a delegate off the vault, whatever is left of its allowance. The last
+/// delegate moves into its place and the vault shrinks back by `Delegate::LEN` bytes.
+pub fn revoke_delegate(accounts: &[AccountInfo], delegate: &Pubkey, bump: u8) -> ProgramResult {
+    let [signer, vault] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(signer.is_signer(), VaultError::MissingSignature);
+
+    let pda = hashv(&[
+        signer.key().as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(&pda == vault.key(), VaultError::InvalidPda);
+    check_canonical_bump(&[signer.key().as_ref()], bump)?;
+
+    let data = unsafe { vault.borrow_mut_data_unchecked() };
+    VaultState::load(data)?;
+
+    let offset = VaultState::find_delegate(data, delegate).ok_or(VaultError::NotADelegate)?;
+    let last = data.len() - Delegate::LEN;
+    data.copy_within(last.., offset);
+
+    vault.realloc(last, false)
+}
+
+/// Fails unless `stream` is the PDA of `[b"stream", owner, recipient, bump]`.
+fn check_stream(stream: &AccountInfo, owner: &Pubkey, recipient: &Pubkey, bump: u8) -> ProgramResult {
+    let pda = hashv(&[
+        Stream::SEED,
+        owner.as_ref(),
+        recipient.as_ref(),
+        &[bump],
+        ID.as_ref(),
+        PDA_MARKER,
+    ]);
+
+    require!(&pda == stream.key(), VaultError::InvalidPda);
+    check_canonical_bump(&[Stream::SEED, owner.as_ref(), recipient.as_ref()], bump)
+}
+
+/// # Create Stream
+///
+/// Handles the owner locking `amount` lamports into a stream to `recipient`. The stream
+/// account is set up here the way Anchor's `init` does it, so lamports someone sent to the
+/// address beforehand can't block it: the owner tops it up to its rent-exempt minimum plus
+/// `amount`, then the PDA signs for `Allocate` and `Assign`. A live stream already has data
+/// and isn't the system program's, so `Allocate` fails on it and one recipient can only have
+/// one stream from the same owner at a time.
+pub fn create_stream(accounts: &[AccountInfo], recipient: &Pubkey, stream: &Stream, bump: u8) -> ProgramResult {
+    let [owner, stream_account, _system_program] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(owner.is_signer(), VaultError::MissingSignature);
+    check_stream(stream_account, owner.key(), recipient, bump)?;
+
+    let lamports = Rent::get()?
+        .minimum_balance(Stream::LEN)
+        .checked_add(stream.amount)
+        .ok_or(VaultError::ArithmeticOverflow)?
+        .saturating_sub(unsafe { *stream_account.borrow_lamports_unchecked() });
+
+    if lamports > 0 {
+        // System program `Transfer`: [2u32, lamports]
+        let mut transfer = [0u8; 12];
+        transfer[0] = 2;
+        transfer[4..].copy_from_slice(&lamports.to_le_bytes());
+
+        invoke(
+            &Instruction {
+                program_id: &SYSTEM_PROGRAM_ID,
+                data: &transfer,
+                accounts: &[
+                    AccountMeta::writable_signer(owner.key()),
+                    AccountMeta::writable(stream_account.key()),
+                ],
+            },
+            &[owner, stream_account],
+        )?;
+    }
+
+    let bump = [bump];
+    let seeds = [
+        Seed::from(Stream::SEED),
+        Seed::from(owner.key().as_ref()),
+        Seed::from(recipient.as_ref()),
+        Seed::from(&bump),
+    ];
+
+    // System program `Allocate`: [8u32, space]
+    let mut allocate = [0u8; 12];
+    allocate[0] = 8;
+    allocate[4..].copy_from_slice(&(Stream::LEN as u64).to_le_bytes());
+
+    invoke_signed(
+        &Instruction {
+            program_id: &SYSTEM_PROGRAM_ID,
+            data: &allocate,
+            accounts: &[AccountMeta::writable_signer(stream_account.key())],
+        },
+        &[stream_account],
+        &[Signer::from(&seeds)],
+    )?;
+
+    // System program `Assign`: [1u32, owner]
+    let mut assign = [0u8; 36];
+    assign[0] = 1;
+    assign[4..].copy_from_slice(&ID);
+
+    invoke_signed(
+        &Instruction {
+            program_id: &SYSTEM_PROGRAM_ID,
+            data: &assign,
+            accounts: &[AccountMeta::writable_signer(stream_account.key())],
+        },
+        &[stream_account],
+        &[Signer::from(&seeds)],
+    )?;
+
+    stream.store(unsafe { stream_account.borrow_mut_data_unchecked() });
+
+    Ok(())
+}
+
+/// # Withdraw Stream
+///
+/// Handles the recipient taking whatever has vested and they haven't withdrawn yet, straight
+/// out of the stream like a vault withdrawal.
+pub fn withdraw_stream(accounts: &[AccountInfo], owner: &Pubkey, bump: u8) -> ProgramResult {
+    let [recipient, stream_account] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(recipient.is_signer(), VaultError::MissingSignature);
+    check_stream(stream_account, owner, recipient.key(), bump)?;
+
+    let data = unsafe { stream_account.borrow_mut_data_unchecked() };
+    let mut stream = Stream::load(data)?;
+
+    let lamports = stream.withdrawable(Clock::get()?.unix_timestamp);
+    stream.withdrawn += lamports;
+    stream.store(data);
+
+    move_lamports(stream_account, recipient, lamports, false)
+}
+
+/// # Cancel Stream
+///
+/// Handles the owner calling a stream off. What has vested and isn't withdrawn yet still
+/// goes to the recipient, everything else (the unvested part and the rent) back to the
+/// owner, and the stream is shut down the same way `close` shuts a vault down.
+pub fn cancel_stream(accounts: &[AccountInfo], bump: u8) -> ProgramResult {
+    let [owner, stream_account, recipient] = accounts else {
+        return Err(VaultError::InvalidAccountCount.into());
+    };
+
+    require!(owner.is_signer(), VaultError::MissingSignature);
+    check_stream(stream_account, owner.key(), recipient.key(), bump)?;
+
+    let stream = Stream::load(unsafe { stream_account.borrow_data_unchecked() })?;
+
+    move_lamports(stream_account, recipient, stream.withdrawable(Clock::get()?.unix_timestamp), false)?;
+
+    let lamports = unsafe { *stream_account.borrow_lamports_unchecked() };
+    move_lamports(stream_account, owner, lamports, false)?;
+
+    unsafe { stream_account.borrow_mut_data_unchecked() }.fill(0);
+    stream_account.realloc(0, false)?;
+    stream_account.assign(&SYSTEM_PROGRAM_ID);
+
     Ok(())
 }
@@ -0,0 +1,440 @@
+/// # Vault state
+///
+/// A vault starts out holding nothing but lamports. Data only shows up once its owner opts
+/// into something that needs it, so a vault without data behaves exactly as it always has.
+/// `SetLock` grows a vault to `LOCK_LEN` bytes and `SetRateLimit` to `LEN`, fields past the
+/// end of a shorter vault read as 0. `SetDelegate` grows it past `LEN`, one `Delegate` at a
+/// time.
+///
+/// -- Layout --
+/// > unlock_timestamp [u8; 8]
+/// > unlock_slot [u8; 8]
+/// > limit [u8; 8]
+/// > pending_limit [u8; 8]
+/// > pending_slot [u8; 8]
+/// > epoch [u8; 8]
+/// > withdrawn [u8; 8]
+/// > delegates [Delegate; n], up to `MAX_DELEGATES`
+///
+/// -- Lock --
+/// Withdrawing (or closing) only works once the Clock sysvar has reached both the unix
+/// timestamp and the slot. Either one can be left at 0 to only lock on the other.
+///
+/// -- Rate limit --
+/// At most `limit` lamports can leave the vault per epoch, `withdrawn` counts them for
+/// `epoch`. A limit of 0 means no limit. Lowering the limit applies right away, raising it
+/// only once `RATE_LIMIT_RAISE_DELAY` slots have passed, so a stolen key can't lift it and
+/// drain the vault in one go. Token withdrawals aren't counted.
+///
+/// -- Delegates --
+/// Keys the owner lets withdraw to anywhere, within their own allowance. Delegated
+/// withdrawals are held to the lock and rate limit like any other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VaultState {
+    pub unlock_timestamp: i64,
+    pub unlock_slot: u64,
+    pub limit: u64,
+    pub pending_limit: u64,
+    pub pending_slot: u64,
+    pub epoch: u64,
+    pub withdrawn: u64,
+}
+
+/// Vault data that is neither empty nor a `VaultState`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidVaultData;
+
+/// A withdrawal over what is left of the vault's limit for this epoch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimitExceeded;
+
+/// Slots a raised rate limit waits before it applies, about a day.
+pub const RATE_LIMIT_RAISE_DELAY: u64 = 216_000;
+
+/// Bytes every account is charged rent for on top of its data.
+pub const ACCOUNT_STORAGE_OVERHEAD: u64 = 128;
+
+/// The lamports an account with `data_len` bytes needs to be rent-exempt, for backends that
+/// read the Rent sysvar raw. Same arithmetic as the SDKs' `Rent::minimum_balance`.
+pub fn minimum_balance(lamports_per_byte_year: u64, exemption_threshold: f64, data_len: usize) -> u64 {
+    (((ACCOUNT_STORAGE_OVERHEAD + data_len as u64) * lamports_per_byte_year) as f64 * exemption_threshold) as u64
+}
+
+impl VaultState {
+    /// Vaults that only ever had a lock set.
+    pub const LOCK_LEN: usize = 16;
+    pub const LEN: usize = 56;
+    pub const MAX_DELEGATES: usize = 8;
+
+    /// Whether `len` bytes of data can be a vault.
+    pub fn is_valid_len(len: usize) -> bool {
+        match len {
+            0 | Self::LOCK_LEN => true,
+            _ if len >= Self::LEN => {
+                let delegates = (len - Self::LEN) / Delegate::LEN;
+                len == Self::LEN + delegates * Delegate::LEN && delegates <= Self::MAX_DELEGATES
+            }
+            _ => false,
+        }
+    }
+
+    /// Reads the state of a vault, an empty vault is an unlocked one without a limit.
+    pub fn load(data: &[u8]) -> Result<Self, InvalidVaultData> {
+        if !Self::is_valid_len(data.len()) {
+            return Err(InvalidVaultData);
+        }
+
+        let field = |i: usize| match data.get(i * 8..i * 8 + 8) {
+            Some(bytes) => read_u64(bytes),
+            None => 0,
+        };
+
+        Ok(Self {
+            unlock_timestamp: field(0) as i64,
+            unlock_slot: field(1),
+            limit: field(2),
+            pending_limit: field(3),
+            pending_slot: field(4),
+            epoch: field(5),
+            withdrawn: field(6),
+        })
+    }
+
+    /// Writes as much of the state as fits into `data`, which is at least `LOCK_LEN` bytes
+    /// long. Delegates after the state are left alone.
+    pub fn store(&self, data: &mut [u8]) {
+        let fields = [
+            self.unlock_timestamp as u64,
+            self.unlock_slot,
+            self.limit,
+            self.pending_limit,
+            self.pending_slot,
+            self.epoch,
+            self.withdrawn,
+        ];
+
+        for (chunk, field) in data.chunks_exact_mut(8).zip(fields) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+    }
+
+    pub fn is_unlocked(&self, unix_timestamp: i64, slot: u64) -> bool {
+        unix_timestamp >= self.unlock_timestamp && slot >= self.unlock_slot
+    }
+
+    /// Moves the lock to `unlock_timestamp` and `unlock_slot`. A lock can only ever be
+    /// pushed further out, so this fails if either of them would unlock earlier than now.
+    pub fn extend_lock(&mut self, unlock_timestamp: i64, unlock_slot: u64) -> bool {
+        if unlock_timestamp < self.unlock_timestamp || unlock_slot < self.unlock_slot {
+            return false;
+        }
+
+        self.unlock_timestamp = unlock_timestamp;
+        self.unlock_slot = unlock_slot;
+        true
+    }
+
+    /// Changes the limit at `slot`. Anything stricter than the current limit replaces it and
+    /// drops a pending raise, anything looser becomes the pending raise.
+    pub fn set_limit(&mut self, limit: u64, slot: u64) {
+        self.apply_pending_limit(slot);
+
+        // No limit is the loosest one there is
+        let strictness = |limit: u64| if limit == 0 { u64::MAX } else { limit };
+
+        if strictness(limit) <= strictness(self.limit) {
+            self.limit = limit;
+            self.pending_limit = 0;
+            self.pending_slot = 0;
+        } else {
+            self.pending_limit = limit;
+            self.pending_slot = slot.saturating_add(RATE_LIMIT_RAISE_DELAY);
+        }
+    }
+
+    /// Counts `lamports` against the limit of `epoch`, or fails if they don't fit in it.
+    pub fn record_withdrawal(&mut self, lamports: u64, epoch: u64, slot: u64) -> Result<(), RateLimitExceeded> {
+        self.apply_pending_limit(slot);
+
+        if epoch != self.epoch {
+            self.epoch = epoch;
+            self.withdrawn = 0;
+        }
+
+        let withdrawn = self.withdrawn.saturating_add(lamports);
+        if self.limit != 0 && withdrawn > self.limit {
+            return Err(RateLimitExceeded);
+        }
+
+        self.withdrawn = withdrawn;
+        Ok(())
+    }
+
+    fn apply_pending_limit(&mut self, slot: u64) {
+        if self.pending_slot != 0 && slot >= self.pending_slot {
+            self.limit = self.pending_limit;
+            self.pending_limit = 0;
+            self.pending_slot = 0;
+        }
+    }
+
+    /// Offset of the delegate with `key` in the vault `data`.
+    pub fn find_delegate(data: &[u8], key: &[u8]) -> Option<usize> {
+        let delegates = data.get(Self::LEN..)?;
+
+        delegates
+            .chunks_exact(Delegate::LEN)
+            .position(|delegate| delegate[..32] == *key)
+            .map(|i| Self::LEN + i * Delegate::LEN)
+    }
+}
+
+/// # Delegate
+///
+/// -- Layout --
+/// > key [u8; 32]
+/// > allowance [u8; 8]
+/// > expires_at [u8; 8]
+///
+/// `allowance` is what's left for the delegate to withdraw, in lamports. `expires_at` is a
+/// unix timestamp, 0 never expires.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Delegate {
+    pub key: [u8; 32],
+    pub allowance: u64,
+    pub expires_at: i64,
+}
+
+impl Delegate {
+    pub const LEN: usize = 48;
+
+    /// Reads the delegate at the start of `data`, anything past `LEN` bytes is ignored.
+    pub fn load(data: &[u8]) -> Result<Self, InvalidVaultData> {
+        if data.len() < Self::LEN {
+            return Err(InvalidVaultData);
+        }
+
+        Ok(Self {
+            key: data[..32].try_into().unwrap(),
+            allowance: read_u64(&data[32..]),
+            expires_at: read_u64(&data[40..]) as i64,
+        })
+    }
+
+    /// Writes the delegate to the start of `data`, which has to be at least `LEN` bytes long.
+    pub fn store(&self, data: &mut [u8]) {
+        data[..32].copy_from_slice(&self.key);
+        data[32..40].copy_from_slice(&self.allowance.to_le_bytes());
+        data[40..48].copy_from_slice(&self.expires_at.to_le_bytes());
+    }
+
+    /// Takes `lamports` out of the allowance, unless it doesn't cover them or the delegate
+    /// expired by `unix_timestamp`.
+    pub fn spend(&mut self, lamports: u64, unix_timestamp: i64) -> bool {
+        if self.expires_at != 0 && unix_timestamp >= self.expires_at {
+            return false;
+        }
+
+        let Some(allowance) = self.allowance.checked_sub(lamports) else {
+            return false;
+        };
+
+        self.allowance = allowance;
+        true
+    }
+}
+
+// Only called once the length has been checked above.
+#[inline(always)]
+fn read_u64(data: &[u8]) -> u64 {
+    unsafe { (data.as_ptr() as *const u64).read_unaligned() }
+}
+
+/// # Multisig
+///
+/// The member set of a multisig vault. It lives in its own config account, created by the
+/// client with `MultisigConfig::LEN` bytes and assigned to this program, and the vault is
+/// the PDA of `[b"multisig", config, bump]`. Rotating members rewrites the config in place,
+/// so the vault never moves.
+///
+/// -- Layout --
+/// > threshold [u8; 1]
+/// > member_count [u8; 1]
+/// > members [[u8; 32]; MAX_MEMBERS], only the first `member_count` are used
+///
+/// A threshold of 0 marks a config that hasn't been created yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MultisigConfig {
+    threshold: u8,
+    member_count: u8,
+    members: [[u8; 32]; MultisigConfig::MAX_MEMBERS],
+}
+
+/// A member set that can't ever approve anything, or has members twice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidMultisig;
+
+impl MultisigConfig {
+    pub const MAX_MEMBERS: usize = 10;
+    pub const LEN: usize = 2 + 32 * Self::MAX_MEMBERS;
+    pub const SEED: &'static [u8] = b"multisig";
+
+    /// Takes between 1 and `MAX_MEMBERS` distinct members, of which `threshold` (at least
+    /// one) have to approve.
+    pub fn new(threshold: u8, members: &[[u8; 32]]) -> Result<Self, InvalidMultisig> {
+        if threshold == 0 || threshold as usize > members.len() || members.len() > Self::MAX_MEMBERS {
+            return Err(InvalidMultisig);
+        }
+
+        for (i, member) in members.iter().enumerate() {
+            if members[..i].contains(member) {
+                return Err(InvalidMultisig);
+            }
+        }
+
+        let mut config = Self {
+            threshold,
+            member_count: members.len() as u8,
+            members: [[0; 32]; Self::MAX_MEMBERS],
+        };
+        config.members[..members.len()].copy_from_slice(members);
+        Ok(config)
+    }
+
+    /// Reads a created config.
+    pub fn load(data: &[u8]) -> Result<Self, InvalidVaultData> {
+        if data.len() != Self::LEN {
+            return Err(InvalidVaultData);
+        }
+
+        let member_count = data[1] as usize;
+        if member_count > Self::MAX_MEMBERS {
+            return Err(InvalidVaultData);
+        }
+
+        let members: &[[u8; 32]] =
+            unsafe { core::slice::from_raw_parts(data[2..].as_ptr() as *const [u8; 32], member_count) };
+
+        Self::new(data[0], members).map_err(|_| InvalidVaultData)
+    }
+
+    /// Whether `data` is the right size for a config that hasn't been created yet.
+    pub fn is_uncreated(data: &[u8]) -> bool {
+        data.len() == Self::LEN && data[0] == 0
+    }
+
+    /// Writes the config into `data`, which has to be exactly `LEN` bytes long.
+    pub fn store(&self, data: &mut [u8]) {
+        data[0] = self.threshold;
+        data[1] = self.member_count;
+        for (chunk, member) in data[2..Self::LEN].chunks_exact_mut(32).zip(&self.members) {
+            chunk.copy_from_slice(member);
+        }
+    }
+
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    pub fn members(&self) -> &[[u8; 32]] {
+        &self.members[..self.member_count as usize]
+    }
+
+    /// Whether at least `threshold` distinct members are among `signers`. Signing twice, or
+    /// passing the same account twice, still only counts once.
+    pub fn is_approved_by<K: AsRef<[u8]>>(&self, signers: impl IntoIterator<Item = K>) -> bool {
+        let mut approved = 0u16;
+
+        for signer in signers {
+            if let Some(i) = self.members().iter().position(|member| member[..] == *signer.as_ref()) {
+                approved |= 1 << i;
+            }
+        }
+
+        approved.count_ones() >= self.threshold as u32
+    }
+}
+
+/// # Stream
+///
+/// Lamports an owner streams to a recipient, vesting linearly from `start` to `end`. Nothing
+/// vests before the cliff, at which point everything since `start` vests at once. A stream
+/// is its own account, the PDA of `[b"stream", owner, recipient, bump]`, holding what is
+/// left of `amount` on top of its rent-exempt minimum.
+///
+/// -- Layout --
+/// > amount [u8; 8]
+/// > withdrawn [u8; 8]
+/// > start [u8; 8]
+/// > cliff [u8; 8]
+/// > end [u8; 8]
+///
+/// Times are unix timestamps, a cliff of 0 means there is none.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stream {
+    pub amount: u64,
+    pub withdrawn: u64,
+    pub start: i64,
+    pub cliff: i64,
+    pub end: i64,
+}
+
+/// A schedule that ends before it starts, has its cliff outside of it, or streams nothing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidStream;
+
+impl Stream {
+    pub const LEN: usize = 40;
+    pub const SEED: &'static [u8] = b"stream";
+
+    pub fn new(amount: u64, start: i64, cliff: i64, end: i64) -> Result<Self, InvalidStream> {
+        if amount == 0 || start >= end || (cliff != 0 && (cliff < start || cliff > end)) {
+            return Err(InvalidStream);
+        }
+
+        Ok(Self { amount, withdrawn: 0, start, cliff, end })
+    }
+
+    pub fn load(data: &[u8]) -> Result<Self, InvalidVaultData> {
+        if data.len() != Self::LEN {
+            return Err(InvalidVaultData);
+        }
+
+        Ok(Self {
+            amount: read_u64(data),
+            withdrawn: read_u64(&data[8..]),
+            start: read_u64(&data[16..]) as i64,
+            cliff: read_u64(&data[24..]) as i64,
+            end: read_u64(&data[32..]) as i64,
+        })
+    }
+
+    /// Writes the stream into `data`, which has to be exactly `LEN` bytes long.
+    pub fn store(&self, data: &mut [u8]) {
+        let fields = [self.amount, self.withdrawn, self.start as u64, self.cliff as u64, self.end as u64];
+
+        for (chunk, field) in data.chunks_exact_mut(8).zip(fields) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+    }
+
+    /// How much of `amount` has vested by `unix_timestamp`, withdrawn or not.
+    pub fn vested(&self, unix_timestamp: i64) -> u64 {
+        if unix_timestamp < self.start || unix_timestamp < self.cliff {
+            return 0;
+        }
+        if unix_timestamp >= self.end {
+            return self.amount;
+        }
+
+        let elapsed = (unix_timestamp as i128 - self.start as i128) as u128;
+        let duration = (self.end as i128 - self.start as i128) as u128;
+
+        (self.amount as u128 * elapsed / duration) as u64
+    }
+
+    /// What the recipient can withdraw at `unix_timestamp`.
+    pub fn withdrawable(&self, unix_timestamp: i64) -> u64 {
+        self.vested(unix_timestamp).saturating_sub(self.withdrawn)
+    }
+}
@@ -1,48 +1,1737 @@
+#[cfg(test)]
+mod differential;
+
 #[cfg(test)]
 mod tests {
-    use mollusk_svm::Mollusk;
+    use mollusk_svm::{program, result::ProgramResult, Mollusk};
     use solana_sdk::{
-        account::{AccountSharedData, ReadableAccount}, entrypoint::ProgramResult, instruction::{AccountMeta, Instruction}, pubkey::Pubkey
+        account::{AccountSharedData, ReadableAccount, WritableAccount}, instruction::{AccountMeta, Instruction}, program_error::ProgramError, program_option::COption, program_pack::Pack, pubkey::Pubkey
     };
+    use spl_token::state::AccountState;
 
-    #[test]
-    fn withdraw() {
-        let program_id = Pubkey::new_from_array([
-            0x7b, 0x07, 0x5a, 0x4f, 0xca, 0x15, 0x61, 0x6e, 
-            0xbe, 0x53, 0xc1, 0xa8, 0x43, 0x6f, 0x42, 0x89, 
-            0x2b, 0x02, 0x1a, 0xb6, 0x62, 0x5a, 0x2a, 0x02, 
-            0x2a, 0x68, 0x9a, 0xef, 0xbd, 0xed, 0x26, 0xef
-        ]);
+    use crate::{
+        error::VaultError,
+        input::{instruction_data, Input, MAX_PERMITTED_DATA_INCREASE, NON_DUP_MARKER},
+        instruction::{InvalidInstructionData, VaultInstruction},
+        state::{
+            minimum_balance, Delegate, InvalidMultisig, InvalidStream, InvalidVaultData, MultisigConfig, RateLimitExceeded,
+            Stream, VaultState, RATE_LIMIT_RAISE_DELAY,
+        },
+    };
 
-        let signer = Pubkey::new_unique();
-        let (vault, bump) =
-            Pubkey::try_find_program_address(&[signer.as_ref()], &program_id).unwrap();
+    pub(super) const PROGRAM_ID: Pubkey = Pubkey::new_from_array(crate::ID);
 
-        let instruction = Instruction::new_with_bytes(
-            program_id,
-            &[&1_000_000_000u64.to_le_bytes()[..], &[bump]].concat(),
+    /// A vault of `signer` at a bump below the canonical one, that is off the curve too.
+    pub(super) fn lower_bump(signer: &Pubkey) -> (Pubkey, u8) {
+        let (_, canonical) = Pubkey::find_program_address(&[signer.as_ref()], &PROGRAM_ID);
+
+        (0..canonical)
+            .rev()
+            .find_map(|bump| {
+                let vault = Pubkey::create_program_address(&[signer.as_ref(), &[bump]], &PROGRAM_ID).ok()?;
+                Some((vault, bump))
+            })
+            .unwrap()
+    }
+
+    fn withdraw_instruction(signer: Pubkey, vault: Pubkey, bump: u8, lamports: u64) -> Instruction {
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[&[0], &lamports.to_le_bytes()[..], &[bump]].concat(),
+            vec![
+                AccountMeta::new(signer, true),
+                AccountMeta::new(vault, false),
+            ],
+        )
+    }
+
+    fn deposit_instruction(payer: Pubkey, owner: Pubkey, vault: Pubkey, bump: u8, lamports: u64) -> Instruction {
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[&[1], &lamports.to_le_bytes()[..], &[bump], owner.as_ref()].concat(),
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(solana_sdk::system_program::ID, false),
+            ],
+        )
+    }
+
+    fn set_lock_instruction(signer: Pubkey, vault: Pubkey, bump: u8, unlock_timestamp: i64, unlock_slot: u64) -> Instruction {
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[&[3], &unlock_timestamp.to_le_bytes()[..], &unlock_slot.to_le_bytes(), &[bump]].concat(),
+            vec![
+                AccountMeta::new(signer, true),
+                AccountMeta::new(vault, false),
+            ],
+        )
+    }
+
+    fn set_delegate_instruction(signer: Pubkey, vault: Pubkey, bump: u8, delegate: Pubkey, allowance: u64, expires_at: i64) -> Instruction {
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[&[10], delegate.as_ref(), &allowance.to_le_bytes(), &expires_at.to_le_bytes(), &[bump]].concat(),
             vec![
                 AccountMeta::new(signer, true),
                 AccountMeta::new(vault, false),
             ],
+        )
+    }
+
+    fn withdraw_delegated_instruction(delegate: Pubkey, vault: Pubkey, destination: Pubkey, lamports: u64) -> Instruction {
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[&[0], &lamports.to_le_bytes()[..], &[0]].concat(),
+            vec![
+                AccountMeta::new(delegate, true),
+                AccountMeta::new(vault, false),
+                AccountMeta::new(destination, false),
+            ],
+        )
+    }
+
+    fn multisig_data(discriminator: u8, threshold: u8, members: &[Pubkey]) -> Vec<u8> {
+        let mut data = vec![discriminator, threshold, members.len() as u8];
+        for member in members {
+            data.extend_from_slice(member.as_ref());
+        }
+        data
+    }
+
+    /// A created config account that needs 2 of `members`, its vault and the vault's bump.
+    fn multisig(members: &[Pubkey]) -> (Pubkey, AccountSharedData, Pubkey, u8) {
+        let config = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[b"multisig", config.as_ref()], &PROGRAM_ID).unwrap();
+
+        let members: Vec<[u8; 32]> = members.iter().map(|member| member.to_bytes()).collect();
+        let mut data = vec![0; MultisigConfig::LEN];
+        MultisigConfig::new(2, &members).unwrap().store(&mut data);
+
+        let mut config_account = AccountSharedData::new(1_000_000_000, MultisigConfig::LEN, &PROGRAM_ID);
+        config_account.set_data_from_slice(&data);
+
+        (config, config_account, vault, bump)
+    }
+
+    fn withdraw_multisig_instruction(config: Pubkey, vault: Pubkey, destination: Pubkey, bump: u8, lamports: u64, signers: &[Pubkey]) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(config, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(destination, false),
+            AccountMeta::new_readonly(solana_sdk::system_program::ID, false),
+        ];
+        accounts.extend(signers.iter().map(|signer| AccountMeta::new_readonly(*signer, true)));
+
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[&[6], &lamports.to_le_bytes()[..], &[bump]].concat(),
+            accounts,
+        )
+    }
+
+    fn token_account(mollusk: &Mollusk, mint: Pubkey, owner: Pubkey, amount: u64) -> AccountSharedData {
+        let mut account = AccountSharedData::new(
+            mollusk.sysvars.rent.minimum_balance(spl_token::state::Account::LEN),
+            spl_token::state::Account::LEN,
+            &spl_token::ID,
         );
+        Pack::pack(
+            spl_token::state::Account {
+                mint,
+                owner,
+                amount,
+                delegate: COption::None,
+                state: AccountState::Initialized,
+                is_native: COption::None,
+                delegated_amount: 0,
+                close_authority: COption::None,
+            },
+            account.data_as_mut_slice(),
+        )
+        .unwrap();
+        account
+    }
+
+    fn token_amount(account: &AccountSharedData) -> u64 {
+        spl_token::state::Account::unpack(account.data()).unwrap().amount
+    }
+
+    #[test]
+    fn withdraw() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
 
-        let mollusk = Mollusk::new(&program_id, "target/deploy/native_vault");
+        let instruction = withdraw_instruction(signer, vault, bump, 1_000_000_000);
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
 
         let result: mollusk_svm::result::InstructionResult = mollusk.process_instruction(
             &instruction,
-            &vec![
+            &[
                 (
                     signer,
                     AccountSharedData::new(0, 0, &Pubkey::default()),
                 ),
-                (vault, AccountSharedData::new(1_000_000_000u64, 0, &program_id)),
+                (vault, AccountSharedData::new(1_000_000_000u64, 0, &PROGRAM_ID)),
+            ],
+        );
+
+        assert_eq!(result.get_account(&signer).unwrap().lamports(), 1_000_000_000);
+        assert_eq!(result.get_account(&vault).unwrap().lamports(), 0);
+
+        assert!(!result.program_result.is_err());
+    }
+
+    #[test]
+    fn withdraw_more_than_the_vault_holds() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let result = mollusk.process_instruction(
+            &withdraw_instruction(signer, vault, bump, 1_000_000_001),
+            &[
+                (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID)),
+            ],
+        );
+
+        assert_eq!(
+            result.program_result,
+            ProgramResult::Failure(ProgramError::Custom(VaultError::InsufficientFunds.code()))
+        );
+
+        // Or than the signer can hold
+        let result = mollusk.process_instruction(
+            &withdraw_instruction(signer, vault, bump, 1),
+            &[
+                (signer, AccountSharedData::new(u64::MAX, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID)),
+            ],
+        );
+
+        assert_eq!(
+            result.program_result,
+            ProgramResult::Failure(ProgramError::Custom(VaultError::ArithmeticOverflow.code()))
+        );
+    }
+
+    #[test]
+    fn withdraw_to_destination() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+        let destination = Pubkey::new_unique();
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let mut instruction = withdraw_instruction(signer, vault, bump, 400_000_000);
+        instruction.accounts.push(AccountMeta::new(destination, false));
+
+        let result = mollusk.process_instruction(
+            &instruction,
+            &[
+                (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID)),
+                (destination, AccountSharedData::new(0, 0, &Pubkey::default())),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+        assert_eq!(result.get_account(&destination).unwrap().lamports(), 400_000_000);
+        assert_eq!(result.get_account(&signer).unwrap().lamports(), 0);
+        assert_eq!(result.get_account(&vault).unwrap().lamports(), 600_000_000);
+
+        // Somebody else's vault is still only open to its delegates
+        let stranger = Pubkey::new_unique();
+        let mut instruction = withdraw_instruction(stranger, vault, bump, 1);
+        instruction.accounts.push(AccountMeta::new(destination, false));
+
+        let result = mollusk.process_instruction(
+            &instruction,
+            &[
+                (stranger, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(1_000_000_000, VaultState::LEN, &PROGRAM_ID)),
+                (destination, AccountSharedData::new(0, 0, &Pubkey::default())),
+            ],
+        );
+
+        assert_eq!(
+            result.program_result,
+            ProgramResult::Failure(ProgramError::Custom(VaultError::NotADelegate.code()))
+        );
+    }
+
+    #[test]
+    fn withdraw_keep_rent_exempt() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+        let minimum = mollusk.sysvars.rent.minimum_balance(VaultState::LEN);
+
+        let withdraw = |lamports: u64| {
+            Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[12], &lamports.to_le_bytes()[..], &[bump]].concat(),
+                vec![
+                    AccountMeta::new(signer, true),
+                    AccountMeta::new(vault, false),
+                ],
+            )
+        };
+        let accounts = [
+            (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (vault, AccountSharedData::new(minimum + 100, VaultState::LEN, &PROGRAM_ID)),
+        ];
+
+        let result = mollusk.process_instruction(&withdraw(101), &accounts);
+
+        assert_eq!(
+            result.program_result,
+            ProgramResult::Failure(ProgramError::Custom(VaultError::BelowRentExempt.code()))
+        );
+
+        let result = mollusk.process_instruction(&withdraw(100), &accounts);
+
+        assert!(!result.program_result.is_err());
+        assert_eq!(result.get_account(&vault).unwrap().lamports(), minimum);
+
+        // Vaults without data have no minimum to keep
+        let result = mollusk.process_instruction(
+            &withdraw(1_000_000_000),
+            &[
+                (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID)),
             ],
         );
 
+        assert!(!result.program_result.is_err());
         assert_eq!(result.get_account(&signer).unwrap().lamports(), 1_000_000_000);
+    }
+
+    #[test]
+    fn non_canonical_bump() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) = lower_bump(&signer);
+        let (system_program, system_program_account) = program::keyed_account_for_system_program();
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        // A second vault for the same signer, the program can't tell it apart by the hash
+        let deposit = mollusk.process_instruction(
+            &deposit_instruction(signer, signer, vault, bump, 1_000_000_000),
+            &[
+                (signer, AccountSharedData::new(1_000_000_000, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (system_program, system_program_account),
+            ],
+        );
+        let withdraw = mollusk.process_instruction(
+            &withdraw_instruction(signer, vault, bump, 1_000_000_000),
+            &[
+                (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID)),
+            ],
+        );
+
+        if cfg!(feature = "canonical-bump") {
+            let refused = ProgramResult::Failure(ProgramError::Custom(VaultError::NonCanonicalBump.code()));
+
+            assert_eq!(deposit.program_result, refused);
+            assert_eq!(withdraw.program_result, refused);
+        } else {
+            assert!(!deposit.program_result.is_err());
+            assert!(!withdraw.program_result.is_err());
+        }
+    }
+
+    #[cfg(feature = "optimized")]
+    fn create_stream_instruction(owner: Pubkey, stream: Pubkey, recipient: Pubkey, bump: u8, schedule: Stream) -> Instruction {
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &VaultInstruction::CreateStream { recipient: recipient.to_bytes(), stream: schedule, bump }.to_bytes(),
+            vec![
+                AccountMeta::new(owner, true),
+                AccountMeta::new(stream, false),
+                AccountMeta::new_readonly(solana_sdk::system_program::ID, false),
+            ],
+        )
+    }
+
+    /// The stream from a fresh owner to a fresh recipient, created with 1 SOL vesting over
+    /// 1000 seconds from 1_700_000_000 and a cliff a quarter in.
+    #[cfg(feature = "optimized")]
+    fn created_stream(mollusk: &Mollusk) -> (Pubkey, Pubkey, Pubkey, u8, AccountSharedData) {
+        let owner = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let (stream, bump) = Pubkey::find_program_address(
+            &[Stream::SEED, owner.as_ref(), recipient.as_ref()],
+            &PROGRAM_ID,
+        );
+        let (system_program, system_program_account) = program::keyed_account_for_system_program();
+
+        let schedule = Stream::new(1_000_000_000, 1_700_000_000, 1_700_000_250, 1_700_001_000).unwrap();
+        let minimum = mollusk.sysvars.rent.minimum_balance(Stream::LEN);
+
+        let result = mollusk.process_instruction(
+            &create_stream_instruction(owner, stream, recipient, bump, schedule),
+            &[
+                (owner, AccountSharedData::new(2_000_000_000, 0, &Pubkey::default())),
+                (stream, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (system_program, system_program_account),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+
+        let stream_account = result.get_account(&stream).unwrap().clone();
+        assert_eq!(stream_account.owner(), &PROGRAM_ID);
+        assert_eq!(stream_account.lamports(), minimum + 1_000_000_000);
+        assert_eq!(Stream::load(stream_account.data()), Ok(schedule));
+        assert_eq!(result.get_account(&owner).unwrap().lamports(), 1_000_000_000 - minimum);
+
+        (owner, recipient, stream, bump, stream_account)
+    }
+
+    #[test]
+    #[cfg(feature = "optimized")]
+    fn create_stream_at_a_funded_address() {
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+        let (owner, recipient, stream, bump, stream_account) = created_stream(&mollusk);
+        let (system_program, system_program_account) = program::keyed_account_for_system_program();
+
+        let schedule = Stream::load(stream_account.data()).unwrap();
+        let minimum = mollusk.sysvars.rent.minimum_balance(Stream::LEN);
+        let instruction = create_stream_instruction(owner, stream, recipient, bump, schedule);
+
+        // Someone sent the address lamports before the owner got to it, the owner only tops
+        // it up
+        let result = mollusk.process_instruction(
+            &instruction,
+            &[
+                (owner, AccountSharedData::new(2_000_000_000, 0, &Pubkey::default())),
+                (stream, AccountSharedData::new(1_000, 0, &Pubkey::default())),
+                (system_program, system_program_account.clone()),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+
+        let created = result.get_account(&stream).unwrap();
+        assert_eq!(created.owner(), &PROGRAM_ID);
+        assert_eq!(created.lamports(), minimum + 1_000_000_000);
+        assert_eq!(Stream::load(created.data()), Ok(schedule));
+        assert_eq!(result.get_account(&owner).unwrap().lamports(), 1_000_000_000 - minimum + 1_000);
+
+        // Not over a stream that's still running
+        let result = mollusk.process_instruction(
+            &instruction,
+            &[
+                (owner, AccountSharedData::new(2_000_000_000, 0, &Pubkey::default())),
+                (stream, stream_account),
+                (system_program, system_program_account),
+            ],
+        );
+
+        assert!(result.program_result.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "optimized")]
+    fn stream_withdraw() {
+        let mut mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+        let (owner, recipient, stream, bump, mut stream_account) = created_stream(&mollusk);
+        let minimum = mollusk.sysvars.rent.minimum_balance(Stream::LEN);
+
+        let instruction = Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &VaultInstruction::WithdrawStream { owner: owner.to_bytes(), bump }.to_bytes(),
+            vec![AccountMeta::new(recipient, true), AccountMeta::new(stream, false)],
+        );
+        let mut withdraw = |unix_timestamp: i64, stream_account: AccountSharedData| {
+            mollusk.sysvars.clock.unix_timestamp = unix_timestamp;
+
+            let result = mollusk.process_instruction(
+                &instruction,
+                &[(recipient, AccountSharedData::new(0, 0, &Pubkey::default())), (stream, stream_account)],
+            );
+            assert!(!result.program_result.is_err());
+
+            (
+                result.get_account(&recipient).unwrap().lamports(),
+                result.get_account(&stream).unwrap().clone(),
+            )
+        };
+
+        // Before the cliff nothing has vested yet
+        let (received, account) = withdraw(1_700_000_249, stream_account);
+        assert_eq!(received, 0);
+        stream_account = account;
+
+        let (received, account) = withdraw(1_700_000_500, stream_account);
+        assert_eq!(received, 500_000_000);
+        stream_account = account;
+
+        // Only what vested since
+        let (received, account) = withdraw(1_700_000_750, stream_account);
+        assert_eq!(received, 250_000_000);
+        stream_account = account;
+
+        let (received, account) = withdraw(1_800_000_000, stream_account);
+        assert_eq!(received, 250_000_000);
+        assert_eq!(account.lamports(), minimum);
+        assert_eq!(Stream::load(account.data()).unwrap().withdrawn, 1_000_000_000);
+
+        // Nobody else can take the recipient's share
+        let thief = Pubkey::new_unique();
+        let result = mollusk.process_instruction(
+            &Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &VaultInstruction::WithdrawStream { owner: owner.to_bytes(), bump }.to_bytes(),
+                vec![AccountMeta::new(thief, true), AccountMeta::new(stream, false)],
+            ),
+            &[(thief, AccountSharedData::new(0, 0, &Pubkey::default())), (stream, account)],
+        );
+        assert_eq!(
+            result.program_result,
+            ProgramResult::Failure(ProgramError::Custom(VaultError::InvalidPda.code()))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "optimized")]
+    fn cancel_stream() {
+        let mut mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+        let (owner, recipient, stream, bump, stream_account) = created_stream(&mollusk);
+        let minimum = mollusk.sysvars.rent.minimum_balance(Stream::LEN);
+
+        // 40% in, of which the recipient already took 10%
+        let mut schedule = Stream::load(stream_account.data()).unwrap();
+        schedule.withdrawn = 100_000_000;
+        let mut data = [0; Stream::LEN];
+        schedule.store(&mut data);
+
+        let mut stream_account = AccountSharedData::new(minimum + 900_000_000, Stream::LEN, &PROGRAM_ID);
+        stream_account.set_data_from_slice(&data);
+
+        mollusk.sysvars.clock.unix_timestamp = 1_700_000_400;
+
+        let result = mollusk.process_instruction(
+            &Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &VaultInstruction::CancelStream { bump }.to_bytes(),
+                vec![
+                    AccountMeta::new(owner, true),
+                    AccountMeta::new(stream, false),
+                    AccountMeta::new(recipient, false),
+                ],
+            ),
+            &[
+                (owner, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (stream, stream_account),
+                (recipient, AccountSharedData::new(0, 0, &Pubkey::default())),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+        assert_eq!(result.get_account(&recipient).unwrap().lamports(), 300_000_000);
+        assert_eq!(result.get_account(&owner).unwrap().lamports(), minimum + 600_000_000);
+
+        let closed = result.get_account(&stream).unwrap();
+        assert_eq!(closed.lamports(), 0);
+        assert!(closed.data().is_empty());
+        assert_eq!(closed.owner(), &Pubkey::default());
+    }
+
+    #[test]
+    fn deposit_then_withdraw() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+        let (system_program, system_program_account) = program::keyed_account_for_system_program();
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        // The vault doesn't exist yet, so the deposit has to hand it over to the program
+        let result = mollusk.process_instruction(
+            &deposit_instruction(signer, signer, vault, bump, 1_000_000_000),
+            &[
+                (
+                    signer,
+                    AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
+                ),
+                (vault, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (system_program, system_program_account),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+
+        let vault_account = result.get_account(&vault).unwrap().clone();
+        assert_eq!(vault_account.lamports(), 1_000_000_000);
+        assert_eq!(vault_account.owner(), &PROGRAM_ID);
+        assert_eq!(result.get_account(&signer).unwrap().lamports(), 0);
+
+        let result = mollusk.process_instruction(
+            &withdraw_instruction(signer, vault, bump, 400_000_000),
+            &[
+                (signer, result.get_account(&signer).unwrap().clone()),
+                (vault, vault_account),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+
+        assert_eq!(result.get_account(&signer).unwrap().lamports(), 400_000_000);
+        assert_eq!(result.get_account(&vault).unwrap().lamports(), 600_000_000);
+    }
+
+    #[test]
+    fn deposit_to_someone_elses_vault() {
+        let payer = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[owner.as_ref()], &PROGRAM_ID).unwrap();
+        let (system_program, system_program_account) = program::keyed_account_for_system_program();
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let result = mollusk.process_instruction(
+            &deposit_instruction(payer, owner, vault, bump, 500_000_000),
+            &[
+                (
+                    payer,
+                    AccountSharedData::new(1_000_000_000, 0, &Pubkey::default()),
+                ),
+                (vault, AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID)),
+                (system_program, system_program_account),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+
+        assert_eq!(result.get_account(&payer).unwrap().lamports(), 500_000_000);
+        assert_eq!(result.get_account(&vault).unwrap().lamports(), 1_500_000_000);
+    }
+
+    #[test]
+    fn close() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let result = mollusk.process_instruction(
+            &Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[2, bump],
+                vec![
+                    AccountMeta::new(signer, true),
+                    AccountMeta::new(vault, false),
+                ],
+            ),
+            &[
+                (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(1_234_567_890, 0, &PROGRAM_ID)),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+
+        assert_eq!(result.get_account(&signer).unwrap().lamports(), 1_234_567_890);
         assert_eq!(result.get_account(&vault).unwrap().lamports(), 0);
+        assert_eq!(result.get_account(&vault).unwrap().owner(), &Pubkey::default());
+    }
+
+    #[test]
+    fn close_vault_with_data() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+
+        // An expired lock, a limit and a delegate
+        let mut data = vec![0; VaultState::LEN + Delegate::LEN];
+        VaultState { unlock_timestamp: -1, limit: 2_000_000_000, ..Default::default() }.store(&mut data);
+        Delegate { key: [7; 32], allowance: 1, expires_at: 0 }.store(&mut data[VaultState::LEN..]);
+
+        let mut vault_account = AccountSharedData::new(1_234_567_890, data.len(), &PROGRAM_ID);
+        vault_account.set_data_from_slice(&data);
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let result = mollusk.process_instruction(
+            &Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[2, bump],
+                vec![
+                    AccountMeta::new(signer, true),
+                    AccountMeta::new(vault, false),
+                ],
+            ),
+            &[
+                (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (vault, vault_account),
+            ],
+        );
 
         assert!(!result.program_result.is_err());
+
+        // Gone, down to the owner
+        let closed = result.get_account(&vault).unwrap();
+        assert_eq!(closed.lamports(), 0);
+        assert!(closed.data().is_empty());
+        assert_eq!(closed.owner(), &Pubkey::default());
+        assert_eq!(result.get_account(&signer).unwrap().lamports(), 1_234_567_890);
+    }
+
+    #[test]
+    fn time_locked_withdraw() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+
+        let mut mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+        mollusk.sysvars.clock.unix_timestamp = 1_700_000_000;
+
+        let result = mollusk.process_instruction(
+            &set_lock_instruction(signer, vault, bump, 1_700_086_400, 0),
+            &[
+                (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID)),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+
+        let vault_account = result.get_account(&vault).unwrap().clone();
+        assert_eq!(
+            VaultState::load(vault_account.data()),
+            Ok(VaultState { unlock_timestamp: 1_700_086_400, unlock_slot: 0, ..Default::default() })
+        );
+
+        let accounts = [
+            (signer, result.get_account(&signer).unwrap().clone()),
+            (vault, vault_account),
+        ];
+        let withdraw = withdraw_instruction(signer, vault, bump, 400_000_000);
+
+        // A second short of the unlock
+        mollusk.sysvars.clock.unix_timestamp = 1_700_086_399;
+        assert!(mollusk.process_instruction(&withdraw, &accounts).program_result.is_err());
+
+        mollusk.sysvars.clock.unix_timestamp = 1_700_086_400;
+        let result = mollusk.process_instruction(&withdraw, &accounts);
+
+        assert!(!result.program_result.is_err());
+        assert_eq!(result.get_account(&signer).unwrap().lamports(), 400_000_000);
+        assert_eq!(result.get_account(&vault).unwrap().lamports(), 600_000_000);
+    }
+
+    #[test]
+    fn slot_locked_close() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+
+        let mut mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let result = mollusk.process_instruction(
+            &set_lock_instruction(signer, vault, bump, 0, 1_000),
+            &[
+                (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID)),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+
+        let accounts = [
+            (signer, result.get_account(&signer).unwrap().clone()),
+            (vault, result.get_account(&vault).unwrap().clone()),
+        ];
+        let close = Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[2, bump],
+            vec![
+                AccountMeta::new(signer, true),
+                AccountMeta::new(vault, false),
+            ],
+        );
+
+        // Closing drains the vault, so it's held to the lock just like a withdraw
+        mollusk.warp_to_slot(999);
+        assert!(mollusk.process_instruction(&close, &accounts).program_result.is_err());
+
+        mollusk.warp_to_slot(1_000);
+        let result = mollusk.process_instruction(&close, &accounts);
+
+        assert!(!result.program_result.is_err());
+        assert_eq!(result.get_account(&signer).unwrap().lamports(), 1_000_000_000);
+        assert_eq!(result.get_account(&vault).unwrap().lamports(), 0);
+    }
+
+    #[test]
+    fn lock_can_only_be_extended() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let mut data = vec![0; VaultState::LEN];
+        VaultState { unlock_timestamp: 1_000, unlock_slot: 1_000, ..Default::default() }.store(&mut data);
+        let mut vault_account = AccountSharedData::new(1_000_000_000, VaultState::LEN, &PROGRAM_ID);
+        vault_account.set_data_from_slice(&data);
+
+        let accounts = [
+            (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (vault, vault_account),
+        ];
+
+        for (unlock_timestamp, unlock_slot) in [(999, 1_000), (1_000, 999), (0, 0)] {
+            let result = mollusk.process_instruction(
+                &set_lock_instruction(signer, vault, bump, unlock_timestamp, unlock_slot),
+                &accounts,
+            );
+
+            assert!(result.program_result.is_err());
+        }
+
+        let result = mollusk.process_instruction(
+            &set_lock_instruction(signer, vault, bump, 2_000, 1_000),
+            &accounts,
+        );
+
+        assert!(!result.program_result.is_err());
+        assert_eq!(
+            VaultState::load(result.get_account(&vault).unwrap().data()),
+            Ok(VaultState { unlock_timestamp: 2_000, unlock_slot: 1_000, ..Default::default() })
+        );
+    }
+
+    #[test]
+    fn rate_limited_withdraw() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+
+        let mut mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let result = mollusk.process_instruction(
+            &Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[9], &300_000_000u64.to_le_bytes()[..], &[bump]].concat(),
+                vec![
+                    AccountMeta::new(signer, true),
+                    AccountMeta::new(vault, false),
+                ],
+            ),
+            &[
+                (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID)),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+
+        let mut accounts = [
+            (signer, result.get_account(&signer).unwrap().clone()),
+            (vault, result.get_account(&vault).unwrap().clone()),
+        ];
+        let mut withdraw = |lamports: u64| {
+            let result = mollusk.process_instruction(
+                &withdraw_instruction(signer, vault, bump, lamports),
+                &accounts,
+            );
+            if !result.program_result.is_err() {
+                accounts = [
+                    (signer, result.get_account(&signer).unwrap().clone()),
+                    (vault, result.get_account(&vault).unwrap().clone()),
+                ];
+            }
+            result.program_result
+        };
+
+        assert_eq!(withdraw(200_000_000), ProgramResult::Success);
+        assert_eq!(
+            withdraw(100_000_001),
+            ProgramResult::Failure(ProgramError::Custom(VaultError::RateLimitExceeded.code()))
+        );
+        assert_eq!(withdraw(100_000_000), ProgramResult::Success);
+        assert_eq!(
+            withdraw(1),
+            ProgramResult::Failure(ProgramError::Custom(VaultError::RateLimitExceeded.code()))
+        );
+
+        // A new epoch, a new allowance
+        mollusk.sysvars.clock.epoch += 1;
+        let result = mollusk.process_instruction(
+            &withdraw_instruction(signer, vault, bump, 300_000_000),
+            &accounts,
+        );
+
+        assert!(!result.program_result.is_err());
+        assert_eq!(result.get_account(&signer).unwrap().lamports(), 600_000_000);
+    }
+
+    #[test]
+    fn delegated_withdraw() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+        let delegate = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let result = mollusk.process_instruction(
+            &set_delegate_instruction(signer, vault, bump, delegate, 300_000_000, 0),
+            &[
+                (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID)),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+
+        let vault_account = result.get_account(&vault).unwrap().clone();
+        assert_eq!(vault_account.data().len(), VaultState::LEN + Delegate::LEN);
+
+        let mut accounts = [
+            (delegate, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (vault, vault_account),
+            (destination, AccountSharedData::new(0, 0, &Pubkey::default())),
+        ];
+        let result = mollusk.process_instruction(
+            &withdraw_delegated_instruction(delegate, vault, destination, 200_000_000),
+            &accounts,
+        );
+
+        assert!(!result.program_result.is_err());
+        assert_eq!(result.get_account(&destination).unwrap().lamports(), 200_000_000);
+        assert_eq!(result.get_account(&vault).unwrap().lamports(), 800_000_000);
+
+        // What's left of the allowance
+        accounts[1].1 = result.get_account(&vault).unwrap().clone();
+        let result = mollusk.process_instruction(
+            &withdraw_delegated_instruction(delegate, vault, destination, 100_000_001),
+            &accounts,
+        );
+
+        assert!(result.program_result.is_err());
+
+        // Nobody else can spend it
+        let stranger = Pubkey::new_unique();
+        let result = mollusk.process_instruction(
+            &withdraw_delegated_instruction(stranger, vault, destination, 1),
+            &[
+                (stranger, AccountSharedData::new(0, 0, &Pubkey::default())),
+                accounts[1].clone(),
+                accounts[2].clone(),
+            ],
+        );
+
+        assert!(result.program_result.is_err());
+
+        // Once revoked, the delegate is gone along with its allowance
+        let result = mollusk.process_instruction(
+            &Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[11], delegate.as_ref(), &[bump]].concat(),
+                vec![
+                    AccountMeta::new(signer, true),
+                    AccountMeta::new(vault, false),
+                ],
+            ),
+            &[
+                (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                accounts[1].clone(),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+
+        accounts[1].1 = result.get_account(&vault).unwrap().clone();
+        assert_eq!(accounts[1].1.data().len(), VaultState::LEN);
+
+        let result = mollusk.process_instruction(
+            &withdraw_delegated_instruction(delegate, vault, destination, 1),
+            &accounts,
+        );
+
+        assert!(result.program_result.is_err());
+    }
+
+    #[test]
+    fn expired_delegate() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+        let delegate = Pubkey::new_unique();
+
+        let mut mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let result = mollusk.process_instruction(
+            &set_delegate_instruction(signer, vault, bump, delegate, 300_000_000, 100),
+            &[
+                (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (vault, AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID)),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+
+        // The delegate withdraws to itself
+        let accounts = [
+            (delegate, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (vault, result.get_account(&vault).unwrap().clone()),
+        ];
+        let instruction = withdraw_delegated_instruction(delegate, vault, delegate, 1);
+
+        mollusk.sysvars.clock.unix_timestamp = 99;
+        assert!(!mollusk.process_instruction(&instruction, &accounts).program_result.is_err());
+
+        mollusk.sysvars.clock.unix_timestamp = 100;
+        assert!(mollusk.process_instruction(&instruction, &accounts).program_result.is_err());
+    }
+
+    #[test]
+    fn create_multisig() {
+        let config = Pubkey::new_unique();
+        let members = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let create = |config_account: AccountSharedData| {
+            mollusk.process_instruction(
+                &Instruction::new_with_bytes(
+                    PROGRAM_ID,
+                    &multisig_data(4, 2, &members),
+                    vec![AccountMeta::new(config, true)],
+                ),
+                &[(config, config_account)],
+            )
+        };
+
+        let result = create(AccountSharedData::new(1_000_000_000, MultisigConfig::LEN, &PROGRAM_ID));
+
+        assert!(!result.program_result.is_err());
+
+        let created = result.get_account(&config).unwrap().clone();
+        let multisig = MultisigConfig::load(created.data()).unwrap();
+        assert_eq!(multisig.threshold(), 2);
+        assert_eq!(multisig.members(), members.map(|member| member.to_bytes()));
+
+        // A config can't be created twice
+        assert!(create(created).program_result.is_err());
+    }
+
+    #[test]
+    fn multisig_withdraw() {
+        let members = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let (config, config_account, vault, bump) = multisig(&members);
+        let destination = Pubkey::new_unique();
+        let outsider = Pubkey::new_unique();
+        let (system_program, system_program_account) = program::keyed_account_for_system_program();
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let mut accounts = vec![
+            (config, config_account),
+            (vault, AccountSharedData::new(1_000_000_000, 0, &Pubkey::default())),
+            (destination, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (system_program, system_program_account),
+        ];
+        for signer in members.iter().chain([&outsider]) {
+            accounts.push((*signer, AccountSharedData::new(0, 0, &Pubkey::default())));
+        }
+
+        // One member, one member twice, or one member and an outsider, are all short of 2
+        for signers in [
+            &[members[0]][..],
+            &[members[0], members[0]],
+            &[members[1], outsider],
+        ] {
+            let result = mollusk.process_instruction(
+                &withdraw_multisig_instruction(config, vault, destination, bump, 400_000_000, signers),
+                &accounts,
+            );
+
+            assert!(result.program_result.is_err());
+        }
+
+        let result = mollusk.process_instruction(
+            &withdraw_multisig_instruction(config, vault, destination, bump, 400_000_000, &[members[2], members[0]]),
+            &accounts,
+        );
+
+        assert!(!result.program_result.is_err());
+        assert_eq!(result.get_account(&destination).unwrap().lamports(), 400_000_000);
+        assert_eq!(result.get_account(&vault).unwrap().lamports(), 600_000_000);
+    }
+
+    #[test]
+    fn rotate_members() {
+        let members = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let (config, config_account, vault, bump) = multisig(&members);
+        let rotated = [members[0], Pubkey::new_unique()];
+        let destination = Pubkey::new_unique();
+        let (system_program, system_program_account) = program::keyed_account_for_system_program();
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let rotate = |signers: &[Pubkey], config_account: AccountSharedData| {
+            let mut metas = vec![AccountMeta::new(config, false)];
+            let mut accounts = vec![(config, config_account)];
+            for signer in signers {
+                metas.push(AccountMeta::new_readonly(*signer, true));
+                accounts.push((*signer, AccountSharedData::new(0, 0, &Pubkey::default())));
+            }
+
+            mollusk.process_instruction(
+                &Instruction::new_with_bytes(PROGRAM_ID, &multisig_data(5, 1, &rotated), metas),
+                &accounts,
+            )
+        };
+
+        // Rotating needs the current threshold too
+        assert!(rotate(&[members[0]], config_account.clone()).program_result.is_err());
+
+        let result = rotate(&[members[1], members[2]], config_account);
+
+        assert!(!result.program_result.is_err());
+
+        let config_account = result.get_account(&config).unwrap().clone();
+        let multisig = MultisigConfig::load(config_account.data()).unwrap();
+        assert_eq!(multisig.threshold(), 1);
+        assert_eq!(multisig.members(), rotated.map(|member| member.to_bytes()));
+
+        let withdraw = |signer: Pubkey| {
+            mollusk.process_instruction(
+                &withdraw_multisig_instruction(config, vault, destination, bump, 1_000_000_000, &[signer]),
+                &[
+                    (config, config_account.clone()),
+                    (vault, AccountSharedData::new(1_000_000_000, 0, &Pubkey::default())),
+                    (destination, AccountSharedData::new(0, 0, &Pubkey::default())),
+                    (system_program, system_program_account.clone()),
+                    (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                ],
+            )
+        };
+
+        // Same vault, new members
+        assert!(withdraw(members[1]).program_result.is_err());
+        assert!(!withdraw(rotated[1]).program_result.is_err());
+    }
+
+    #[test]
+    fn token_deposit_then_withdraw() {
+        let payer = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[owner.as_ref()], &PROGRAM_ID).unwrap();
+        let mint = Pubkey::new_unique();
+        let payer_ta = Pubkey::new_unique();
+        let vault_ta = Pubkey::new_unique();
+        let owner_ta = Pubkey::new_unique();
+
+        let mut mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+        mollusk_token::token::add_program(&mut mollusk);
+        let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+
+        let result = mollusk.process_instruction(
+            &Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[7], &1_000_000u64.to_le_bytes()[..], &[bump], owner.as_ref()].concat(),
+                vec![
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new(payer_ta, false),
+                    AccountMeta::new(vault_ta, false),
+                    AccountMeta::new_readonly(token_program, false),
+                ],
+            ),
+            &[
+                (payer, AccountSharedData::new(1_000_000_000, 0, &Pubkey::default())),
+                (payer_ta, token_account(&mollusk, mint, payer, 1_000_000)),
+                (vault_ta, token_account(&mollusk, mint, vault, 0)),
+                (token_program, token_program_account.clone()),
+            ],
+        );
+
+        assert!(!result.program_result.is_err());
+        assert_eq!(token_amount(result.get_account(&payer_ta).unwrap()), 0);
+        assert_eq!(token_amount(result.get_account(&vault_ta).unwrap()), 1_000_000);
+
+        // The vault PDA never had to exist, it only signs for its token account
+        let withdraw = |signer: Pubkey| {
+            mollusk.process_instruction(
+                &Instruction::new_with_bytes(
+                    PROGRAM_ID,
+                    &[&[8], &400_000u64.to_le_bytes()[..], &[bump]].concat(),
+                    vec![
+                        AccountMeta::new(signer, true),
+                        AccountMeta::new(vault, false),
+                        AccountMeta::new(vault_ta, false),
+                        AccountMeta::new(owner_ta, false),
+                        AccountMeta::new_readonly(token_program, false),
+                    ],
+                ),
+                &[
+                    (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                    (vault, AccountSharedData::new(0, 0, &Pubkey::default())),
+                    (vault_ta, result.get_account(&vault_ta).unwrap().clone()),
+                    (owner_ta, token_account(&mollusk, mint, owner, 0)),
+                    (token_program, token_program_account.clone()),
+                ],
+            )
+        };
+
+        // Only the owner can take them out
+        assert!(withdraw(payer).program_result.is_err());
+
+        let result = withdraw(owner);
+
+        assert!(!result.program_result.is_err());
+        assert_eq!(token_amount(result.get_account(&vault_ta).unwrap()), 600_000);
+        assert_eq!(token_amount(result.get_account(&owner_ta).unwrap()), 400_000);
+    }
+
+    #[test]
+    fn token_deposit_into_someone_elses_token_account() {
+        let payer = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let (_, bump) =
+            Pubkey::try_find_program_address(&[owner.as_ref()], &PROGRAM_ID).unwrap();
+        let mint = Pubkey::new_unique();
+        let payer_ta = Pubkey::new_unique();
+        let other_ta = Pubkey::new_unique();
+
+        let mut mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+        mollusk_token::token::add_program(&mut mollusk);
+        let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+
+        // A token account the vault PDA has no authority over isn't the vault
+        let result = mollusk.process_instruction(
+            &Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[7], &1_000_000u64.to_le_bytes()[..], &[bump], owner.as_ref()].concat(),
+                vec![
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new(payer_ta, false),
+                    AccountMeta::new(other_ta, false),
+                    AccountMeta::new_readonly(token_program, false),
+                ],
+            ),
+            &[
+                (payer, AccountSharedData::new(1_000_000_000, 0, &Pubkey::default())),
+                (payer_ta, token_account(&mollusk, mint, payer, 1_000_000)),
+                (other_ta, token_account(&mollusk, mint, owner, 0)),
+                (token_program, token_program_account),
+            ],
+        );
+
+        assert!(result.program_result.is_err());
+    }
+
+    #[test]
+    fn time_locked_token_withdraw() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+        let mint = Pubkey::new_unique();
+        let vault_ta = Pubkey::new_unique();
+        let signer_ta = Pubkey::new_unique();
+
+        let mut mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+        mollusk_token::token::add_program(&mut mollusk);
+        let (token_program, token_program_account) = mollusk_token::token::keyed_account();
+
+        let mut data = vec![0; VaultState::LEN];
+        VaultState { unlock_timestamp: 1_000, unlock_slot: 0, ..Default::default() }.store(&mut data);
+        let mut vault_account = AccountSharedData::new(1_000_000_000, VaultState::LEN, &PROGRAM_ID);
+        vault_account.set_data_from_slice(&data);
+
+        let instruction = Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &[&[8], &1_000_000u64.to_le_bytes()[..], &[bump]].concat(),
+            vec![
+                AccountMeta::new(signer, true),
+                AccountMeta::new(vault, false),
+                AccountMeta::new(vault_ta, false),
+                AccountMeta::new(signer_ta, false),
+                AccountMeta::new_readonly(token_program, false),
+            ],
+        );
+        let accounts = [
+            (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+            (vault, vault_account),
+            (vault_ta, token_account(&mollusk, mint, vault, 1_000_000)),
+            (signer_ta, token_account(&mollusk, mint, signer, 0)),
+            (token_program, token_program_account),
+        ];
+
+        assert!(mollusk.process_instruction(&instruction, &accounts).program_result.is_err());
+
+        mollusk.sysvars.clock.unix_timestamp = 1_000;
+        let result = mollusk.process_instruction(&instruction, &accounts);
+
+        assert!(!result.program_result.is_err());
+        assert_eq!(token_amount(result.get_account(&signer_ta).unwrap()), 1_000_000);
+    }
+
+    #[test]
+    fn malformed_instruction_data() {
+        let signer = Pubkey::new_unique();
+        let (vault, bump) =
+            Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+
+        let mollusk = Mollusk::new(&PROGRAM_ID, "target/deploy/native_vault");
+
+        let withdraw = [&[0], &1u64.to_le_bytes()[..], &[bump]].concat();
+
+        for data in [
+            &[][..],                           // Empty
+            &[0xff],                           // Unknown discriminator
+            &withdraw[..withdraw.len() - 1],   // Short
+            &[&withdraw[..], &[0]].concat(),   // Trailing bytes
+        ] {
+            let result = mollusk.process_instruction(
+                &Instruction::new_with_bytes(
+                    PROGRAM_ID,
+                    data,
+                    vec![
+                        AccountMeta::new(signer, true),
+                        AccountMeta::new(vault, false),
+                    ],
+                ),
+                &[
+                    (signer, AccountSharedData::new(0, 0, &Pubkey::default())),
+                    (vault, AccountSharedData::new(1, 0, &PROGRAM_ID)),
+                ],
+            );
+
+            assert_eq!(
+                result.program_result,
+                ProgramResult::Failure(ProgramError::InvalidInstructionData)
+            );
+        }
+    }
+
+    #[test]
+    fn instruction_wire_format() {
+        let owner = Pubkey::new_unique();
+
+        assert_eq!(
+            VaultInstruction::try_from(&[&[0], &7u64.to_le_bytes()[..], &[254]].concat()[..]),
+            Ok(VaultInstruction::Withdraw { lamports: 7, bump: 254 })
+        );
+        assert_eq!(
+            VaultInstruction::try_from(&[&[1], &7u64.to_le_bytes()[..], &[254], owner.as_ref()].concat()[..]),
+            Ok(VaultInstruction::Deposit { lamports: 7, bump: 254, owner: owner.to_bytes() })
+        );
+        assert_eq!(
+            VaultInstruction::try_from(&[2, 254][..]),
+            Ok(VaultInstruction::Close { bump: 254 })
+        );
+        assert_eq!(
+            VaultInstruction::try_from(&[&[3], &(-1i64).to_le_bytes()[..], &7u64.to_le_bytes(), &[254]].concat()[..]),
+            Ok(VaultInstruction::SetLock { unlock_timestamp: -1, unlock_slot: 7, bump: 254 })
+        );
+        assert_eq!(
+            VaultInstruction::try_from(&[&[6], &7u64.to_le_bytes()[..], &[254]].concat()[..]),
+            Ok(VaultInstruction::WithdrawMultisig { lamports: 7, bump: 254 })
+        );
+        assert_eq!(
+            VaultInstruction::try_from(&multisig_data(4, 1, &[owner])[..]),
+            Ok(VaultInstruction::CreateMultisig { config: MultisigConfig::new(1, &[owner.to_bytes()]).unwrap() })
+        );
+        assert_eq!(
+            VaultInstruction::try_from(&multisig_data(5, 1, &[owner])[..]),
+            Ok(VaultInstruction::RotateMembers { config: MultisigConfig::new(1, &[owner.to_bytes()]).unwrap() })
+        );
+        assert_eq!(
+            VaultInstruction::try_from(&[&[7], &7u64.to_le_bytes()[..], &[254], owner.as_ref()].concat()[..]),
+            Ok(VaultInstruction::DepositToken { amount: 7, bump: 254, owner: owner.to_bytes() })
+        );
+        assert_eq!(
+            VaultInstruction::try_from(&[&[8], &7u64.to_le_bytes()[..], &[254]].concat()[..]),
+            Ok(VaultInstruction::WithdrawToken { amount: 7, bump: 254 })
+        );
+        assert_eq!(
+            VaultInstruction::try_from(&[&[10], owner.as_ref(), &7u64.to_le_bytes(), &(-1i64).to_le_bytes(), &[254]].concat()[..]),
+            Ok(VaultInstruction::SetDelegate {
+                delegate: Delegate { key: owner.to_bytes(), allowance: 7, expires_at: -1 },
+                bump: 254,
+            })
+        );
+        assert_eq!(
+            VaultInstruction::try_from(&[&[11], owner.as_ref(), &[254]].concat()[..]),
+            Ok(VaultInstruction::RevokeDelegate { delegate: owner.to_bytes(), bump: 254 })
+        );
+        assert_eq!(
+            VaultInstruction::try_from(&[&[12], &7u64.to_le_bytes()[..], &[254]].concat()[..]),
+            Ok(VaultInstruction::WithdrawKeepRentExempt { lamports: 7, bump: 254 })
+        );
+        assert_eq!(VaultInstruction::try_from(&[2][..]), Err(InvalidInstructionData));
+
+        // Encoding is the inverse of decoding, for every variant
+        let config = MultisigConfig::new(1, &[owner.to_bytes(), [7; 32]]).unwrap();
+        for instruction in [
+            VaultInstruction::Withdraw { lamports: 7, bump: 254 },
+            VaultInstruction::Deposit { lamports: 7, bump: 254, owner: owner.to_bytes() },
+            VaultInstruction::Close { bump: 254 },
+            VaultInstruction::SetLock { unlock_timestamp: -1, unlock_slot: 7, bump: 254 },
+            VaultInstruction::CreateMultisig { config },
+            VaultInstruction::RotateMembers { config },
+            VaultInstruction::WithdrawMultisig { lamports: 7, bump: 254 },
+            VaultInstruction::DepositToken { amount: 7, bump: 254, owner: owner.to_bytes() },
+            VaultInstruction::WithdrawToken { amount: 7, bump: 254 },
+            VaultInstruction::SetRateLimit { limit: 7, bump: 254 },
+            VaultInstruction::SetDelegate {
+                delegate: Delegate { key: owner.to_bytes(), allowance: 7, expires_at: -1 },
+                bump: 254,
+            },
+            VaultInstruction::RevokeDelegate { delegate: owner.to_bytes(), bump: 254 },
+            VaultInstruction::WithdrawKeepRentExempt { lamports: 7, bump: 254 },
+            VaultInstruction::CreateStream {
+                recipient: owner.to_bytes(),
+                stream: Stream::new(7, -1, 0, 9).unwrap(),
+                bump: 254,
+            },
+            VaultInstruction::WithdrawStream { owner: owner.to_bytes(), bump: 254 },
+            VaultInstruction::CancelStream { bump: 254 },
+        ] {
+            assert_eq!(VaultInstruction::try_from(&instruction.to_bytes()[..]), Ok(instruction));
+            assert!(VaultInstruction::max_accounts(instruction.to_bytes()[0]).is_some());
+        }
+        assert_eq!(VaultInstruction::max_accounts(16), None);
+        assert_eq!(
+            VaultInstruction::Withdraw { lamports: 7, bump: 254 }.to_bytes(),
+            [&[0], &7u64.to_le_bytes()[..], &[254]].concat()
+        );
+
+        // Schedules that could never pay anything out
+        let stream = |amount: u64, start: i64, cliff: i64, end: i64| {
+            [&[13], owner.as_ref(), &amount.to_le_bytes(), &start.to_le_bytes(), &cliff.to_le_bytes(), &end.to_le_bytes(), &[254]]
+                .concat()
+        };
+        assert!(VaultInstruction::try_from(&stream(7, 1, 0, 9)[..]).is_ok());
+        for data in [stream(0, 1, 0, 9), stream(7, 9, 0, 9), stream(7, 9, 0, 1), stream(7, 1, -1, 9), stream(7, 1, 10, 9)] {
+            assert_eq!(VaultInstruction::try_from(&data[..]), Err(InvalidInstructionData));
+        }
+
+        // Member sets that don't add up, or could never approve anything
+        for data in [
+            &[4, 1, 2][..],
+            &multisig_data(4, 1, &[owner])[..33],
+            &multisig_data(4, 0, &[owner]),
+            &multisig_data(4, 2, &[owner]),
+            &multisig_data(4, 1, &[owner, owner]),
+        ] {
+            assert_eq!(VaultInstruction::try_from(data), Err(InvalidInstructionData));
+        }
+    }
+
+    #[test]
+    fn multisig_config_layout() {
+        let members: Vec<[u8; 32]> = (1..=MultisigConfig::MAX_MEMBERS as u8).map(|i| [i; 32]).collect();
+
+        assert_eq!(MultisigConfig::new(1, &[]), Err(InvalidMultisig));
+        assert_eq!(MultisigConfig::new(0, &members[..1]), Err(InvalidMultisig));
+        assert_eq!(MultisigConfig::new(3, &members[..2]), Err(InvalidMultisig));
+        assert_eq!(MultisigConfig::new(1, &[members[0], members[0]]), Err(InvalidMultisig));
+        assert_eq!(MultisigConfig::new(1, &[members.clone(), vec![[0xff; 32]]].concat()), Err(InvalidMultisig));
+
+        let config = MultisigConfig::new(3, &members).unwrap();
+        let mut data = [0; MultisigConfig::LEN];
+        assert!(MultisigConfig::is_uncreated(&data));
+        config.store(&mut data);
+
+        assert!(!MultisigConfig::is_uncreated(&data));
+        assert_eq!(data[..2], [3, MultisigConfig::MAX_MEMBERS as u8]);
+        assert_eq!(MultisigConfig::load(&data), Ok(config));
+        assert_eq!(MultisigConfig::load(&data[1..]), Err(InvalidVaultData));
+        assert_eq!(MultisigConfig::load(&[0; MultisigConfig::LEN]), Err(InvalidVaultData));
+
+        assert!(config.is_approved_by(&members[7..]));
+        assert!(!config.is_approved_by([members[0], members[0], members[1], [0xff; 32]]));
+    }
+
+    #[test]
+    fn stream_schedule() {
+        assert_eq!(Stream::new(0, 0, 0, 100), Err(InvalidStream));
+        assert_eq!(Stream::new(1_000, 100, 0, 100), Err(InvalidStream));
+        assert_eq!(Stream::new(1_000, 0, 101, 100), Err(InvalidStream));
+
+        let stream = Stream::new(1_000, 0, 0, 100).unwrap();
+        assert_eq!(stream.vested(-1), 0);
+        assert_eq!(stream.vested(0), 0);
+        assert_eq!(stream.vested(33), 330);
+        assert_eq!(stream.vested(100), 1_000);
+        assert_eq!(stream.vested(i64::MAX), 1_000);
+
+        // Nothing before the cliff, then everything since the start
+        let mut stream = Stream::new(1_000, 0, 25, 100).unwrap();
+        assert_eq!(stream.vested(24), 0);
+        assert_eq!(stream.vested(25), 250);
+
+        stream.withdrawn = 250;
+        assert_eq!(stream.withdrawable(25), 0);
+        assert_eq!(stream.withdrawable(50), 250);
+
+        // No overflow, however far apart start and end are
+        let stream = Stream::new(u64::MAX, i64::MIN, 0, i64::MAX).unwrap();
+        assert_eq!(stream.vested(0), 1 << 63);
+
+        let mut data = [0; Stream::LEN];
+        stream.store(&mut data);
+        assert_eq!(Stream::load(&data), Ok(stream));
+        assert_eq!(Stream::load(&data[1..]), Err(InvalidVaultData));
+    }
+
+    #[test]
+    fn vault_state_layout() {
+        // Plain lamport vaults are unlocked
+        assert_eq!(VaultState::load(&[]), Ok(VaultState::default()));
+        assert_eq!(VaultState::load(&[0; 8]), Err(InvalidVaultData));
+
+        let state = VaultState { unlock_timestamp: -1, unlock_slot: 7, ..Default::default() };
+        let mut data = [0; VaultState::LOCK_LEN];
+        state.store(&mut data);
+
+        // Vaults that were only ever locked stay valid
+        assert_eq!(data, [&(-1i64).to_le_bytes()[..], &7u64.to_le_bytes()].concat()[..]);
+        assert_eq!(VaultState::load(&data), Ok(state));
+
+        assert!(!state.is_unlocked(-1, 6));
+        assert!(!state.is_unlocked(-2, 7));
+        assert!(state.is_unlocked(-1, 7));
+
+        let mut extended = state;
+        assert!(!extended.extend_lock(-2, 7));
+        assert!(!extended.extend_lock(-1, 6));
+        assert_eq!(extended, state);
+        assert!(extended.extend_lock(0, 7));
+        assert_eq!(extended, VaultState { unlock_timestamp: 0, unlock_slot: 7, ..Default::default() });
+    }
+
+    #[test]
+    fn rate_limit() {
+        let mut state = VaultState::default();
+
+        // No limit
+        assert_eq!(state.record_withdrawal(u64::MAX, 0, 0), Ok(()));
+
+        state.set_limit(100, 0);
+        assert_eq!(state.limit, 100);
+
+        // The epoch is reset on the first withdrawal in it
+        assert_eq!(state.record_withdrawal(60, 1, 10), Ok(()));
+        assert_eq!(state.record_withdrawal(41, 1, 10), Err(RateLimitExceeded));
+        assert_eq!(state.record_withdrawal(40, 1, 10), Ok(()));
+        assert_eq!(state.record_withdrawal(1, 1, 10), Err(RateLimitExceeded));
+        assert_eq!(state.record_withdrawal(100, 2, 10), Ok(()));
+
+        // Raising waits for the delay, lifting the limit counts as raising it
+        state.set_limit(0, 10);
+        assert_eq!(state.limit, 100);
+        assert_eq!(state.record_withdrawal(1, 3, 10 + RATE_LIMIT_RAISE_DELAY - 1), Ok(()));
+        assert_eq!(state.record_withdrawal(100, 3, 10 + RATE_LIMIT_RAISE_DELAY - 1), Err(RateLimitExceeded));
+        assert_eq!(state.record_withdrawal(100, 3, 10 + RATE_LIMIT_RAISE_DELAY), Ok(()));
+        assert_eq!(state.limit, 0);
+
+        // Lowering applies right away, and drops a pending raise
+        state.set_limit(1_000, 20);
+        state.set_limit(50, 20);
+        assert_eq!((state.limit, state.pending_limit, state.pending_slot), (50, 0, 0));
+        assert_eq!(state.record_withdrawal(51, 4, 20 + RATE_LIMIT_RAISE_DELAY), Err(RateLimitExceeded));
+
+        let mut data = [0; VaultState::LEN];
+        state.store(&mut data);
+        assert_eq!(VaultState::load(&data), Ok(state));
+    }
+
+    #[test]
+    fn rent_exempt_minimum() {
+        use solana_sdk::rent::Rent;
+
+        let rent = Rent::default();
+        for data_len in [0, VaultState::LOCK_LEN, VaultState::LEN, VaultState::LEN + Delegate::LEN] {
+            assert_eq!(
+                minimum_balance(rent.lamports_per_byte_year, rent.exemption_threshold, data_len),
+                rent.minimum_balance(data_len)
+            );
+        }
+    }
+
+    #[test]
+    fn delegates() {
+        let delegate = Delegate { key: [1; 32], allowance: 100, expires_at: 50 };
+        let mut data = vec![0; VaultState::LEN + 2 * Delegate::LEN];
+        delegate.store(&mut data[VaultState::LEN + Delegate::LEN..]);
+
+        assert_eq!(VaultState::load(&data), Ok(VaultState::default()));
+        assert_eq!(VaultState::find_delegate(&data, &[1; 32]), Some(VaultState::LEN + Delegate::LEN));
+        assert_eq!(VaultState::find_delegate(&data, &[2; 32]), None);
+        assert_eq!(VaultState::find_delegate(&data[..VaultState::LOCK_LEN], &[0; 32]), None);
+        assert_eq!(Delegate::load(&data[VaultState::LEN + Delegate::LEN..]), Ok(delegate));
+        assert_eq!(Delegate::load(&data[data.len() - Delegate::LEN + 1..]), Err(InvalidVaultData));
+
+        // Only whole delegates, and never as long as a multisig config
+        assert!(VaultState::is_valid_len(VaultState::LEN + VaultState::MAX_DELEGATES * Delegate::LEN));
+        assert!(!VaultState::is_valid_len(VaultState::LEN + (VaultState::MAX_DELEGATES + 1) * Delegate::LEN));
+        assert!(!VaultState::is_valid_len(VaultState::LEN + 8));
+        assert!(!VaultState::is_valid_len(MultisigConfig::LEN));
+
+        let mut spent = delegate;
+        assert!(!spent.spend(101, 0));
+        assert!(spent.spend(60, 49));
+        assert!(!spent.spend(41, 49));
+        assert!(!spent.spend(40, 50));
+        assert_eq!(spent, Delegate { allowance: 40, ..delegate });
+
+        let mut forever = Delegate { expires_at: 0, ..delegate };
+        assert!(forever.spend(100, i64::MAX));
+    }
+
+    /// Serializes accounts the way the runtime does for a program's entrypoint. `Err(i)`
+    /// duplicates account `i`. Backed by `u64`s, so the buffer is aligned like the real one.
+    fn serialize_input(accounts: &[Result<(Pubkey, AccountSharedData, bool), u8>], data: &[u8]) -> Vec<u64> {
+        let mut input = (accounts.len() as u64).to_le_bytes().to_vec();
+
+        for account in accounts {
+            match account {
+                Ok((key, account, is_signer)) => {
+                    input.extend([NON_DUP_MARKER, *is_signer as u8, 1, 0, 0, 0, 0, 0]);
+                    input.extend(key.as_ref());
+                    input.extend(account.owner().as_ref());
+                    input.extend(account.lamports().to_le_bytes());
+                    input.extend((account.data().len() as u64).to_le_bytes());
+                    input.extend(account.data());
+                    input.resize((input.len() + MAX_PERMITTED_DATA_INCREASE + 7) & !7, 0);
+                    input.extend(0u64.to_le_bytes());
+                }
+                Err(i) => input.extend([*i, 0, 0, 0, 0, 0, 0, 0]),
+            }
+        }
+
+        input.extend((data.len() as u64).to_le_bytes());
+        input.extend(data);
+        input.extend(PROGRAM_ID.as_ref());
+        input.resize((input.len() + 7) & !7, 0);
+
+        input.chunks_exact(8).map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap())).collect()
+    }
+
+    #[test]
+    fn input_parser() {
+        let signer = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+
+        let mut vault_account = AccountSharedData::new(7, 13, &PROGRAM_ID);
+        vault_account.set_data_from_slice(&[0xab; 13]);
+
+        let mut input = serialize_input(
+            &[
+                Ok((signer, AccountSharedData::new(1, 0, &Pubkey::default()), true)),
+                Ok((vault, vault_account, false)),
+                Err(0),
+                Ok((destination, AccountSharedData::new(3, 1, &Pubkey::default()), false)),
+            ],
+            &[1, 2, 3],
+        );
+
+        // Found without reading any of the accounts, as `lazy` does
+        assert_eq!(unsafe { instruction_data(input.as_mut_ptr() as *mut u8) }, [1, 2, 3]);
+
+        let input = unsafe { Input::<3>::parse(input.as_mut_ptr() as *mut u8) };
+
+        // Accounts past the first 3 are walked over, not kept
+        assert_eq!(input.account_count(), 4);
+        assert_eq!(input.data(), [1, 2, 3]);
+        assert_eq!(input.program_id(), &PROGRAM_ID.to_bytes());
+
+        let [signer_view, vault_view, duplicate] = input.accounts() else {
+            panic!("expected 3 accounts");
+        };
+
+        assert!(signer_view.is_signer() && !vault_view.is_signer());
+        assert_eq!(signer_view.key(), &signer.to_bytes());
+        assert_eq!(signer_view.lamports(), 1);
+        assert_eq!(vault_view.key(), &vault.to_bytes());
+        assert_eq!(vault_view.owner(), &PROGRAM_ID.to_bytes());
+        assert_eq!(vault_view.data(), [0xab; 13]);
+
+        // Duplicates are the same account
+        assert!(!input.is_duplicate(1));
+        assert!(input.is_duplicate(2));
+        assert_eq!(duplicate, signer_view);
+        duplicate.set_lamports(2);
+        assert_eq!(signer_view.lamports(), 2);
+
+        // Data grows into its realloc padding, and no further
+        assert_eq!(vault_view.resize(13 + MAX_PERMITTED_DATA_INCREASE), Ok(()));
+        assert_eq!(vault_view.data_len(), 13 + MAX_PERMITTED_DATA_INCREASE);
+        assert_eq!(vault_view.resize(14 + MAX_PERMITTED_DATA_INCREASE), Err(VaultError::InvalidRealloc));
+        assert_eq!(vault_view.resize(0), Ok(()));
+        assert_eq!(vault_view.resize(13), Ok(()));
+
+        // Regrown bytes don't bring back what was there
+        assert_eq!(vault_view.data(), [0; 13]);
+    }
+
+    #[test]
+    fn error_codes() {
+        // Codes are append-only, clients out there decode these
+        let codes = [
+            (1, VaultError::InvalidAccountCount),
+            (2, VaultError::InvalidAccount),
+            (3, VaultError::InvalidVaultData),
+            (4, VaultError::InvalidPda),
+            (5, VaultError::MissingSignature),
+            (6, VaultError::VaultLocked),
+            (7, VaultError::LockNotExtended),
+            (8, VaultError::RateLimitExceeded),
+            (9, VaultError::NotADelegate),
+            (10, VaultError::AllowanceExceeded),
+            (11, VaultError::TooManyDelegates),
+            (12, VaultError::InsufficientFunds),
+            (13, VaultError::InvalidAccountOwner),
+            (14, VaultError::NotApproved),
+            (15, VaultError::MultisigAlreadyCreated),
+            (16, VaultError::InvalidTokenAccount),
+            (17, VaultError::ArithmeticOverflow),
+            (18, VaultError::BelowRentExempt),
+            (19, VaultError::NonCanonicalBump),
+            (20, VaultError::UnsupportedInstruction),
+            (21, VaultError::InvalidRealloc),
+        ];
+
+        for (code, error) in codes {
+            assert_eq!(error.code(), code);
+            assert_eq!(VaultError::from_code(code), Some(error));
+            assert_eq!(error.to_string(), error.message());
+        }
+        assert_eq!(VaultError::from_code(0), None);
+        assert_eq!(VaultError::from_code(22), None);
+
+        assert_eq!(VaultError::from(InvalidVaultData), VaultError::InvalidVaultData);
+        assert_eq!(VaultError::from(RateLimitExceeded), VaultError::RateLimitExceeded);
+
+        assert_eq!(
+            VaultError::from_log(&format!("Program {PROGRAM_ID} failed: custom program error: 0xc")),
+            Some(VaultError::InsufficientFunds)
+        );
+        assert_eq!(VaultError::from_log("Program log: Vault is locked"), None);
+        assert_eq!(VaultError::from_log("Program failed: custom program error: 0xff"), None);
     }
 }
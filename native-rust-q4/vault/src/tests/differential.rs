@@ -0,0 +1,648 @@
+//! # Differential tests
+//!
+//! Runs one table of valid and adversarial vectors against the `native`, `optimized`, `lazy`
+//! and `based` builds side by side, and checks that every backend agrees with `native` (the
+//! reference) on the result, down to the `VaultError` it fails with, and on the accounts it
+//! leaves behind. Multisig vaults and streams are left out, `based` only handles
+//! single-signer vaults and only `optimized` and `lazy` have streams.
+//!
+//! The backends are mutually exclusive features, so each one is built on its own and
+//! renamed before running these:
+//!
+//! ```sh
+//! for backend in native optimized lazy based; do
+//!     cargo build-sbf --no-default-features --features $backend
+//!     mv target/deploy/native_vault.so target/deploy/native_vault_$backend.so
+//! done
+//! ```
+//!
+//! Add `canonical-bump` to every build and to `cargo test` to run them in that mode.
+
+use mollusk_svm::{program, result::ProgramResult, Mollusk};
+use solana_sdk::{
+    account::AccountSharedData,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+};
+
+use super::tests::{lower_bump, PROGRAM_ID};
+use crate::{
+    error::VaultError,
+    instruction::VaultInstruction,
+    state::{Delegate, VaultState},
+};
+
+const BACKENDS: [&str; 4] = ["native", "optimized", "lazy", "based"];
+
+struct Vector {
+    name: &'static str,
+    succeeds: bool,
+    instruction: Instruction,
+    accounts: Vec<(Pubkey, AccountSharedData)>,
+}
+
+fn withdraw(signer: AccountMeta, vault: Pubkey, bump: u8, lamports: u64) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &[&[0], &lamports.to_le_bytes()[..], &[bump]].concat(),
+        vec![signer, AccountMeta::new(vault, false)],
+    )
+}
+
+fn withdraw_to(signer: Pubkey, vault: Pubkey, destination: Pubkey, bump: u8, lamports: u64) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &[&[0], &lamports.to_le_bytes()[..], &[bump]].concat(),
+        vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false), AccountMeta::new(destination, false)],
+    )
+}
+
+fn vault_with_state(lamports: u64, state: VaultState) -> AccountSharedData {
+    let mut data = vec![0; VaultState::LEN];
+    state.store(&mut data);
+
+    let mut vault = AccountSharedData::new(lamports, VaultState::LEN, &PROGRAM_ID);
+    vault.set_data_from_slice(&data);
+    vault
+}
+
+fn withdraw_delegated(delegate: Pubkey, vault: Pubkey, destination: Pubkey, lamports: u64) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &[&[0], &lamports.to_le_bytes()[..], &[0]].concat(),
+        vec![AccountMeta::new(delegate, true), AccountMeta::new(vault, false), AccountMeta::new(destination, false)],
+    )
+}
+
+fn vault_with_delegate(lamports: u64, state: VaultState, delegate: Delegate) -> AccountSharedData {
+    let mut data = vec![0; VaultState::LEN + Delegate::LEN];
+    state.store(&mut data);
+    delegate.store(&mut data[VaultState::LEN..]);
+
+    let mut vault = AccountSharedData::new(lamports, data.len(), &PROGRAM_ID);
+    vault.set_data_from_slice(&data);
+    vault
+}
+
+fn keep_rent_exempt(signer: Pubkey, vault: Pubkey, bump: u8, lamports: u64) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &[&[12], &lamports.to_le_bytes()[..], &[bump]].concat(),
+        vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+    )
+}
+
+fn set_lock(signer: Pubkey, vault: Pubkey, bump: u8, unlock_timestamp: i64, unlock_slot: u64) -> Instruction {
+    Instruction::new_with_bytes(
+        PROGRAM_ID,
+        &[&[3], &unlock_timestamp.to_le_bytes()[..], &unlock_slot.to_le_bytes(), &[bump]].concat(),
+        vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+    )
+}
+
+fn vectors() -> Vec<Vector> {
+    let signer = Pubkey::new_unique();
+    let (vault, bump) = Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+    let (system_program, system_program_account) = program::keyed_account_for_system_program();
+
+    let signer_account = AccountSharedData::new(1_000_000_000, 0, &Pubkey::default());
+    let vault_account = AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID);
+    let accounts = vec![(signer, signer_account.clone()), (vault, vault_account.clone())];
+    let (lower_vault, lower_bump) = lower_bump(&signer);
+    // More than any instruction takes, a multisig withdraw with every member signing
+    let extra: Vec<_> = (0..16).map(|_| (Pubkey::new_unique(), AccountSharedData::default())).collect();
+
+    // The default Mollusk clock sits at slot 0 and unix timestamp 0
+    let locked = vec![
+        (signer, signer_account.clone()),
+        (vault, vault_with_state(1_000_000_000, VaultState { unlock_timestamp: 1, unlock_slot: 0, ..Default::default() })),
+    ];
+    let limited = vec![
+        (signer, signer_account.clone()),
+        (vault, vault_with_state(1_000_000_000, VaultState { limit: 100_000_000, ..Default::default() })),
+    ];
+    let expired = vec![
+        (signer, signer_account.clone()),
+        (vault, vault_with_state(1_000_000_000, VaultState { unlock_timestamp: -1, unlock_slot: 0, ..Default::default() })),
+    ];
+
+    let delegate = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+    let delegated = vec![
+        (delegate, signer_account.clone()),
+        (
+            vault,
+            vault_with_delegate(
+                1_000_000_000,
+                VaultState::default(),
+                Delegate { key: delegate.to_bytes(), allowance: 300_000_000, expires_at: 0 },
+            ),
+        ),
+        (destination, AccountSharedData::new(0, 0, &Pubkey::default())),
+    ];
+    let delegated_and_limited = vec![
+        delegated[0].clone(),
+        (
+            vault,
+            vault_with_delegate(
+                1_000_000_000,
+                VaultState { limit: 100_000_000, ..Default::default() },
+                Delegate { key: delegate.to_bytes(), allowance: 300_000_000, expires_at: 0 },
+            ),
+        ),
+        delegated[2].clone(),
+    ];
+    let expired_delegate = vec![
+        delegated[0].clone(),
+        (
+            vault,
+            vault_with_delegate(
+                1_000_000_000,
+                VaultState::default(),
+                Delegate { key: delegate.to_bytes(), allowance: 300_000_000, expires_at: -1 },
+            ),
+        ),
+        delegated[2].clone(),
+    ];
+
+    // Mollusk's default Rent
+    let minimum = Rent::default().minimum_balance(VaultState::LEN);
+    let minimum_with_delegate = Rent::default().minimum_balance(VaultState::LEN + Delegate::LEN);
+    let limited_free = vec![
+        (signer, signer_account.clone()),
+        (vault, vault_with_state(1_000_000_000, VaultState::default())),
+    ];
+
+    vec![
+        Vector {
+            name: "withdraw",
+            succeeds: true,
+            instruction: withdraw(AccountMeta::new(signer, true), vault, bump, 400_000_000),
+            accounts: accounts.clone(),
+        },
+        Vector {
+            name: "withdraw everything",
+            succeeds: true,
+            instruction: withdraw(AccountMeta::new(signer, true), vault, bump, 1_000_000_000),
+            accounts: accounts.clone(),
+        },
+        Vector {
+            name: "withdraw to a destination",
+            succeeds: true,
+            instruction: withdraw_to(signer, vault, destination, bump, 400_000_000),
+            accounts: vec![accounts[0].clone(), accounts[1].clone(), delegated[2].clone()],
+        },
+        Vector {
+            name: "withdraw to the vault itself",
+            succeeds: true,
+            instruction: withdraw_to(signer, vault, vault, bump, 400_000_000),
+            accounts: accounts.clone(),
+        },
+        Vector {
+            name: "withdraw to a destination from a locked vault",
+            succeeds: false,
+            instruction: withdraw_to(signer, vault, destination, bump, 1),
+            accounts: vec![locked[0].clone(), locked[1].clone(), delegated[2].clone()],
+        },
+        Vector {
+            name: "deposit into a new vault",
+            succeeds: true,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[1], &400_000_000u64.to_le_bytes()[..], &[bump], signer.as_ref()].concat(),
+                vec![
+                    AccountMeta::new(signer, true),
+                    AccountMeta::new(vault, false),
+                    AccountMeta::new_readonly(system_program, false),
+                ],
+            ),
+            accounts: vec![
+                (signer, signer_account.clone()),
+                (vault, AccountSharedData::new(0, 0, &Pubkey::default())),
+                (system_program, system_program_account.clone()),
+            ],
+        },
+        Vector {
+            name: "close",
+            succeeds: true,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[2, bump],
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            accounts: accounts.clone(),
+        },
+        Vector {
+            name: "withdraw with a lower bump",
+            succeeds: !cfg!(feature = "canonical-bump"),
+            instruction: withdraw(AccountMeta::new(signer, true), lower_vault, lower_bump, 1),
+            accounts: vec![(signer, signer_account.clone()), (lower_vault, vault_account.clone())],
+        },
+        Vector {
+            name: "close a vault with data",
+            succeeds: true,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[2, bump],
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            accounts: expired.clone(),
+        },
+        Vector {
+            name: "set a lock",
+            succeeds: true,
+            instruction: set_lock(signer, vault, bump, 1, 1),
+            accounts: accounts.clone(),
+        },
+        Vector {
+            name: "extend a lock",
+            succeeds: true,
+            instruction: set_lock(signer, vault, bump, 2, 0),
+            accounts: locked.clone(),
+        },
+        Vector {
+            name: "shorten a lock",
+            succeeds: false,
+            instruction: set_lock(signer, vault, bump, 0, 0),
+            accounts: locked.clone(),
+        },
+        Vector {
+            name: "withdraw from a locked vault",
+            succeeds: false,
+            instruction: withdraw(AccountMeta::new(signer, true), vault, bump, 1),
+            accounts: locked.clone(),
+        },
+        Vector {
+            name: "close a locked vault",
+            succeeds: false,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[2, bump],
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            accounts: locked.clone(),
+        },
+        Vector {
+            name: "set a rate limit",
+            succeeds: true,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[9], &100_000_000u64.to_le_bytes()[..], &[bump]].concat(),
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            accounts: locked.clone(),
+        },
+        Vector {
+            name: "withdraw within the rate limit",
+            succeeds: true,
+            instruction: withdraw(AccountMeta::new(signer, true), vault, bump, 100_000_000),
+            accounts: limited.clone(),
+        },
+        Vector {
+            name: "withdraw over the rate limit",
+            succeeds: false,
+            instruction: withdraw(AccountMeta::new(signer, true), vault, bump, 100_000_001),
+            accounts: limited.clone(),
+        },
+        Vector {
+            name: "close over the rate limit",
+            succeeds: false,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[2, bump],
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            accounts: limited,
+        },
+        Vector {
+            name: "withdraw after the lock expired",
+            succeeds: true,
+            instruction: withdraw(AccountMeta::new(signer, true), vault, bump, 400_000_000),
+            accounts: expired,
+        },
+        Vector {
+            name: "deposit into a locked vault",
+            succeeds: true,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[1], &400_000_000u64.to_le_bytes()[..], &[bump], signer.as_ref()].concat(),
+                vec![
+                    AccountMeta::new(signer, true),
+                    AccountMeta::new(vault, false),
+                    AccountMeta::new_readonly(system_program, false),
+                ],
+            ),
+            accounts: vec![
+                (signer, signer_account.clone()),
+                locked[1].clone(),
+                (system_program, system_program_account.clone()),
+            ],
+        },
+        Vector {
+            name: "add a delegate",
+            succeeds: true,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[10], delegate.as_ref(), &300_000_000u64.to_le_bytes(), &0i64.to_le_bytes(), &[bump]].concat(),
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            accounts: locked.clone(),
+        },
+        Vector {
+            name: "replace a delegate's allowance",
+            succeeds: true,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[10], delegate.as_ref(), &1u64.to_le_bytes(), &0i64.to_le_bytes(), &[bump]].concat(),
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            accounts: vec![(signer, signer_account.clone()), delegated[1].clone()],
+        },
+        Vector {
+            name: "revoke a delegate",
+            succeeds: true,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[11], delegate.as_ref(), &[bump]].concat(),
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            accounts: vec![(signer, signer_account.clone()), delegated[1].clone()],
+        },
+        Vector {
+            name: "revoke a delegate the vault doesn't have",
+            succeeds: false,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[11], destination.as_ref(), &[bump]].concat(),
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            accounts: vec![(signer, signer_account.clone()), delegated[1].clone()],
+        },
+        Vector {
+            name: "delegated withdraw",
+            succeeds: true,
+            instruction: withdraw_delegated(delegate, vault, destination, 300_000_000),
+            accounts: delegated.clone(),
+        },
+        Vector {
+            name: "delegated withdraw to the delegate",
+            succeeds: true,
+            instruction: withdraw_delegated(delegate, vault, delegate, 300_000_000),
+            accounts: delegated[..2].to_vec(),
+        },
+        Vector {
+            name: "delegated withdraw over the allowance",
+            succeeds: false,
+            instruction: withdraw_delegated(delegate, vault, destination, 300_000_001),
+            accounts: delegated.clone(),
+        },
+        Vector {
+            name: "delegated withdraw over the rate limit",
+            succeeds: false,
+            instruction: withdraw_delegated(delegate, vault, destination, 100_000_001),
+            accounts: delegated_and_limited,
+        },
+        Vector {
+            name: "delegated withdraw after expiry",
+            succeeds: false,
+            instruction: withdraw_delegated(delegate, vault, destination, 1),
+            accounts: expired_delegate,
+        },
+        Vector {
+            name: "delegated withdraw by the owner",
+            succeeds: false,
+            instruction: withdraw_delegated(signer, vault, destination, 1),
+            accounts: vec![(signer, signer_account.clone()), delegated[1].clone(), delegated[2].clone()],
+        },
+        Vector {
+            name: "withdraw to a signer with data",
+            succeeds: true,
+            instruction: withdraw(AccountMeta::new(signer, true), vault, bump, 400_000_000),
+            accounts: vec![
+                (signer, AccountSharedData::new(1_000_000_000, 100, &Pubkey::default())),
+                (vault, vault_account.clone()),
+            ],
+        },
+        Vector {
+            name: "withdraw into a full signer",
+            succeeds: false,
+            instruction: withdraw(AccountMeta::new(signer, true), vault, bump, 1),
+            accounts: vec![
+                (signer, AccountSharedData::new(u64::MAX, 0, &Pubkey::default())),
+                (vault, vault_account.clone()),
+            ],
+        },
+        Vector {
+            name: "keep rent-exempt",
+            succeeds: true,
+            instruction: keep_rent_exempt(signer, vault, bump, 1_000_000_000 - minimum),
+            accounts: limited_free.clone(),
+        },
+        Vector {
+            name: "keep rent-exempt, going below it",
+            succeeds: false,
+            instruction: keep_rent_exempt(signer, vault, bump, 1_000_000_000 - minimum + 1),
+            accounts: limited_free.clone(),
+        },
+        Vector {
+            name: "keep rent-exempt, delegated",
+            succeeds: false,
+            instruction: {
+                let mut instruction = withdraw_delegated(delegate, vault, destination, 300_000_000);
+                instruction.data[0] = 12;
+                instruction
+            },
+            accounts: vec![
+                delegated[0].clone(),
+                (
+                    vault,
+                    vault_with_delegate(
+                        minimum_with_delegate + 299_999_999,
+                        VaultState::default(),
+                        Delegate { key: delegate.to_bytes(), allowance: 300_000_000, expires_at: 0 },
+                    ),
+                ),
+                delegated[2].clone(),
+            ],
+        },
+        Vector {
+            name: "wrong bump",
+            succeeds: false,
+            instruction: withdraw(AccountMeta::new(signer, true), vault, bump.wrapping_sub(1), 1),
+            accounts: accounts.clone(),
+        },
+        Vector {
+            name: "non-signer",
+            succeeds: false,
+            instruction: withdraw(AccountMeta::new(signer, false), vault, bump, 1),
+            accounts: accounts.clone(),
+        },
+        Vector {
+            name: "set a lock, signed readonly",
+            succeeds: true,
+            instruction: {
+                let mut instruction = set_lock(signer, vault, bump, 1, 1);
+                instruction.accounts[0].is_writable = false;
+                instruction
+            },
+            accounts: accounts.clone(),
+        },
+        Vector {
+            // Signing is all it takes, the runtime refuses the lamports
+            name: "withdraw to a readonly signer",
+            succeeds: false,
+            instruction: withdraw(AccountMeta::new_readonly(signer, true), vault, bump, 1),
+            accounts: accounts.clone(),
+        },
+        Vector {
+            name: "extra accounts",
+            succeeds: false,
+            instruction: {
+                let mut instruction = withdraw(AccountMeta::new(signer, true), vault, bump, 1);
+                instruction.accounts.push(AccountMeta::new_readonly(system_program, false));
+                instruction
+            },
+            accounts: vec![
+                (signer, signer_account.clone()),
+                (vault, vault_account.clone()),
+                (system_program, system_program_account.clone()),
+            ],
+        },
+        Vector {
+            name: "vault with data",
+            succeeds: false,
+            instruction: withdraw(AccountMeta::new(signer, true), vault, bump, 1),
+            accounts: vec![
+                (signer, signer_account.clone()),
+                (vault, AccountSharedData::new(1_000_000_000, 8, &PROGRAM_ID)),
+            ],
+        },
+        Vector {
+            // The PDA is checked before the data, whatever is wrong with it
+            name: "wrong bump, vault with data",
+            succeeds: false,
+            instruction: withdraw(AccountMeta::new(signer, true), vault, bump.wrapping_sub(1), 1),
+            accounts: vec![
+                (signer, signer_account.clone()),
+                (vault, AccountSharedData::new(1_000_000_000, 8, &PROGRAM_ID)),
+            ],
+        },
+        Vector {
+            name: "vault duplicating the signer",
+            succeeds: false,
+            instruction: set_lock(signer, signer, bump, 1, 1),
+            accounts: vec![(signer, signer_account.clone())],
+        },
+        Vector {
+            name: "insufficient lamports",
+            succeeds: false,
+            instruction: withdraw(AccountMeta::new(signer, true), vault, bump, 1_000_000_001),
+            accounts: accounts.clone(),
+        },
+        Vector {
+            name: "withdraw with too many accounts",
+            succeeds: false,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[&[0], &1u64.to_le_bytes()[..], &[bump]].concat(),
+                [AccountMeta::new(signer, true), AccountMeta::new(vault, false)]
+                    .into_iter()
+                    .chain(extra.iter().map(|(key, _)| AccountMeta::new_readonly(*key, false)))
+                    .collect(),
+            ),
+            accounts: [accounts.clone(), extra].concat(),
+        },
+        Vector {
+            name: "malformed data",
+            succeeds: false,
+            instruction: Instruction::new_with_bytes(
+                PROGRAM_ID,
+                &[0, 1, 2, 3],
+                vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+            ),
+            accounts,
+        },
+    ]
+}
+
+#[test]
+fn backends_agree() {
+    let backends: Vec<(&str, Mollusk)> = BACKENDS
+        .iter()
+        .map(|backend| {
+            (
+                *backend,
+                Mollusk::new(&PROGRAM_ID, &format!("target/deploy/native_vault_{backend}")),
+            )
+        })
+        .collect();
+    let ((reference_name, reference), rest) = backends.split_first().unwrap();
+
+    for vector in vectors() {
+        let expected = reference.process_instruction(&vector.instruction, &vector.accounts);
+
+        assert_eq!(
+            !expected.program_result.is_err(),
+            vector.succeeds,
+            "{}: {reference_name} returned {:?}",
+            vector.name,
+            expected.program_result,
+        );
+
+        for (name, mollusk) in rest {
+            let result = mollusk.process_instruction(&vector.instruction, &vector.accounts);
+
+            assert_eq!(
+                result.program_result,
+                expected.program_result,
+                "{}: {name} returned {:?}, {reference_name} returned {:?}",
+                vector.name,
+                result.program_result,
+                expected.program_result,
+            );
+            assert_eq!(
+                result.resulting_accounts, expected.resulting_accounts,
+                "{}: {name} and {reference_name} left different accounts",
+                vector.name,
+            );
+        }
+    }
+}
+
+/// What the backends leave out fails the same way in each, whatever accounts come with it.
+#[test]
+fn unsupported_instructions() {
+    let signer = Pubkey::new_unique();
+    let (vault, bump) = Pubkey::try_find_program_address(&[signer.as_ref()], &PROGRAM_ID).unwrap();
+    let accounts = vec![
+        (signer, AccountSharedData::new(1_000_000_000, 0, &Pubkey::default())),
+        (vault, AccountSharedData::new(1_000_000_000, 0, &PROGRAM_ID)),
+    ];
+    let instruction = |instruction: VaultInstruction| {
+        Instruction::new_with_bytes(
+            PROGRAM_ID,
+            &instruction.to_bytes(),
+            vec![AccountMeta::new(signer, true), AccountMeta::new(vault, false)],
+        )
+    };
+
+    let unsupported = [
+        ("native", VaultInstruction::CancelStream { bump }),
+        ("based", VaultInstruction::CancelStream { bump }),
+        ("based", VaultInstruction::WithdrawMultisig { lamports: 1, bump }),
+        ("based", VaultInstruction::WithdrawToken { amount: 1, bump }),
+    ];
+
+    for (backend, unsupported) in unsupported {
+        let mollusk = Mollusk::new(&PROGRAM_ID, &format!("target/deploy/native_vault_{backend}"));
+        let result = mollusk.process_instruction(&instruction(unsupported), &accounts);
+
+        assert_eq!(
+            result.program_result,
+            ProgramResult::Failure(ProgramError::Custom(VaultError::UnsupportedInstruction.code())),
+            "{backend}: {unsupported:?}",
+        );
+    }
+}
@@ -0,0 +1,38 @@
+//! # Accounts
+//!
+//! Decoders for the data the program keeps. Multisig configs and streams decode with
+//! `MultisigConfig::load` and `Stream::load`, this adds the vault, whose delegates
+//! `VaultState` leaves alone.
+
+use solana_program::pubkey::Pubkey;
+
+use crate::{Delegate, InvalidVaultData, VaultState};
+
+/// # Vault account
+///
+/// The state of a vault and its delegates. A vault without data, or with only a lock,
+/// decodes like the program reads it: fields it doesn't have are 0, and there are no
+/// delegates.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VaultAccount {
+    pub state: VaultState,
+    pub delegates: Vec<Delegate>,
+}
+
+impl VaultAccount {
+    pub fn decode(data: &[u8]) -> Result<Self, InvalidVaultData> {
+        let state = VaultState::load(data)?;
+        let delegates = data
+            .get(VaultState::LEN..)
+            .unwrap_or_default()
+            .chunks_exact(Delegate::LEN)
+            .map(Delegate::load)
+            .collect();
+
+        Ok(Self { state, delegates })
+    }
+
+    pub fn delegate(&self, key: &Pubkey) -> Option<&Delegate> {
+        self.delegates.iter().find(|delegate| delegate.key == key.to_bytes())
+    }
+}
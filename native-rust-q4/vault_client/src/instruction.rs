@@ -0,0 +1,268 @@
+//! # Instruction builders
+//!
+//! One per `VaultInstruction`, with the accounts in the order the program expects them.
+//! Builders derive the canonical vault and its bump themselves, so callers only ever deal
+//! in owners and config accounts.
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_instruction,
+};
+
+use crate::{
+    pda::{find_multisig_vault_address, find_stream_address, find_vault_address},
+    Delegate, MultisigConfig, Stream, VaultInstruction, ID, SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID,
+};
+
+/// Withdraws `lamports` from the vault of `owner` back to `owner`.
+pub fn withdraw(owner: &Pubkey, lamports: u64) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    owner_instruction(owner, vault, VaultInstruction::Withdraw { lamports, bump })
+}
+
+/// `withdraw`, failing instead of leaving a vault with data below its rent-exempt minimum.
+pub fn withdraw_keep_rent_exempt(owner: &Pubkey, lamports: u64) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    owner_instruction(owner, vault, VaultInstruction::WithdrawKeepRentExempt { lamports, bump })
+}
+
+/// Withdraws `lamports` from the vault of `owner` straight to `destination`.
+pub fn withdraw_to(owner: &Pubkey, destination: &Pubkey, lamports: u64) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    destination_instruction(owner, vault, destination, VaultInstruction::Withdraw { lamports, bump })
+}
+
+/// `withdraw_to`, failing instead of leaving a vault with data below its rent-exempt minimum.
+pub fn withdraw_to_keep_rent_exempt(owner: &Pubkey, destination: &Pubkey, lamports: u64) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    destination_instruction(owner, vault, destination, VaultInstruction::WithdrawKeepRentExempt { lamports, bump })
+}
+
+/// Withdraws `lamports` from the vault of `owner` to `destination`, signed by one of its
+/// delegates.
+pub fn withdraw_delegated(delegate: &Pubkey, owner: &Pubkey, destination: &Pubkey, lamports: u64) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    destination_instruction(delegate, vault, destination, VaultInstruction::Withdraw { lamports, bump })
+}
+
+/// `withdraw_delegated`, failing instead of leaving a vault with data below its rent-exempt
+/// minimum.
+pub fn withdraw_delegated_keep_rent_exempt(
+    delegate: &Pubkey,
+    owner: &Pubkey,
+    destination: &Pubkey,
+    lamports: u64,
+) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    destination_instruction(delegate, vault, destination, VaultInstruction::WithdrawKeepRentExempt { lamports, bump })
+}
+
+/// Deposits `lamports` from `payer` into the vault of `owner`, which doesn't have to be
+/// the payer.
+pub fn deposit(payer: &Pubkey, owner: &Pubkey, lamports: u64) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    Instruction::new_with_bytes(
+        ID,
+        &VaultInstruction::Deposit { lamports, bump, owner: owner.to_bytes() }.to_bytes(),
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+        ],
+    )
+}
+
+/// Closes the vault of `owner`, sending everything in it back to `owner`.
+pub fn close(owner: &Pubkey) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    owner_instruction(owner, vault, VaultInstruction::Close { bump })
+}
+
+/// Locks the vault of `owner` until both `unlock_timestamp` and `unlock_slot`, 0 to only
+/// lock on the other one.
+pub fn set_lock(owner: &Pubkey, unlock_timestamp: i64, unlock_slot: u64) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    owner_instruction(owner, vault, VaultInstruction::SetLock { unlock_timestamp, unlock_slot, bump })
+}
+
+/// Limits withdrawals from the vault of `owner` to `limit` lamports per epoch, 0 for no
+/// limit.
+pub fn set_rate_limit(owner: &Pubkey, limit: u64) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    owner_instruction(owner, vault, VaultInstruction::SetRateLimit { limit, bump })
+}
+
+/// Adds `delegate` to the vault of `owner`, or replaces the one with the same key.
+pub fn set_delegate(owner: &Pubkey, delegate: Delegate) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    owner_instruction(owner, vault, VaultInstruction::SetDelegate { delegate, bump })
+}
+
+/// Removes `delegate` from the vault of `owner`.
+pub fn revoke_delegate(owner: &Pubkey, delegate: &Pubkey) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    owner_instruction(owner, vault, VaultInstruction::RevokeDelegate { delegate: delegate.to_bytes(), bump })
+}
+
+/// Creates the `config` account, funded with `lamports` by `payer`, and writes `multisig`
+/// into it. Both go in the same transaction, `config` signs so nobody can claim it in
+/// between.
+pub fn create_multisig(payer: &Pubkey, config: &Pubkey, multisig: MultisigConfig, lamports: u64) -> [Instruction; 2] {
+    [
+        system_instruction::create_account(payer, config, lamports, MultisigConfig::LEN as u64, &ID),
+        Instruction::new_with_bytes(
+            ID,
+            &VaultInstruction::CreateMultisig { config: multisig }.to_bytes(),
+            vec![AccountMeta::new(*config, true)],
+        ),
+    ]
+}
+
+/// Replaces the member set in `config` with `multisig`, approved by `members` of the
+/// current one.
+pub fn rotate_members(config: &Pubkey, members: &[Pubkey], multisig: MultisigConfig) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*config, false)];
+    accounts.extend(members.iter().map(|member| AccountMeta::new_readonly(*member, true)));
+
+    Instruction::new_with_bytes(ID, &VaultInstruction::RotateMembers { config: multisig }.to_bytes(), accounts)
+}
+
+/// Withdraws `lamports` from the vault of the member set in `config` to `destination`,
+/// approved by `members`.
+pub fn withdraw_multisig(config: &Pubkey, destination: &Pubkey, members: &[Pubkey], lamports: u64) -> Instruction {
+    let (vault, bump) = find_multisig_vault_address(config);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*config, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+    ];
+    accounts.extend(members.iter().map(|member| AccountMeta::new_readonly(*member, true)));
+
+    Instruction::new_with_bytes(ID, &VaultInstruction::WithdrawMultisig { lamports, bump }.to_bytes(), accounts)
+}
+
+/// Deposits `amount` tokens from `payer_token_account` into `vault_token_account`, a token
+/// account held by the vault of `owner`.
+pub fn deposit_token(
+    payer: &Pubkey,
+    payer_token_account: &Pubkey,
+    vault_token_account: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let (_, bump) = find_vault_address(owner);
+
+    Instruction::new_with_bytes(
+        ID,
+        &VaultInstruction::DepositToken { amount, bump, owner: owner.to_bytes() }.to_bytes(),
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*payer_token_account, false),
+            AccountMeta::new(*vault_token_account, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        ],
+    )
+}
+
+/// Withdraws `amount` tokens from `vault_token_account`, held by the vault of `owner`, to
+/// `destination_token_account`.
+pub fn withdraw_token(
+    owner: &Pubkey,
+    vault_token_account: &Pubkey,
+    destination_token_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let (vault, bump) = find_vault_address(owner);
+
+    Instruction::new_with_bytes(
+        ID,
+        &VaultInstruction::WithdrawToken { amount, bump }.to_bytes(),
+        vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(*vault_token_account, false),
+            AccountMeta::new(*destination_token_account, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+        ],
+    )
+}
+
+/// Streams `stream.amount` lamports from `owner` to `recipient` on the schedule of `stream`,
+/// see `Stream::new`. `owner` also pays for the stream account's rent.
+pub fn create_stream(owner: &Pubkey, recipient: &Pubkey, stream: Stream) -> Instruction {
+    let (stream_address, bump) = find_stream_address(owner, recipient);
+
+    Instruction::new_with_bytes(
+        ID,
+        &VaultInstruction::CreateStream { recipient: recipient.to_bytes(), stream, bump }.to_bytes(),
+        vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(stream_address, false),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+        ],
+    )
+}
+
+/// Withdraws what has vested so far of the stream from `owner` to `recipient`.
+pub fn withdraw_stream(recipient: &Pubkey, owner: &Pubkey) -> Instruction {
+    let (stream, bump) = find_stream_address(owner, recipient);
+
+    Instruction::new_with_bytes(
+        ID,
+        &VaultInstruction::WithdrawStream { owner: owner.to_bytes(), bump }.to_bytes(),
+        vec![AccountMeta::new(*recipient, true), AccountMeta::new(stream, false)],
+    )
+}
+
+/// Cancels the stream from `owner` to `recipient`, paying out what has vested to `recipient`
+/// and the rest to `owner`.
+pub fn cancel_stream(owner: &Pubkey, recipient: &Pubkey) -> Instruction {
+    let (stream, bump) = find_stream_address(owner, recipient);
+
+    Instruction::new_with_bytes(
+        ID,
+        &VaultInstruction::CancelStream { bump }.to_bytes(),
+        vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(stream, false),
+            AccountMeta::new(*recipient, false),
+        ],
+    )
+}
+
+/// [owner, vault], the accounts of everything the owner does alone.
+fn owner_instruction(owner: &Pubkey, vault: Pubkey, instruction: VaultInstruction) -> Instruction {
+    Instruction::new_with_bytes(
+        ID,
+        &instruction.to_bytes(),
+        vec![AccountMeta::new(*owner, true), AccountMeta::new(vault, false)],
+    )
+}
+
+/// [signer, vault, destination], the accounts of withdrawals that don't pay the signer.
+fn destination_instruction(signer: &Pubkey, vault: Pubkey, destination: &Pubkey, instruction: VaultInstruction) -> Instruction {
+    Instruction::new_with_bytes(
+        ID,
+        &instruction.to_bytes(),
+        vec![
+            AccountMeta::new(*signer, true),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(*destination, false),
+        ],
+    )
+}
@@ -0,0 +1,41 @@
+//! # Native Vault Client
+//!
+//! Everything an off-chain service needs to talk to native-vault without packing bytes by
+//! hand:
+//! > `pda`: vault addresses, derived with the same `ProgramDerivedAddress` sha256 scheme the
+//! > program checks
+//! > `instruction`: a builder for every `VaultInstruction`
+//! > `account`: decoders for vault, multisig config and stream data
+//! > `message`: a transaction message ready to sign
+//!
+//! Wire format and layouts come from `native-vault` itself, built without a backend, so the
+//! client can't drift from the program. So does the program ID, which is whatever
+//! `VAULT_PROGRAM_ID` or `VAULT_PROGRAM_KEYPAIR` say at build time (see `../program_id.rs`).
+
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests;
+
+pub mod account;
+pub mod instruction;
+pub mod pda;
+
+pub use native_vault::{
+    error::VaultError,
+    instruction::VaultInstruction,
+    state::{Delegate, InvalidVaultData, MultisigConfig, Stream, VaultState},
+};
+
+use solana_program::{hash::Hash, instruction::Instruction, message::Message, pubkey, pubkey::Pubkey};
+
+pub const ID: Pubkey = Pubkey::new_from_array(native_vault::ID);
+
+pub const SYSTEM_PROGRAM_ID: Pubkey = pubkey!("11111111111111111111111111111111");
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// A message running `instructions` in order, with `payer` paying the fees. Every account
+/// the instructions mark as a signer has to sign it.
+pub fn message(instructions: &[Instruction], payer: &Pubkey, recent_blockhash: Hash) -> Message {
+    Message::new_with_blockhash(instructions, Some(payer), &recent_blockhash)
+}
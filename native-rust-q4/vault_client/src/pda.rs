@@ -0,0 +1,53 @@
+//! # PDAs
+//!
+//! The program never calls `create_program_address`, it hashes the seeds itself:
+//! > sha256(seeds, bump, program_id, "ProgramDerivedAddress")
+//!
+//! That's the same address the runtime derives, minus the check that it's off the curve, so
+//! the `vault_address` functions take any bump while the `find_` ones only return the
+//! canonical one. Stick to the canonical bump: the program signs for the vault when it
+//! assigns it on a first deposit, or for its token accounts, and the runtime refuses to sign
+//! for an address on the curve.
+
+use solana_program::{hash::hashv, pubkey::Pubkey};
+
+use crate::{MultisigConfig, Stream, ID};
+
+const PDA_MARKER: &[u8; 21] = b"ProgramDerivedAddress";
+
+/// The vault of `owner` for `bump`, exactly as the program checks it.
+pub fn vault_address(owner: &Pubkey, bump: u8) -> Pubkey {
+    derive(&[owner.as_ref()], bump)
+}
+
+/// The canonical vault of `owner` and its bump.
+pub fn find_vault_address(owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[owner.as_ref()], &ID)
+}
+
+/// The vault of the member set in `config` for `bump`, exactly as the program checks it.
+pub fn multisig_vault_address(config: &Pubkey, bump: u8) -> Pubkey {
+    derive(&[MultisigConfig::SEED, config.as_ref()], bump)
+}
+
+/// The canonical vault of the member set in `config` and its bump.
+pub fn find_multisig_vault_address(config: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MultisigConfig::SEED, config.as_ref()], &ID)
+}
+
+/// The stream from `owner` to `recipient` for `bump`, exactly as the program checks it.
+pub fn stream_address(owner: &Pubkey, recipient: &Pubkey, bump: u8) -> Pubkey {
+    derive(&[Stream::SEED, owner.as_ref(), recipient.as_ref()], bump)
+}
+
+/// The canonical stream from `owner` to `recipient` and its bump.
+pub fn find_stream_address(owner: &Pubkey, recipient: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[Stream::SEED, owner.as_ref(), recipient.as_ref()], &ID)
+}
+
+fn derive(seeds: &[&[u8]], bump: u8) -> Pubkey {
+    let bump = [bump];
+    let hash = hashv(&[seeds, &[&bump, ID.as_ref(), PDA_MARKER]].concat());
+
+    Pubkey::new_from_array(hash.to_bytes())
+}
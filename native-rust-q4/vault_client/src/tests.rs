@@ -0,0 +1,204 @@
+#[cfg(test)]
+mod tests {
+    use solana_program::{hash::Hash, instruction::AccountMeta, pubkey::Pubkey};
+
+    use crate::{
+        account::VaultAccount,
+        instruction,
+        message,
+        pda::{
+            find_multisig_vault_address, find_stream_address, find_vault_address, multisig_vault_address,
+            stream_address, vault_address,
+        },
+        Delegate, InvalidVaultData, MultisigConfig, Stream, VaultInstruction, VaultState, ID, TOKEN_PROGRAM_ID,
+    };
+
+    #[test]
+    fn pda_derivation() {
+        let owner = Pubkey::new_unique();
+
+        // The program's hash agrees with the runtime on the canonical bump
+        let (vault, bump) = find_vault_address(&owner);
+        assert_eq!(vault_address(&owner, bump), vault);
+        assert_eq!(Pubkey::create_program_address(&[owner.as_ref(), &[bump]], &ID), Ok(vault));
+        assert_ne!(vault_address(&owner, bump.wrapping_sub(1)), vault);
+
+        let (multisig_vault, bump) = find_multisig_vault_address(&owner);
+        assert_eq!(multisig_vault_address(&owner, bump), multisig_vault);
+        assert_ne!(multisig_vault, vault);
+
+        let recipient = Pubkey::new_unique();
+        let (stream, bump) = find_stream_address(&owner, &recipient);
+        assert_eq!(stream_address(&owner, &recipient, bump), stream);
+        assert_ne!(find_stream_address(&recipient, &owner).0, stream);
+    }
+
+    #[test]
+    fn instruction_builders() {
+        let owner = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let (vault, bump) = find_vault_address(&owner);
+
+        let decode = |instruction: &solana_program::instruction::Instruction| {
+            assert_eq!(instruction.program_id, ID);
+            VaultInstruction::try_from(&instruction.data[..]).unwrap()
+        };
+
+        let withdraw = instruction::withdraw(&owner, 7);
+        assert_eq!(decode(&withdraw), VaultInstruction::Withdraw { lamports: 7, bump });
+        assert_eq!(withdraw.accounts, [AccountMeta::new(owner, true), AccountMeta::new(vault, false)]);
+
+        let keep = instruction::withdraw_keep_rent_exempt(&owner, 7);
+        assert_eq!(decode(&keep), VaultInstruction::WithdrawKeepRentExempt { lamports: 7, bump });
+
+        let withdraw_to = instruction::withdraw_to(&owner, &other, 7);
+        assert_eq!(decode(&withdraw_to), VaultInstruction::Withdraw { lamports: 7, bump });
+        assert_eq!(
+            withdraw_to.accounts,
+            [AccountMeta::new(owner, true), AccountMeta::new(vault, false), AccountMeta::new(other, false)]
+        );
+        let keep = instruction::withdraw_to_keep_rent_exempt(&owner, &other, 7);
+        assert_eq!(decode(&keep), VaultInstruction::WithdrawKeepRentExempt { lamports: 7, bump });
+
+        let delegated = instruction::withdraw_delegated(&other, &owner, &owner, 7);
+        assert_eq!(decode(&delegated), VaultInstruction::Withdraw { lamports: 7, bump });
+        assert_eq!(
+            delegated.accounts,
+            [AccountMeta::new(other, true), AccountMeta::new(vault, false), AccountMeta::new(owner, false)]
+        );
+        let delegated = instruction::withdraw_delegated_keep_rent_exempt(&other, &owner, &owner, 7);
+        assert_eq!(decode(&delegated), VaultInstruction::WithdrawKeepRentExempt { lamports: 7, bump });
+        assert_eq!(delegated.accounts.len(), 3);
+
+        let deposit = instruction::deposit(&other, &owner, 7);
+        assert_eq!(decode(&deposit), VaultInstruction::Deposit { lamports: 7, bump, owner: owner.to_bytes() });
+        assert_eq!(deposit.accounts[..2], [AccountMeta::new(other, true), AccountMeta::new(vault, false)]);
+
+        assert_eq!(decode(&instruction::close(&owner)), VaultInstruction::Close { bump });
+        assert_eq!(
+            decode(&instruction::set_lock(&owner, -1, 7)),
+            VaultInstruction::SetLock { unlock_timestamp: -1, unlock_slot: 7, bump }
+        );
+        assert_eq!(
+            decode(&instruction::set_rate_limit(&owner, 7)),
+            VaultInstruction::SetRateLimit { limit: 7, bump }
+        );
+
+        let delegate = Delegate { key: other.to_bytes(), allowance: 7, expires_at: 0 };
+        assert_eq!(
+            decode(&instruction::set_delegate(&owner, delegate)),
+            VaultInstruction::SetDelegate { delegate, bump }
+        );
+        assert_eq!(
+            decode(&instruction::revoke_delegate(&owner, &other)),
+            VaultInstruction::RevokeDelegate { delegate: other.to_bytes(), bump }
+        );
+
+        let deposit_token = instruction::deposit_token(&other, &other, &vault, &owner, 7);
+        assert_eq!(
+            decode(&deposit_token),
+            VaultInstruction::DepositToken { amount: 7, bump, owner: owner.to_bytes() }
+        );
+        assert_eq!(deposit_token.accounts[3], AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false));
+
+        let withdraw_token = instruction::withdraw_token(&owner, &vault, &other, 7);
+        assert_eq!(decode(&withdraw_token), VaultInstruction::WithdrawToken { amount: 7, bump });
+        assert_eq!(withdraw_token.accounts[..2], [AccountMeta::new(owner, true), AccountMeta::new(vault, false)]);
+    }
+
+    #[test]
+    fn multisig_builders() {
+        let payer = Pubkey::new_unique();
+        let config = Pubkey::new_unique();
+        let members = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let multisig = MultisigConfig::new(2, &members.map(|member| member.to_bytes())).unwrap();
+        let (vault, bump) = find_multisig_vault_address(&config);
+
+        let [create_account, create] = instruction::create_multisig(&payer, &config, multisig, 1_000_000);
+        assert_eq!(create_account.accounts[1], AccountMeta::new(config, true));
+        assert_eq!(VaultInstruction::try_from(&create.data[..]), Ok(VaultInstruction::CreateMultisig { config: multisig }));
+        assert_eq!(create.accounts, [AccountMeta::new(config, true)]);
+
+        let rotate = instruction::rotate_members(&config, &members, multisig);
+        assert_eq!(VaultInstruction::try_from(&rotate.data[..]), Ok(VaultInstruction::RotateMembers { config: multisig }));
+        assert_eq!(rotate.accounts[1..], members.map(|member| AccountMeta::new_readonly(member, true)));
+
+        let withdraw = instruction::withdraw_multisig(&config, &payer, &members, 7);
+        assert_eq!(VaultInstruction::try_from(&withdraw.data[..]), Ok(VaultInstruction::WithdrawMultisig { lamports: 7, bump }));
+        assert_eq!(withdraw.accounts[1], AccountMeta::new(vault, false));
+        assert_eq!(withdraw.accounts.len(), 4 + members.len());
+    }
+
+    #[test]
+    fn stream_builders() {
+        let owner = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let (stream, bump) = find_stream_address(&owner, &recipient);
+        let schedule = Stream::new(7, 1, 0, 9).unwrap();
+
+        let create = instruction::create_stream(&owner, &recipient, schedule);
+        assert_eq!(
+            VaultInstruction::try_from(&create.data[..]),
+            Ok(VaultInstruction::CreateStream { recipient: recipient.to_bytes(), stream: schedule, bump })
+        );
+        assert_eq!(create.accounts[..2], [AccountMeta::new(owner, true), AccountMeta::new(stream, false)]);
+
+        let withdraw = instruction::withdraw_stream(&recipient, &owner);
+        assert_eq!(
+            VaultInstruction::try_from(&withdraw.data[..]),
+            Ok(VaultInstruction::WithdrawStream { owner: owner.to_bytes(), bump })
+        );
+        assert_eq!(withdraw.accounts, [AccountMeta::new(recipient, true), AccountMeta::new(stream, false)]);
+
+        let cancel = instruction::cancel_stream(&owner, &recipient);
+        assert_eq!(VaultInstruction::try_from(&cancel.data[..]), Ok(VaultInstruction::CancelStream { bump }));
+        assert_eq!(cancel.accounts[2], AccountMeta::new(recipient, false));
+    }
+
+    #[test]
+    fn vault_account() {
+        assert_eq!(VaultAccount::decode(&[]), Ok(VaultAccount::default()));
+        assert_eq!(VaultAccount::decode(&[0; 17]), Err(InvalidVaultData));
+
+        let state = VaultState { unlock_timestamp: -1, limit: 7, ..Default::default() };
+        let delegates = [
+            Delegate { key: [1; 32], allowance: 7, expires_at: 0 },
+            Delegate { key: [2; 32], allowance: 8, expires_at: 9 },
+        ];
+
+        let mut data = vec![0; VaultState::LEN + 2 * Delegate::LEN];
+        state.store(&mut data);
+        for (chunk, delegate) in data[VaultState::LEN..].chunks_exact_mut(Delegate::LEN).zip(&delegates) {
+            delegate.store(chunk);
+        }
+
+        let vault = VaultAccount::decode(&data).unwrap();
+        assert_eq!(vault.state, state);
+        assert_eq!(vault.delegates, delegates);
+        assert_eq!(vault.delegate(&Pubkey::new_from_array([2; 32])), Some(&delegates[1]));
+        assert_eq!(vault.delegate(&Pubkey::new_from_array([3; 32])), None);
+
+        // Only a lock
+        let vault = VaultAccount::decode(&data[..VaultState::LOCK_LEN]).unwrap();
+        assert_eq!(vault.state, VaultState { unlock_timestamp: -1, ..Default::default() });
+        assert!(vault.delegates.is_empty());
+    }
+
+    #[test]
+    fn transaction_message() {
+        let payer = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let blockhash = Hash::new_unique();
+
+        let message = message(
+            &[instruction::deposit(&payer, &owner, 7), instruction::set_lock(&owner, 0, 7)],
+            &payer,
+            blockhash,
+        );
+
+        assert_eq!(message.recent_blockhash, blockhash);
+        assert_eq!(message.account_keys[0], payer);
+        assert_eq!(message.header.num_required_signatures, 2);
+        assert_eq!(message.instructions.len(), 2);
+    }
+}